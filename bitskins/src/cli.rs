@@ -0,0 +1,144 @@
+//! Command-line interface for running the bot and for one-off manual operations.
+use anyhow::{ensure, Result};
+use bitskins::trader::Trader;
+use clap::{Parser, Subcommand};
+use common::cli::GlobalArgs;
+use common::Money;
+use log::info;
+use serde::Serialize;
+use std::fmt::Debug;
+
+#[derive(Parser)]
+#[command(about = "BitSkins trading bot")]
+pub struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the bot (the default)
+    Run,
+    /// Check the configuration, the database and the API, then exit
+    Preflight,
+    /// Sync skins, sales, market items, offers and the balance into the database
+    Sync,
+    /// Show the price statistics of a skin
+    Stats { skin_id: i32 },
+    /// Buy a market item at its current price, skipping the profitability checks
+    Buy { item_id: i32 },
+    /// List the items in the inventory
+    ListInventory,
+    /// Update the prices of our offers
+    Reprice,
+    /// Take one of our offers off the market
+    Delist { item_id: i32 },
+    /// Show the balance of the account
+    Balance,
+    /// Show the most recent runs of a scheduled job, e.g. `sync` or `<account>/buy`
+    JobRuns {
+        name: String,
+        /// Number of runs to show
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+    },
+}
+
+#[derive(Serialize, Debug)]
+struct Deal {
+    item_id: i32,
+    skin_id: i32,
+    price: Money,
+}
+
+#[derive(Serialize, Debug)]
+struct Balance {
+    account: String,
+    balance: Money,
+}
+
+impl Cli {
+    /// Runs a one-off `command` and prints its result.
+    pub async fn execute(&self, command: &Command) -> Result<()> {
+        let trader = self.trader().await?;
+        match command {
+            Command::Run | Command::Preflight => unreachable!("handled by main"),
+            Command::Sync => {
+                trader.updater.sync_data().await?;
+                self.print(&"Synced")
+            }
+            Command::Stats { skin_id } => {
+                self.print(&trader.db.get_price_statistics(*skin_id).await?)
+            }
+            Command::Buy { item_id } => {
+                let item = trader.http.fetch_market_item(&item_id.to_string()).await?;
+                let deal = Deal {
+                    item_id: *item_id,
+                    skin_id: item.skin_id,
+                    price: item.price,
+                };
+                if self.global.dry_run {
+                    info!("Dry run, not buying");
+                } else {
+                    trader
+                        .buy_item(deal.item_id, deal.price, deal.skin_id)
+                        .await?;
+                }
+                self.print(&deal)
+            }
+            Command::ListInventory => {
+                let listings = trader.updater.get_inventory_listings().await?;
+                if self.global.dry_run {
+                    info!("Dry run, not listing");
+                } else {
+                    trader.updater.list_inventory_items().await?;
+                }
+                self.print(&listings)
+            }
+            Command::Reprice => {
+                let updates = trader.updater.get_offer_price_updates().await?;
+                if self.global.dry_run {
+                    info!("Dry run, not repricing");
+                } else {
+                    trader.updater.update_offer_prices().await?;
+                }
+                self.print(&updates)
+            }
+            Command::Delist { item_id } => {
+                if !trader.db.is_in_offers(*item_id).await? {
+                    info!("Item {item_id} is not among our known offers");
+                }
+                if self.global.dry_run {
+                    info!("Dry run, not delisting");
+                } else {
+                    let delisted = trader.http.delist_item(&item_id.to_string()).await?;
+                    ensure!(delisted, "Item {item_id} could not be delisted");
+                    trader.updater.sync_offered_items().await?;
+                }
+                self.print(item_id)
+            }
+            Command::Balance => self.print(&Balance {
+                balance: trader.http.fetch_balance().await?,
+                account: trader.account.name,
+            }),
+            Command::JobRuns { name, limit } => {
+                self.print(&trader.db.get_job_runs(name, *limit).await?)
+            }
+        }
+    }
+
+    /// The trader of the selected account.
+    async fn trader(&self) -> Result<Trader> {
+        let traders = Trader::for_all_accounts().await?;
+        Ok(self
+            .global
+            .select("BitSkins", traders, |trader| &trader.account.name)?)
+    }
+
+    fn print<T: Serialize + Debug>(&self, value: &T) -> Result<()> {
+        Ok(self.global.print(value)?)
+    }
+}
@@ -4,7 +4,10 @@
 //! that stores information about CS:GO skins, sales, and related statistics.
 use crate::date::DateTime;
 use crate::{Error, Result};
-use sqlx::{postgres::PgPoolOptions, types::time::OffsetDateTime, Executor, PgPool};
+use common::{Money, Secret};
+use serde::Serialize;
+use sqlx::pool::PoolConnection;
+use sqlx::{postgres::PgPoolOptions, types::time::OffsetDateTime, Executor, PgPool, Postgres};
 use std::collections::HashSet;
 use std::env;
 
@@ -30,7 +33,7 @@ pub struct Sale {
     pub paint_index: Option<i32>,
     pub paint_seed: Option<i32>,
     pub phase_id: Option<i32>,
-    pub price: f64,
+    pub price: Money,
 }
 
 pub struct Sticker {
@@ -48,7 +51,7 @@ pub struct Sticker {
     pub rotation: Option<f64>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Stats {
     pub skin_id: i32,
     pub mean_price: Option<f64>,
@@ -56,12 +59,130 @@ pub struct Stats {
     pub price_slope: Option<f64>,
 }
 
+/// What we know about the outcome of a purchase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "purchase_status", rename_all = "lowercase")]
+pub enum PurchaseStatus {
+    /// The request was sent but its outcome is unknown
+    Pending,
+    Confirmed,
+    Failed,
+}
+
+#[derive(Clone, Debug)]
+pub struct Purchase {
+    pub item_id: i32,
+    pub account_id: i32,
+    pub skin_id: i32,
+    pub price: Money,
+    pub status: PurchaseStatus,
+    pub created_at: OffsetDateTime,
+}
+
+/// The account configured through `BITSKIN_API_KEY` before multiple accounts were supported
+pub const DEFAULT_ACCOUNT_ID: i32 = 1;
+
+/// A BitSkins account we trade with.
+#[derive(Clone, Debug)]
+pub struct TradingAccount {
+    pub id: i32,
+    pub name: String,
+    /// Name of the secret holding the API key
+    pub credentials: String,
+    /// Most we pay for a single item
+    pub max_purchase_price: Option<Money>,
+    /// Most we spend on purchases within a day
+    pub daily_spend_limit: Option<Money>,
+}
+
+impl TradingAccount {
+    /// Resolves the API key of the account through the secrets provider.
+    pub fn api_key(&self) -> common::secrets::Result<Secret> {
+        common::secrets::get(&self.credentials)
+    }
+}
+
+impl Default for TradingAccount {
+    fn default() -> Self {
+        Self {
+            id: DEFAULT_ACCOUNT_ID,
+            name: "default".to_string(),
+            credentials: "BITSKIN_API_KEY".to_string(),
+            max_purchase_price: None,
+            daily_spend_limit: None,
+        }
+    }
+}
+
+/// Outcome of a run of a scheduled job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "job_outcome", rename_all = "lowercase")]
+pub enum JobOutcome {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Persistent state of a scheduled job, kept across restarts.
+#[derive(Clone, Debug)]
+pub struct JobState {
+    pub name: String,
+    pub last_run_at: Option<OffsetDateTime>,
+    pub last_outcome: Option<JobOutcome>,
+    pub next_run_at: Option<OffsetDateTime>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct JobRun {
+    pub id: i32,
+    pub job_name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+    pub outcome: JobOutcome,
+    pub error: Option<String>,
+}
+
+/// Exclusive right to run a job, held as a Postgres advisory lock.
+///
+/// The lock lives as long as its connection, so it is also released if the process dies
+/// mid-run. A lock dropped without being released, e.g. when its job panics, closes the
+/// connection instead of returning it to the pool still holding the lock.
+pub struct JobLock {
+    conn: Option<PoolConnection<Postgres>>,
+    name: String,
+}
+
+impl JobLock {
+    pub async fn release(mut self) -> Result<()> {
+        let Some(mut conn) = self.conn.take() else {
+            return Ok(());
+        };
+        sqlx::query!("SELECT pg_advisory_unlock(hashtext($1))", self.name)
+            .fetch_one(&mut *conn)
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for JobLock {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            log::warn!("Lock of job {} dropped without release", self.name);
+            // Dropping the detached connection ends its session and with it the lock
+            drop(conn.detach());
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MarketItem {
     pub created_at: DateTime,
     pub id: i32,
     pub skin_id: i32,
-    pub price: f64,
+    pub price: Money,
     pub float_value: Option<f64>,
 }
 
@@ -87,7 +208,16 @@ impl Database {
             .connect(&env::var("DATABASE_URL")?)
             .await?;
         log::info!("Connected to database");
-        Ok(Self { pool })
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Wraps an existing connection pool, e.g. one provided by `sqlx::test`.
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
     }
 
     pub async fn calculate_price_statistics(&self) -> Result<Vec<Stats>> {
@@ -208,7 +338,7 @@ impl Database {
             sale.paint_index,
             sale.paint_seed,
             sale.phase_id,
-            sale.price
+            sale.price.mills()
         )
         .fetch_one(&self.pool)
         .await?;
@@ -237,7 +367,7 @@ impl Database {
             *item.created_at,
             item.id,
             item.skin_id,
-            item.price,
+            item.price.mills(),
             item.float_value
         )
         .execute(executor)
@@ -268,14 +398,14 @@ impl Database {
         Ok(())
     }
 
-    pub async fn update_market_item_price(&self, item_id: i32, price: f64) -> Result<()> {
+    pub async fn update_market_item_price(&self, item_id: i32, price: Money) -> Result<()> {
         let result = sqlx::query!(
             r#"
             UPDATE MarketItem
             SET price = $1
             WHERE id = $2
             "#,
-            price,
+            price.mills(),
             item_id
         )
         .execute(&self.pool)
@@ -545,15 +675,20 @@ impl Database {
         Ok(())
     }
 
-    pub async fn insert_offer(&self, item: MarketItem) -> Result<()> {
+    pub async fn insert_offer(&self, account_id: i32, item: MarketItem) -> Result<()> {
         let item_id = item.id;
         self.insert_market_item(item).await?;
-        sqlx::query!("INSERT INTO Offer (item_id) VALUES ($1)", item_id)
-            .execute(&self.pool)
-            .await?;
+        sqlx::query!(
+            "INSERT INTO Offer (item_id, account_id) VALUES ($1, $2)",
+            item_id,
+            account_id
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
+    /// Offers of all of our accounts on `skin_id`.
     pub async fn get_offers(&self, skin_id: i32) -> Result<Vec<MarketItem>> {
         Ok(sqlx::query_as!(
             MarketItem,
@@ -564,10 +699,11 @@ impl Database {
         .await?)
     }
 
-    pub async fn get_all_offers(&self) -> Result<Vec<MarketItem>> {
+    pub async fn get_account_offers(&self, account_id: i32) -> Result<Vec<MarketItem>> {
         Ok(sqlx::query_as!(
             MarketItem,
-            "SELECT * FROM MarketItem WHERE id IN (SELECT item_id FROM Offer)"
+            "SELECT * FROM MarketItem WHERE id IN (SELECT item_id FROM Offer WHERE account_id = $1)",
+            account_id
         )
         .fetch_all(&self.pool)
         .await?)
@@ -580,20 +716,28 @@ impl Database {
         Ok(())
     }
 
+    /// Whether `item_id` is listed by any of our accounts.
     pub async fn is_in_offers(&self, item_id: i32) -> Result<bool> {
+        Ok(self.get_offer_account(item_id).await?.is_some())
+    }
+
+    /// The account listing `item_id`, if it is one of our offers.
+    pub async fn get_offer_account(&self, item_id: i32) -> Result<Option<i32>> {
         Ok(
-            sqlx::query_scalar!("SELECT 1 FROM Offer WHERE item_id = $1", item_id)
+            sqlx::query_scalar!("SELECT account_id FROM Offer WHERE item_id = $1", item_id)
                 .fetch_optional(&self.pool)
-                .await?
-                .is_some(),
+                .await?,
         )
     }
 
-    pub async fn delete_all_offers(&self) -> Result<()> {
-        self.flush_table("Offer").await
+    pub async fn delete_account_offers(&self, account_id: i32) -> Result<()> {
+        sqlx::query!("DELETE FROM Offer WHERE account_id = $1", account_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
-    pub async fn get_cheapest_price(&self, skin_id: i32) -> Result<Option<f64>> {
+    pub async fn get_cheapest_price(&self, skin_id: i32) -> Result<Option<Money>> {
         Ok(sqlx::query_scalar!(
             r#"
             SELECT mi.price
@@ -610,19 +754,260 @@ impl Database {
             skin_id
         )
         .fetch_optional(&self.pool)
+        .await?
+        .map(Money::from_mills))
+    }
+
+    pub async fn update_balance(&self, account_id: i32, balance: Money) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO Account (account_id, balance) VALUES ($1, $2)
+            ON CONFLICT (account_id) DO UPDATE SET balance = EXCLUDED.balance
+            "#,
+            account_id,
+            balance.mills()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_balance(&self, account_id: i32) -> Result<Money> {
+        Ok(sqlx::query_scalar!(
+            "SELECT balance FROM Account WHERE account_id = $1",
+            account_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Money::from_mills)
+        .unwrap_or_default())
+    }
+
+    /// Enabled BitSkins accounts, in the order they were added.
+    pub async fn get_trading_accounts(&self) -> Result<Vec<TradingAccount>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, credentials, max_purchase_price, daily_spend_limit
+            FROM trading_account
+            WHERE market = 'bitskins' AND enabled
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| TradingAccount {
+                id: row.id,
+                name: row.name,
+                credentials: row.credentials,
+                max_purchase_price: row.max_purchase_price.map(Money::from_mills),
+                daily_spend_limit: row.daily_spend_limit.map(Money::from_mills),
+            })
+            .collect())
+    }
+
+    /// Total price of the purchases of `account_id` in the last day that did not fail.
+    pub async fn get_daily_spend(&self, account_id: i32) -> Result<Money> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(price), 0)::BIGINT AS "spend!"
+            FROM Purchase
+            WHERE account_id = $1
+              AND status != 'failed'
+              AND created_at > now() - INTERVAL '1 day'
+            "#,
+            account_id
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .into())
+    }
+
+    /// Records that we are about to buy `item_id`.
+    ///
+    /// Returns false if the item was already bought or its purchase is still unresolved, in
+    /// which case it must not be bought again.
+    pub async fn start_purchase(
+        &self,
+        account_id: i32,
+        item_id: i32,
+        skin_id: i32,
+        price: Money,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO Purchase (item_id, skin_id, price, status, account_id)
+            VALUES ($1, $2, $3, 'pending', $4)
+            ON CONFLICT (item_id) DO UPDATE
+            SET price = EXCLUDED.price, status = 'pending', account_id = EXCLUDED.account_id,
+                created_at = now(), updated_at = now()
+            WHERE Purchase.status = 'failed'
+            "#,
+            item_id,
+            skin_id,
+            price.mills(),
+            account_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    pub async fn set_purchase_status(&self, item_id: i32, status: PurchaseStatus) -> Result<()> {
+        sqlx::query!(
+            "UPDATE Purchase SET status = $1, updated_at = now() WHERE item_id = $2",
+            status as PurchaseStatus,
+            item_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_purchase(&self, item_id: i32) -> Result<Option<Purchase>> {
+        Ok(sqlx::query_as!(
+            Purchase,
+            r#"
+            SELECT item_id, account_id, skin_id, price, status AS "status: PurchaseStatus",
+                created_at
+            FROM Purchase
+            WHERE item_id = $1
+            "#,
+            item_id
+        )
+        .fetch_optional(&self.pool)
         .await?)
     }
 
-    pub async fn update_balance(&self, balance: f64) -> Result<()> {
-        sqlx::query!("UPDATE Account SET balance = $1", balance)
-            .execute(&self.pool)
+    pub async fn get_pending_purchases(&self, account_id: i32) -> Result<Vec<Purchase>> {
+        Ok(sqlx::query_as!(
+            Purchase,
+            r#"
+            SELECT item_id, account_id, skin_id, price, status AS "status: PurchaseStatus",
+                created_at
+            FROM Purchase
+            WHERE status = 'pending' AND account_id = $1
+            ORDER BY created_at
+            "#,
+            account_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Takes the lock of job `name`, or returns `None` if another run holds it.
+    pub async fn try_lock_job(&self, name: &str) -> Result<Option<JobLock>> {
+        let mut conn = self.pool.acquire().await?;
+        let locked = sqlx::query_scalar!("SELECT pg_try_advisory_lock(hashtext($1))", name)
+            .fetch_one(&mut *conn)
             .await?;
+        Ok(locked.unwrap_or_default().then(|| JobLock {
+            conn: Some(conn),
+            name: name.to_string(),
+        }))
+    }
+
+    /// Records the start of a run of job `name` and returns the ID of the run.
+    pub async fn start_job_run(&self, name: &str) -> Result<i32> {
+        sqlx::query!(
+            r#"
+            INSERT INTO ScheduledJob (name, last_run_at, last_outcome)
+            VALUES ($1, now(), 'running')
+            ON CONFLICT (name) DO UPDATE
+            SET last_run_at = now(), last_outcome = 'running'
+            "#,
+            name
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(sqlx::query_scalar!(
+            "INSERT INTO ScheduledJobRun (job_name) VALUES ($1) RETURNING id",
+            name
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Records the outcome of run `run_id` and, if given, when its job is due next.
+    pub async fn finish_job_run(
+        &self,
+        run_id: i32,
+        error: Option<&str>,
+        next_run_at: Option<OffsetDateTime>,
+    ) -> Result<()> {
+        let outcome = match error {
+            Some(_) => JobOutcome::Failed,
+            None => JobOutcome::Succeeded,
+        };
+        let job_name = sqlx::query_scalar!(
+            r#"
+            UPDATE ScheduledJobRun
+            SET finished_at = now(), outcome = $1, error = $2
+            WHERE id = $3
+            RETURNING job_name
+            "#,
+            outcome as JobOutcome,
+            error,
+            run_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE ScheduledJob
+            SET last_outcome = $1, next_run_at = COALESCE($2, next_run_at)
+            WHERE name = $3
+            "#,
+            outcome as JobOutcome,
+            next_run_at,
+            job_name
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
-    pub async fn get_balance(&self) -> Result<f64> {
-        Ok(sqlx::query_scalar!("SELECT balance FROM Account")
-            .fetch_one(&self.pool)
-            .await?)
+    pub async fn get_job(&self, name: &str) -> Result<Option<JobState>> {
+        Ok(sqlx::query_as!(
+            JobState,
+            r#"
+            SELECT name, last_run_at, last_outcome AS "last_outcome: JobOutcome", next_run_at
+            FROM ScheduledJob
+            WHERE name = $1
+            "#,
+            name
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Whether job `name` missed a run, which includes never having run at all.
+    pub async fn is_job_overdue(&self, name: &str) -> Result<bool> {
+        Ok(self
+            .get_job(name)
+            .await?
+            .and_then(|job| job.next_run_at)
+            .is_none_or(|next_run_at| next_run_at <= OffsetDateTime::now_utc()))
+    }
+
+    /// Most recent runs of job `name`, newest first.
+    pub async fn get_job_runs(&self, name: &str, limit: i64) -> Result<Vec<JobRun>> {
+        Ok(sqlx::query_as!(
+            JobRun,
+            r#"
+            SELECT id, job_name, started_at, finished_at, outcome AS "outcome: JobOutcome", error
+            FROM ScheduledJobRun
+            WHERE job_name = $1
+            ORDER BY started_at DESC, id DESC
+            LIMIT $2
+            "#,
+            name,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?)
     }
 }
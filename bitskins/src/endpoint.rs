@@ -31,3 +31,11 @@ pub enum Endpoint {
     #[strum(serialize = "/wallet/transaction/list")]
     Transactions,
 }
+
+impl Endpoint {
+    /// Whether requests to the endpoint are idempotent in the sense of
+    /// [`common::RetryPolicy::should_retry`]. Purchases are not.
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Self::BuySingle)
+    }
+}
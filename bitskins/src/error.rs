@@ -14,11 +14,17 @@ pub enum Error {
     #[error("EnvVar error: {0}")]
     EnvVar(#[from] env::VarError),
 
+    #[error("Secret error: {0}")]
+    Secret(#[from] common::secrets::Error),
+
+    #[error("No API key configured for the client")]
+    MissingApiKey,
+
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
 
     #[error("Failed to deserialize response: {0}")]
-    Deserialize(String),
+    Deserialize(#[from] common::quarantine::ParseError),
 
     #[error("Market item {0} not present in table")]
     MarketItemDeleteFailed(i32),
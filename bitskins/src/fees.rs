@@ -0,0 +1,12 @@
+//! BitSkins' fees on sales.
+use common::{FeeSchedule, Money};
+
+/// Share of the sale price BitSkins keeps
+const SALES_FEE: f64 = 0.1;
+/// Least fee BitSkins charges on a sale
+const MIN_FEE: Money = Money::from_cents(1);
+
+/// The fees we pay when selling an item on BitSkins.
+pub fn schedule() -> FeeSchedule {
+    FeeSchedule::flat(SALES_FEE, MIN_FEE)
+}
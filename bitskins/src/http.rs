@@ -1,29 +1,58 @@
 use crate::date::DateTime;
 use crate::endpoint::Endpoint;
 use crate::{Error, Result};
+use common::drift::ResponseSample;
+use common::lenient::{self, List};
+use common::money::serde_mills;
+use common::quarantine;
+use common::{Failure, Limit, LimiterStats, Money, RateLimiter, RetryPolicy, Secret};
 use reqwest::{RequestBuilder, Response};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::cmp::max;
-use std::env;
+use std::collections::HashMap;
 use std::future::Future;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
-use tokio::time::{sleep, Instant};
+use tokio::time::sleep;
 
 const BASE_URL: &str = "https://api.bitskins.com";
 const MAX_LIMIT: usize = 500;
 const MAX_OFFSET: usize = 2000;
 pub const CS2_APP_ID: i32 = 730;
 const SPEED: f64 = 0.5; // Fraction of the default rate limit
+const DEFAULT_RATE: f64 = 5.0;
+const SEARCH_RATE: f64 = 1.0;
 
 const STATUS_SELLING: usize = 2;
 const STATUS_INVENTORY: usize = 4;
 
+/// Endpoints sharing a rate limit budget on BitSkins' side
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestGroup {
+    Search,
+    Other,
+}
+
+impl RequestGroup {
+    const LIMITS: [(Self, Limit); 2] = [
+        (Self::Search, Limit::new(SEARCH_RATE * SPEED, 1)),
+        (Self::Other, Limit::new(DEFAULT_RATE * SPEED, 2)),
+    ];
+
+    /// Groups a request to `endpoint` counts against. Searches are also part of the general limit.
+    fn for_endpoint(endpoint: Endpoint) -> &'static [Self] {
+        match endpoint {
+            Endpoint::SearchGet | Endpoint::SearchCsgo | Endpoint::Inventory => {
+                &[Self::Search, Self::Other]
+            }
+            _ => &[Self::Other],
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Balance {
-    pub balance: i32,
+    #[serde(with = "serde_mills")]
+    pub balance: Money,
 }
 
 #[derive(Clone, Deserialize)]
@@ -31,6 +60,7 @@ pub struct Skin {
     pub id: i32,
     pub name: String,
     pub class_id: String,
+    #[serde(default, deserialize_with = "lenient::option_number")]
     pub suggested_price: Option<i32>,
 }
 
@@ -42,7 +72,8 @@ pub(crate) struct Sale {
     pub paint_index: Option<i32>,
     pub paint_seed: Option<i32>,
     pub phase_id: Option<i32>,
-    pub price: f64,
+    #[serde(with = "serde_mills")]
+    pub price: Money,
     pub stickers: Option<Vec<Sticker>>,
 }
 
@@ -85,7 +116,8 @@ pub struct MarketItem {
     pub paint_index: Option<i32>,
     pub paint_seed: Option<i32>,
     pub phase_id: Option<i32>,
-    pub price: f64,
+    #[serde(with = "serde_mills")]
+    pub price: Money,
     pub quality_id: i32,
     pub skin_id: i32,
     pub skin_status: i32,
@@ -93,6 +125,7 @@ pub struct MarketItem {
     pub status: i32,
     pub sticker_counter: i32,
     pub stickers: Option<Vec<Sticker>>,
+    #[serde(default, deserialize_with = "lenient::option_number")]
     pub suggested_price: Option<i32>,
     pub tradehold: i32,
     pub type_id: Option<i8>,
@@ -100,8 +133,9 @@ pub struct MarketItem {
 }
 
 #[derive(Deserialize)]
-#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
 pub struct ListData<T> {
+    #[serde(deserialize_with = "lenient::list")]
     pub list: Vec<T>,
     pub counter: DataCounter,
 }
@@ -114,11 +148,12 @@ pub struct DataCounter {
 #[derive(Serialize, Debug)]
 pub struct ItemPrice {
     pub id: String,
-    pub price: u32,
+    #[serde(with = "serde_mills")]
+    pub price: Money,
 }
 
 impl ItemPrice {
-    pub fn new(id: String, price: u32) -> Self {
+    pub fn new(id: String, price: Money) -> Self {
         Self { id, price }
     }
 }
@@ -142,6 +177,13 @@ pub struct Transaction {
     pub type_: u32,
 }
 
+impl Transaction {
+    /// The market item a purchase or sale transaction refers to.
+    pub fn item_id(&self) -> Option<&str> {
+        self.extras.as_ref()?.get("id")?.as_str()
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Receipt {
     pub receipt_id: String,
@@ -156,55 +198,98 @@ impl Default for HttpClient {
 #[derive(Clone)]
 pub struct HttpClient {
     client: reqwest::Client,
-    /// Prevents a thread from making a request before another has finished
-    lock: Arc<Mutex<()>>,
-    request_ok: Arc<Mutex<Instant>>,
-    market_request_ok: Arc<Mutex<Instant>>,
+    base_url: Arc<str>,
+    limiter: RateLimiter<RequestGroup>,
+    retry_policy: RetryPolicy,
+    api_key: Option<Secret>,
 }
 
 impl HttpClient {
     pub fn new() -> Self {
+        Self::with_base_url(BASE_URL)
+    }
+
+    /// Creates a client that sends every request to `base_url` instead of the live API.
+    pub fn with_base_url(base_url: &str) -> Self {
         Self {
             client: reqwest::Client::new(),
-            lock: Arc::new(Mutex::new(())),
-            request_ok: Arc::new(Mutex::new(Instant::now())),
-            market_request_ok: Arc::new(Mutex::new(Instant::now())),
+            base_url: base_url.trim_end_matches('/').into(),
+            limiter: RateLimiter::new(RequestGroup::LIMITS),
+            retry_policy: RetryPolicy::default(),
+            api_key: None,
         }
     }
 
+    /// Authenticates every request with `api_key`.
+    pub fn with_api_key(mut self, api_key: Secret) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Replaces the policy deciding how often and how quickly failed requests are retried.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Wait-time statistics for each endpoint group.
+    pub fn rate_limit_stats(&self) -> HashMap<RequestGroup, LimiterStats> {
+        self.limiter.stats()
+    }
+
     async fn process_request(
         &self,
         builder: RequestBuilder,
         endpoint: Endpoint,
     ) -> Result<Response> {
-        let _lock = self.lock.lock().await;
+        let groups = RequestGroup::for_endpoint(endpoint);
+        let mut attempt = 0;
 
         loop {
-            let mut request_ok = self.request_ok.lock().await;
-            if endpoint.to_string().starts_with("/market/search") {
-                let mut market_request_ok = self.market_request_ok.lock().await;
-                sleep(max(*market_request_ok, *request_ok) - Instant::now()).await;
-                *market_request_ok =
-                    Instant::now() + Duration::from_millis((1000.0 / SPEED) as u64);
-            } else {
-                sleep(*request_ok - Instant::now()).await;
-            }
-            *request_ok = Instant::now() + Duration::from_millis((200.0 / SPEED) as u64);
-
-            let response = builder.try_clone().unwrap().send().await?;
-            let status = response.status();
+            attempt += 1;
 
-            if status.is_success() {
-                return Ok(response);
-            } else if status.is_server_error() {
-                return Err(Error::InternalService(endpoint));
+            for group in groups {
+                self.limiter.acquire(*group).await;
             }
 
-            log::warn!(
-                "Request failed with status {status} for endpoint {endpoint}. Retrying in 5 seconds"
-            );
+            let (failure, error) = match builder.try_clone().unwrap().send().await {
+                Ok(response) => {
+                    let status = response.status();
+
+                    for group in groups {
+                        self.limiter.record(*group, status, response.headers());
+                    }
+
+                    if status.is_success() {
+                        return Ok(response);
+                    }
+
+                    let error = if status.is_server_error() {
+                        Error::InternalService(endpoint)
+                    } else {
+                        Error::StatusCode(status)
+                    };
+                    (Failure::Status(status), error)
+                }
+                Err(e) => (
+                    Failure::Transport {
+                        sent: !e.is_connect(),
+                    },
+                    e.into(),
+                ),
+            };
+
+            if !self
+                .retry_policy
+                .should_retry(attempt, &failure, endpoint.is_idempotent())
+            {
+                log::warn!("Request to {endpoint} failed after {attempt} attempt(s): {failure}");
+                return Err(error);
+            }
 
-            sleep(Duration::from_secs(5)).await;
+            let delay = self.retry_policy.backoff(attempt);
+            log::warn!("Request to {endpoint} failed ({failure}). Retrying in {delay:?}");
+            sleep(delay).await;
         }
     }
 
@@ -213,28 +298,30 @@ impl HttpClient {
         builder: RequestBuilder,
         endpoint: Endpoint,
     ) -> Result<T> {
+        let api_key = self.api_key.as_ref().ok_or(Error::MissingApiKey)?;
         let response = self
-            .process_request(
-                builder.header("x-apikey", env::var("BITSKIN_API_KEY")?),
-                endpoint,
-            )
+            .process_request(builder.header("x-apikey", api_key.expose()), endpoint)
             .await?;
 
         let text = response.text().await?;
-        serde_json::from_str(&text).map_err(|_| Error::Deserialize(text))
+        Ok(quarantine::from_str(
+            "bitskins",
+            &endpoint.to_string(),
+            &text,
+        )?)
     }
 
     async fn post<T: DeserializeOwned>(&self, endpoint: Endpoint, payload: Value) -> Result<T> {
         let builder = self
             .client
-            .post(format!("{BASE_URL}{endpoint}"))
+            .post(format!("{}{endpoint}", self.base_url))
             .json(&payload);
 
         self.request(builder, endpoint).await
     }
 
     async fn get<T: DeserializeOwned>(&self, endpoint: Endpoint) -> Result<T> {
-        let builder = self.client.get(format!("{BASE_URL}{endpoint}"));
+        let builder = self.client.get(format!("{}{endpoint}", self.base_url));
         self.request(builder, endpoint).await
     }
 
@@ -290,25 +377,25 @@ impl HttpClient {
         .await
     }
 
-    pub async fn update_price(&self, app_id: i32, item_id: &str, price: i32) -> Result<()> {
+    pub async fn update_price(&self, app_id: i32, item_id: &str, price: Money) -> Result<()> {
         self.post(
             Endpoint::UpdatePriceSingle,
             json!({
                 "app_id": app_id,
                 "id": item_id,
-                "price": price,
+                "price": price.mills(),
             }),
         )
         .await
     }
 
-    pub async fn list_item(&self, item_id: &str, price: f64) -> Result<bool> {
+    pub async fn list_item(&self, item_id: &str, price: Money) -> Result<bool> {
         self.post(
             Endpoint::RelistSingle,
             json!({
                 "app_id": CS2_APP_ID,
                 "id": item_id,
-                "price": price.round() as u32,
+                "price": price.mills(),
             }),
         )
         .await
@@ -324,39 +411,42 @@ impl HttpClient {
         .await
     }
 
-    pub async fn fetch_balance(&self) -> Result<f64> {
+    pub async fn fetch_balance(&self) -> Result<Money> {
         Ok(self
             .post::<Balance>(Endpoint::ProfileBalance, json!({}))
             .await?
-            .balance as f64)
+            .balance)
     }
 
-    pub async fn buy_item(&self, item_id: &str, price: f64) -> Result<Receipt> {
+    pub async fn buy_item(&self, item_id: &str, price: Money) -> Result<Receipt> {
         self.post(
             Endpoint::BuySingle,
             json!({
                 "app_id": CS2_APP_ID,
                 "id": item_id,
-                "max_price": price.round() as u32,
+                "max_price": price.mills(),
             }),
         )
         .await
     }
 
     pub(crate) async fn fetch_sales(&self, skin_id: i32) -> Result<Vec<Sale>> {
-        self.post(
-            Endpoint::PricingList,
-            json!({
-                "app_id": CS2_APP_ID,
-                "skin_id": skin_id,
-                "limit": MAX_LIMIT,
-            }),
-        )
-        .await
+        let sales: List<Sale> = self
+            .post(
+                Endpoint::PricingList,
+                json!({
+                    "app_id": CS2_APP_ID,
+                    "skin_id": skin_id,
+                    "limit": MAX_LIMIT,
+                }),
+            )
+            .await?;
+        Ok(sales.0)
     }
 
     pub async fn fetch_skins(&self) -> Result<Vec<Skin>> {
-        self.get(Endpoint::Skin).await
+        let skins: List<Skin> = self.get(Endpoint::Skin).await?;
+        Ok(skins.0)
     }
 
     pub async fn fetch_market_item(&self, id: &str) -> Result<MarketItem> {
@@ -423,6 +513,62 @@ impl HttpClient {
         .await
     }
 
+    /// Raw responses of the read-only endpoints, checked against the structs they are
+    /// deserialized into. Market searches and sales are sampled for `skin_id`.
+    pub async fn sample_responses(&self, skin_id: i32) -> Result<Vec<ResponseSample>> {
+        let skins: Value = self.get(Endpoint::Skin).await?;
+        let search: Value = self
+            .post(
+                Endpoint::SearchCsgo,
+                json!({"where": {"skin_id": [skin_id]}, "limit": MAX_LIMIT, "offset": 0}),
+            )
+            .await?;
+        let owned: Value = self
+            .post(
+                Endpoint::Inventory,
+                json!({
+                    "where_mine": {"status": [STATUS_INVENTORY, STATUS_SELLING]},
+                    "limit": MAX_LIMIT,
+                    "offset": 0,
+                }),
+            )
+            .await?;
+        let transactions: Value = self
+            .post(
+                Endpoint::Transactions,
+                json!({"limit": MAX_LIMIT, "offset": 0}),
+            )
+            .await?;
+        let sales: Value = self
+            .post(
+                Endpoint::PricingList,
+                json!({"app_id": CS2_APP_ID, "skin_id": skin_id, "limit": MAX_LIMIT}),
+            )
+            .await?;
+        let balance: Value = self.post(Endpoint::ProfileBalance, json!({})).await?;
+
+        let name = |endpoint: Endpoint| endpoint.to_string();
+        Ok(vec![
+            ResponseSample::new::<Skin>(&name(Endpoint::Skin), &skins, ""),
+            ResponseSample::new::<ListData<Value>>(&name(Endpoint::SearchCsgo), &search, ""),
+            ResponseSample::new::<MarketItem>(&name(Endpoint::SearchCsgo), &search, "/list"),
+            ResponseSample::new::<ListData<Value>>(&name(Endpoint::Inventory), &owned, ""),
+            ResponseSample::new::<MarketItem>(&name(Endpoint::Inventory), &owned, "/list"),
+            ResponseSample::new::<ListData<Value>>(
+                &name(Endpoint::Transactions),
+                &transactions,
+                "",
+            ),
+            ResponseSample::new::<Transaction>(
+                &name(Endpoint::Transactions),
+                &transactions,
+                "/list",
+            ),
+            ResponseSample::new::<Sale>(&name(Endpoint::PricingList), &sales, ""),
+            ResponseSample::new::<Balance>(&name(Endpoint::ProfileBalance), &balance, ""),
+        ])
+    }
+
     pub async fn fetch_transactions(&self) -> Result<Vec<Transaction>> {
         self.fetch_list_data(|offset| {
             self.post(
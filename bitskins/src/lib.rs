@@ -5,16 +5,24 @@ mod date;
 pub mod db;
 mod endpoint;
 mod error;
+pub mod fees;
 mod http;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod preflight;
 pub mod scheduler;
 pub mod trader;
 mod update;
 mod ws;
 
 pub use date::DateTime;
-pub use db::{Database, MarketItem, Skin, Stats};
+pub use db::{
+    Database, JobLock, JobOutcome, JobRun, JobState, MarketItem, Purchase, PurchaseStatus, Skin,
+    Stats, TradingAccount, DEFAULT_ACCOUNT_ID,
+};
+pub use endpoint::Endpoint;
 pub use error::Error;
-pub use http::{HttpClient, CS2_APP_ID};
+pub use http::{HttpClient, RequestGroup, CS2_APP_ID};
 pub use update::Updater;
 pub use ws::{Channel, WsClient, WsData};
 
@@ -1,26 +1,57 @@
-use anyhow::Result;
+mod cli;
+
+use anyhow::{bail, Result};
 use bitskins::scheduler::Scheduler;
 use bitskins::trader::Trader;
 use bitskins::WsClient;
+use clap::Parser;
+use cli::{Cli, Command};
+use futures::future::try_join_all;
 use tokio::try_join;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
     common::setup_env();
-    start_bitskins().await?;
-    Ok(())
+    if let Some(Command::Preflight) = cli.command {
+        bitskins::preflight::run().await.exit();
+    }
+    common::secrets::init()?;
+    match &cli.command {
+        None | Some(Command::Run) => start_bitskins().await,
+        Some(command) => cli.execute(command).await,
+    }
 }
 
 async fn start_bitskins() -> Result<()> {
-    let trader = Trader::new().await?;
-    let scheduler = Scheduler::new(trader.clone()).await?;
+    let traders = Trader::for_all_accounts().await?;
+    if traders.is_empty() {
+        bail!("No enabled BitSkins accounts");
+    }
+
+    let mut schedulers = Vec::new();
+    for trader in &traders {
+        let scheduler = Scheduler::new(trader.clone()).await?;
+        scheduler.schedule_tasks().await?;
+        schedulers.push(scheduler.start());
+    }
 
-    try_join!(start_ws(trader), scheduler.start())?;
+    try_join!(start_ws(traders), try_join_all(schedulers))?;
 
     Ok(())
 }
 
-async fn start_ws(trader: Trader) -> Result<()> {
-    let ws = WsClient::connect(|channel, ws_data| trader.process_data(channel, ws_data)).await?;
+/// Listens to the market with the credentials of the first account and lets every account act
+/// on each event. An item bought by one account is never bought again by another.
+async fn start_ws(traders: Vec<Trader>) -> Result<()> {
+    let api_key = traders[0].account.api_key()?;
+    let traders = &traders;
+    let ws = WsClient::connect(|channel, ws_data| async move {
+        for trader in traders {
+            trader.process_data(channel, ws_data.clone()).await;
+        }
+    })
+    .await?
+    .with_api_key(api_key);
     Ok(ws.start().await?)
 }
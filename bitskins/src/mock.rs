@@ -0,0 +1,522 @@
+//! In-process fake of the BitSkins REST and WebSocket APIs.
+//!
+//! The server keeps a scriptable [`MarketState`] in memory and answers every path in
+//! [`Endpoint`] the way [`HttpClient`] expects, so `Updater`, `Trader` and `Scheduler`
+//! flows can run end-to-end without the live service.
+use crate::endpoint::Endpoint;
+use crate::{Channel, HttpClient, CS2_APP_ID};
+use axum::body::Bytes;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use common::Secret;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+const STATUS_SELLING: i64 = 2;
+const STATUS_INVENTORY: i64 = 4;
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A single item as it appears on the fake market, in our inventory or in our offers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockListing {
+    pub id: String,
+    pub skin_id: i32,
+    pub price: f64,
+    pub float_value: Option<f64>,
+}
+
+impl MockListing {
+    pub fn new(id: &str, skin_id: i32, price: f64) -> Self {
+        Self {
+            id: id.to_string(),
+            skin_id,
+            price,
+            float_value: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MockSale {
+    pub skin_id: i32,
+    pub price: f64,
+    pub created_at: OffsetDateTime,
+}
+
+/// Everything the fake server knows about the market and our account.
+#[derive(Default)]
+pub struct MarketState {
+    pub balance: i32,
+    pub skins: BTreeMap<i32, String>,
+    pub market: Vec<MockListing>,
+    pub inventory: Vec<MockListing>,
+    pub offers: Vec<MockListing>,
+    pub sales: Vec<MockSale>,
+    pub purchases: Vec<MockListing>,
+    /// Holds bought items in [`Self::in_transfer`] until [`Self::deliver`] is called
+    pub hold_deliveries: bool,
+    /// Bought items that are neither in our inventory nor in our transactions yet
+    pub in_transfer: Vec<MockListing>,
+    /// Paths of every REST request received, in order
+    pub requests: Vec<String>,
+    /// WebSocket channels subscribed to by connected clients
+    pub subscriptions: HashSet<String>,
+    failures: HashMap<String, VecDeque<StatusCode>>,
+    lost_responses: HashMap<String, VecDeque<StatusCode>>,
+}
+
+impl MarketState {
+    pub fn add_skin(&mut self, id: i32, name: &str) {
+        self.skins.insert(id, name.to_string());
+    }
+
+    /// Adds `count` sales of `skin_id` at `price`, one hour apart and ending now.
+    pub fn add_sales(&mut self, skin_id: i32, price: f64, count: usize) {
+        let now = OffsetDateTime::now_utc();
+        self.sales.extend((0..count).map(|i| MockSale {
+            skin_id,
+            price,
+            created_at: now - time::Duration::hours(i as i64),
+        }));
+    }
+
+    /// Makes the next request to `endpoint` fail with `status`.
+    pub fn fail_next(&mut self, endpoint: Endpoint, status: StatusCode) {
+        self.failures
+            .entry(endpoint.to_string())
+            .or_default()
+            .push_back(status);
+    }
+
+    /// Makes the next request to `endpoint` take effect but answer with `status`, as if the
+    /// response was lost on the way back.
+    pub fn lose_next_response(&mut self, endpoint: Endpoint, status: StatusCode) {
+        self.lost_responses
+            .entry(endpoint.to_string())
+            .or_default()
+            .push_back(status);
+    }
+
+    /// Completes the transfer of every item bought while deliveries were held.
+    pub fn deliver(&mut self) {
+        for listing in std::mem::take(&mut self.in_transfer) {
+            self.inventory.push(listing.clone());
+            self.purchases.push(listing);
+        }
+    }
+
+    pub fn request_count(&self, endpoint: Endpoint) -> usize {
+        let path = endpoint.to_string();
+        self.requests.iter().filter(|r| **r == path).count()
+    }
+
+    fn skin_name(&self, skin_id: i32) -> String {
+        self.skins
+            .get(&skin_id)
+            .cloned()
+            .unwrap_or_else(|| format!("Skin {skin_id}"))
+    }
+
+    fn market_item_json(&self, listing: &MockListing, status: i64) -> Value {
+        json!({
+            "asset_id": listing.id,
+            "bot_id": 1,
+            "bot_steam_id": "76561198000000000",
+            "bumped_at": null,
+            "category_id": 1,
+            "class_id": listing.skin_id.to_string(),
+            "created_at": now_rfc3339(),
+            "discount": 0,
+            "float_value": listing.float_value,
+            "id": listing.id,
+            "name": self.skin_name(listing.skin_id),
+            "price": listing.price,
+            "quality_id": 1,
+            "skin_id": listing.skin_id,
+            "skin_status": 0,
+            "ss": 0,
+            "status": status,
+            "sticker_counter": 0,
+            "tradehold": 0,
+        })
+    }
+
+    fn ws_item_json(&self, listing: &MockListing) -> Value {
+        json!({
+            "app_id": CS2_APP_ID,
+            "asset_id": listing.id,
+            "class_id": listing.skin_id.to_string(),
+            "float_value": listing.float_value,
+            "id": listing.id,
+            "name": self.skin_name(listing.skin_id),
+            "price": listing.price,
+            "skin_id": listing.skin_id,
+        })
+    }
+}
+
+#[derive(Clone)]
+struct Shared {
+    state: Arc<Mutex<MarketState>>,
+    events: broadcast::Sender<(String, Value)>,
+}
+
+/// A running fake BitSkins server bound to a random local port.
+pub struct MockServer {
+    addr: SocketAddr,
+    shared: Shared,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let shared = Shared {
+            state: Default::default(),
+            events: broadcast::channel(1024).0,
+        };
+
+        let app = Router::new()
+            .route("/ws", get(ws_handler))
+            .fallback(rest_handler)
+            .with_state(shared.clone());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Self { addr, shared }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/ws", self.addr)
+    }
+
+    /// An [`HttpClient`] pointed at this server.
+    pub fn client(&self) -> HttpClient {
+        HttpClient::with_base_url(&self.url()).with_api_key(Secret::new("test"))
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MarketState> {
+        self.shared.state.lock().unwrap()
+    }
+
+    /// Pushes `data` to every WebSocket client subscribed to `channel`.
+    pub fn publish(&self, channel: Channel, data: Value) {
+        let channel = serde_json::to_value(channel)
+            .ok()
+            .and_then(|c| c.as_str().map(str::to_string))
+            .unwrap_or_default();
+        // No receivers simply means no client is connected yet
+        self.shared.events.send((channel, data)).ok();
+    }
+
+    /// Puts `listing` on the market and announces it on the `listed` channel.
+    pub fn list(&self, listing: MockListing) {
+        let data = {
+            let mut state = self.state();
+            state.market.push(listing.clone());
+            state.ws_item_json(&listing)
+        };
+        self.publish(Channel::Listed, data);
+    }
+
+    /// Polls the state until `condition` holds, panicking after a generous timeout.
+    pub async fn wait_until<F: Fn(&MarketState) -> bool>(&self, condition: F) {
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            while !condition(&self.state()) {
+                tokio::time::sleep(WAIT_INTERVAL).await;
+            }
+        })
+        .await
+        .expect("condition not met before timeout");
+    }
+}
+
+fn now_rfc3339() -> String {
+    OffsetDateTime::now_utc().format(&Rfc3339).unwrap()
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(json!({ "code": status.as_u16(), "message": message })),
+    )
+        .into_response()
+}
+
+fn take_listing(list: &mut Vec<MockListing>, id: &str) -> Option<MockListing> {
+    let index = list.iter().position(|l| l.id == id)?;
+    Some(list.remove(index))
+}
+
+fn paginate(items: Vec<Value>, body: &Value) -> Value {
+    let offset = body["offset"].as_u64().unwrap_or(0) as usize;
+    let limit = body["limit"].as_u64().unwrap_or(items.len() as u64) as usize;
+    let filtered = items.len();
+    let list: Vec<_> = items.into_iter().skip(offset).take(limit).collect();
+    json!({ "list": list, "counter": { "filtered": filtered } })
+}
+
+async fn rest_handler(State(shared): State<Shared>, uri: Uri, body: Bytes) -> Response {
+    let path = uri.path().to_string();
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    let mut state = shared.state.lock().unwrap();
+    state.requests.push(path.clone());
+
+    if let Some(status) = state.failures.get_mut(&path).and_then(|f| f.pop_front()) {
+        return error(status, "Injected failure");
+    }
+
+    let Ok(endpoint) = Endpoint::from_str(&path) else {
+        return error(StatusCode::NOT_FOUND, "Unknown endpoint");
+    };
+
+    let response = handle_endpoint(&mut state, endpoint, &body);
+
+    match state
+        .lost_responses
+        .get_mut(&path)
+        .and_then(|f| f.pop_front())
+    {
+        Some(status) => error(status, "Injected failure after processing"),
+        None => response,
+    }
+}
+
+fn handle_endpoint(state: &mut MarketState, endpoint: Endpoint, body: &Value) -> Response {
+    let id = body["id"].as_str().unwrap_or_default();
+
+    let response = match endpoint {
+        Endpoint::Skin => json!(state
+            .skins
+            .iter()
+            .map(|(id, name)| json!({
+                "id": id,
+                "name": name,
+                "class_id": id.to_string(),
+                "suggested_price": null,
+            }))
+            .collect::<Vec<_>>()),
+        Endpoint::SearchCsgo => {
+            let skin_id = body["where"]["skin_id"][0].as_i64().unwrap_or_default();
+            let mut listings: Vec<_> = state
+                .market
+                .iter()
+                .filter(|l| l.skin_id as i64 == skin_id)
+                .collect();
+            listings.sort_by(|a, b| a.price.total_cmp(&b.price));
+            let items = listings
+                .into_iter()
+                .map(|l| state.market_item_json(l, STATUS_SELLING))
+                .collect();
+            paginate(items, body)
+        }
+        Endpoint::Inventory => {
+            let status = body["where_mine"]["status"][0].as_i64().unwrap_or_default();
+            let listings = match status {
+                STATUS_SELLING => &state.offers,
+                STATUS_INVENTORY => &state.inventory,
+                _ => return error(StatusCode::BAD_REQUEST, "Unknown status"),
+            };
+            let items = listings
+                .iter()
+                .map(|l| state.market_item_json(l, status))
+                .collect();
+            paginate(items, body)
+        }
+        Endpoint::SearchGet => {
+            match state
+                .market
+                .iter()
+                .chain(&state.offers)
+                .find(|l| l.id == id)
+            {
+                Some(listing) => state.market_item_json(listing, STATUS_SELLING),
+                None => return error(StatusCode::NOT_FOUND, "Item not found"),
+            }
+        }
+        Endpoint::PricingList => {
+            let skin_id = body["skin_id"].as_i64().unwrap_or_default();
+            let limit = body["limit"].as_u64().unwrap_or(u64::MAX) as usize;
+            let mut sales: Vec<_> = state
+                .sales
+                .iter()
+                .filter(|s| s.skin_id as i64 == skin_id)
+                .collect();
+            sales.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+            json!(sales
+                .into_iter()
+                .take(limit)
+                .map(|s| json!({
+                    "created_at": s.created_at.format(&Rfc3339).unwrap(),
+                    "price": s.price,
+                }))
+                .collect::<Vec<_>>())
+        }
+        Endpoint::ProfileBalance => json!({ "balance": state.balance }),
+        Endpoint::BuySingle => {
+            let max_price = body["max_price"].as_f64().unwrap_or_default();
+            let Some(listing) = state.market.iter().find(|l| l.id == id).cloned() else {
+                return error(StatusCode::BAD_REQUEST, "Item not available");
+            };
+            if listing.price > max_price {
+                return error(StatusCode::BAD_REQUEST, "Price is above max_price");
+            }
+            if listing.price > state.balance as f64 {
+                return error(StatusCode::BAD_REQUEST, "Insufficient balance");
+            }
+            take_listing(&mut state.market, id);
+            state.balance -= listing.price.round() as i32;
+            state.in_transfer.push(listing);
+            if !state.hold_deliveries {
+                state.deliver();
+            }
+            json!({ "receipt_id": format!("receipt-{id}") })
+        }
+        Endpoint::RelistSingle => {
+            let price = body["price"].as_f64().unwrap_or_default();
+            json!(relist(state, id, price))
+        }
+        Endpoint::RelistMany => json!(for_each_item(body, |id, price| relist(state, id, price))),
+        Endpoint::UpdatePriceSingle => {
+            let price = body["price"].as_f64().unwrap_or_default();
+            update_price(state, id, price);
+            Value::Null
+        }
+        Endpoint::UpdateOfferPrices => {
+            json!(for_each_item(body, |id, price| update_price(
+                state, id, price
+            )))
+        }
+        Endpoint::DelistSingle => match take_listing(&mut state.offers, id) {
+            Some(listing) => {
+                state.inventory.push(listing);
+                json!(true)
+            }
+            None => json!(false),
+        },
+        Endpoint::Transactions => {
+            let transactions = state
+                .purchases
+                .iter()
+                .map(|p| {
+                    json!({
+                        "action": "buy",
+                        "amount": -(p.price.round() as i32),
+                        "channel": "market",
+                        "created_at": now_rfc3339(),
+                        "extras": { "id": p.id },
+                        "id": format!("tx-{}", p.id),
+                        "service_id": 1,
+                        "type": 1,
+                    })
+                })
+                .collect();
+            paginate(transactions, body)
+        }
+        Endpoint::HistoryList => paginate(Vec::new(), body),
+    };
+
+    Json(response).into_response()
+}
+
+fn for_each_item<F: FnMut(&str, f64) -> bool>(body: &Value, mut f: F) -> Vec<Value> {
+    body["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|item| {
+            let id = item["id"].as_str().unwrap_or_default();
+            let success = f(id, item["price"].as_f64().unwrap_or_default());
+            json!({ "id": id, "success": success })
+        })
+        .collect()
+}
+
+fn relist(state: &mut MarketState, id: &str, price: f64) -> bool {
+    match take_listing(&mut state.inventory, id) {
+        Some(listing) => {
+            state.offers.push(MockListing { price, ..listing });
+            true
+        }
+        None => false,
+    }
+}
+
+fn update_price(state: &mut MarketState, id: &str, price: f64) -> bool {
+    match state.offers.iter_mut().find(|l| l.id == id) {
+        Some(listing) => {
+            listing.price = price;
+            true
+        }
+        None => false,
+    }
+}
+
+async fn ws_handler(State(shared): State<Shared>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(|socket| ws_session(shared, socket))
+}
+
+async fn ws_session(shared: Shared, mut socket: WebSocket) {
+    let mut events = shared.events.subscribe();
+    let mut subscribed = HashSet::new();
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(_)) => continue,
+                    None | Some(Err(_)) => break,
+                };
+                let Ok(Value::Array(array)) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+                match array.first().and_then(Value::as_str) {
+                    Some("WS_AUTH_APIKEY") => {
+                        let reply = json!(["WS_AUTH_APIKEY", "ok"]).to_string();
+                        if socket.send(Message::Text(reply.into())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some("WS_SUB") => {
+                        if let Some(channel) = array.get(1).and_then(Value::as_str) {
+                            subscribed.insert(channel.to_string());
+                            shared.state.lock().unwrap().subscriptions.insert(channel.to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            event = events.recv() => {
+                let Ok((channel, data)) = event else {
+                    continue;
+                };
+                if subscribed.contains(&channel) {
+                    let message = json!([channel, data]).to_string();
+                    if socket.send(Message::Text(message.into())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
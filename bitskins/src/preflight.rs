@@ -0,0 +1,53 @@
+//! Startup self-check, run with `bitskins preflight`.
+//!
+//! Verifies everything the bot needs before it trades: the database and its migrations, and for
+//! every enabled account its API key, a working balance call and its row in `Account`.
+use crate::{Database, HttpClient};
+use common::preflight::{self, Report};
+use common::secrets;
+
+/// Checks the configured secrets, database and the live API.
+pub async fn run() -> Report {
+    let mut report = Report::default();
+    report.record("secrets", secrets::init().map(|()| "loaded"));
+    match Database::new().await {
+        Ok(db) => {
+            report.pass("database", "connected");
+            check(&mut report, &db, &HttpClient::new()).await;
+        }
+        Err(e) => report.fail("database", e),
+    }
+    report
+}
+
+/// Checks `db` and authenticates each enabled account against the API `http` points at.
+pub async fn check(report: &mut Report, db: &Database, http: &HttpClient) {
+    report.record("migrations", preflight::check_migrations(db.pool()).await);
+
+    let accounts = match db.get_trading_accounts().await {
+        Ok(accounts) if accounts.is_empty() => {
+            return report.fail("accounts", "no enabled BitSkins accounts");
+        }
+        Ok(accounts) => accounts,
+        Err(e) => return report.fail("accounts", e),
+    };
+    report.pass("accounts", format!("{} enabled", accounts.len()));
+
+    for account in accounts {
+        let name = &account.name;
+        match account.api_key() {
+            Ok(api_key) => {
+                let balance = http.clone().with_api_key(api_key).fetch_balance().await;
+                report.record(
+                    format!("{name}: authentication"),
+                    balance.map(|balance| format!("balance {balance}")),
+                );
+            }
+            Err(e) => report.fail(format!("{name}: authentication"), e),
+        }
+        report.record(
+            format!("{name}: Account row"),
+            preflight::check_balance_row(db.pool(), "Account", account.id).await,
+        );
+    }
+}
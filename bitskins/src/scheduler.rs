@@ -1,7 +1,15 @@
+//! Cron-style jobs whose state survives restarts.
+//!
+//! Every run is recorded in the database together with the time the job is due next. A job that
+//! missed its run while the service was down, or never ran at all, is run right after startup.
+//! Runs of the same job never overlap, even across processes.
 use crate::trader::Trader;
 use anyhow::Result;
-use log::error;
+use log::{error, info};
 use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
 use tokio_cron_scheduler::{Job, JobScheduler};
 
 pub struct Scheduler {
@@ -15,36 +23,65 @@ impl Scheduler {
         Ok(Scheduler { trader, scheduler })
     }
 
-    pub async fn schedule_task<F, Fut>(&self, schedule: &str, task: F) -> Result<()>
+    /// Schedules `task` as job `name`, running it at startup as well if it is overdue.
+    pub async fn schedule_task<F, Fut>(&self, name: &str, schedule: &str, task: F) -> Result<()>
     where
         F: Fn(Trader) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Result<()>> + Send + 'static,
     {
-        let trader_clone = self.trader.clone();
-
-        self.scheduler
-            .add(Job::new_async(schedule, move |_uuid, _l| {
-                let trader = trader_clone.clone();
-                let fut = task(trader);
-                Box::pin(async move {
-                    if let Err(e) = fut.await {
-                        error!("Error executing scheduled task: {:?}", e);
-                    }
-                })
+        let task = Arc::new(task);
+        let job_name = name.to_string();
+        let run = move |trader: Trader, job_id, mut scheduler: JobScheduler, catch_up: bool| {
+            let task = task.clone();
+            let name = job_name.clone();
+            Box::pin(async move {
+                let next_run_at = scheduler
+                    .next_tick_for_job(job_id)
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|next| OffsetDateTime::from_unix_timestamp(next.timestamp()).ok());
+                let task = task(trader.clone());
+                if let Err(e) = run_job(&trader, &name, task, next_run_at, catch_up).await {
+                    error!("Error executing scheduled task {name}: {e:?}");
+                }
+            })
+        };
+        let run = Arc::new(run);
+
+        let trader = self.trader.clone();
+        let scheduled = run.clone();
+        let job_id = self
+            .scheduler
+            .add(Job::new_async(schedule, move |uuid, l| {
+                scheduled(trader.clone(), uuid, l, false)
             })?)
             .await?;
 
+        if self.trader.db.is_job_overdue(name).await? {
+            info!("Job {name} is overdue, running it at startup");
+            let trader = self.trader.clone();
+            self.scheduler
+                .add(Job::new_one_shot_async(Duration::ZERO, move |_uuid, l| {
+                    run(trader.clone(), job_id, l, true)
+                })?)
+                .await?;
+        }
+
         Ok(())
     }
 
+    /// Schedules the jobs of the trader's account, and the market-wide ones. The latter are
+    /// shared by all accounts and run by whichever scheduler gets to them first.
     pub async fn schedule_tasks(&self) -> Result<()> {
-        self.schedule_task("every day", |trader| async move {
+        let buy = format!("{}/buy", self.trader.account.name);
+        self.schedule_task(&buy, "every day", |trader| async move {
             trader.updater.sync_offered_items().await?;
             trader.purchase_best_items().await
         })
         .await?;
 
-        self.schedule_task("every 10 days", |trader| async move {
+        self.schedule_task("sync", "every 10 days", |trader| async move {
             trader.updater.sync_market_items().await?;
             Ok(trader.updater.sync_new_sales().await?)
         })
@@ -53,8 +90,48 @@ impl Scheduler {
         Ok(())
     }
 
+    /// Starts running the scheduled jobs in the background.
     pub async fn start(self) -> Result<()> {
-        self.schedule_tasks().await?;
         Ok(self.scheduler.start().await?)
     }
 }
+
+/// Runs `task` as job `name` unless another run of it is in progress, recording the outcome.
+///
+/// A `catch_up` run is skipped if the job is no longer overdue once its lock is held, which is
+/// the case when the scheduler of another account caught up on it first. A failed run leaves
+/// the job due, so it is caught up on at the next startup.
+async fn run_job<Fut>(
+    trader: &Trader,
+    name: &str,
+    task: Fut,
+    next_run_at: Option<OffsetDateTime>,
+    catch_up: bool,
+) -> Result<()>
+where
+    Fut: Future<Output = Result<()>>,
+{
+    let db = &trader.db;
+    let Some(lock) = db.try_lock_job(name).await? else {
+        info!("Skipping job {name}: previous run still in progress");
+        return Ok(());
+    };
+
+    let result = async {
+        if catch_up && !db.is_job_overdue(name).await? {
+            info!("Skipping job {name}: no longer overdue");
+            return Ok(());
+        }
+        let run_id = db.start_job_run(name).await?;
+        let result = task.await;
+        let error = result.as_ref().err().map(|e| format!("{e:#}"));
+        let next_run_at = next_run_at.filter(|_| error.is_none());
+        db.finish_job_run(run_id, error.as_deref(), next_run_at)
+            .await?;
+        result
+    }
+    .await;
+    lock.release().await?;
+
+    result
+}
@@ -1,34 +1,70 @@
-use crate::Error::{InternalService, MarketItemDeleteFailed, MarketItemUpdateFailed};
+use crate::fees;
+use crate::Error::{self, InternalService, MarketItemDeleteFailed, MarketItemUpdateFailed};
 use crate::{
-    Channel, Database, DateTime, HttpClient, MarketItem, Skin, Stats, Updater, WsData, CS2_APP_ID,
+    Channel, Database, DateTime, HttpClient, MarketItem, Purchase, PurchaseStatus, Skin, Stats,
+    TradingAccount, Updater, WsData, CS2_APP_ID,
 };
 use anyhow::{bail, Result};
-use log::{debug, info, warn};
-use std::cmp::Ordering;
+use common::{map, Money, Rounding};
+use log::{debug, error, info, warn};
+use std::collections::HashSet;
+use time::OffsetDateTime;
 
 const MAX_PRICE_BALANCE_THRESHOLD: f64 = 0.5;
-const SALES_FEE: f64 = 0.1;
 const MIN_PROFIT_MARGIN: f64 = 0.2;
 const MIN_SALE_COUNT: i32 = 500;
 const MIN_SLOPE: f64 = 0.0;
+/// Purchases that cannot be resolved within this time are given up on
+const MAX_PENDING_AGE: time::Duration = time::Duration::days(1);
 
 #[derive(Clone)]
 pub struct Trader {
-    db: Database,
-    http: HttpClient,
+    pub db: Database,
+    pub http: HttpClient,
+    pub account: TradingAccount,
     pub updater: Updater,
 }
 
 impl Trader {
     pub async fn new() -> Result<Self> {
-        let db = Database::new().await?;
-        let http = HttpClient::new();
+        Self::for_account(
+            Database::new().await?,
+            HttpClient::new(),
+            TradingAccount::default(),
+        )
+    }
+
+    /// Creates a trader for the default account that uses `http` as it is.
+    pub fn from_db_and_client(db: Database, http: HttpClient) -> Self {
+        Self::with_account(db, http, TradingAccount::default())
+    }
 
-        Ok(Self {
+    /// Creates a trader for `account`, authenticating `http` with its API key.
+    ///
+    /// Fails if the API key cannot be found, so a misconfigured account stops the service at
+    /// startup rather than on its first request.
+    pub fn for_account(db: Database, http: HttpClient, account: TradingAccount) -> Result<Self> {
+        let http = http.with_api_key(account.api_key()?);
+        Ok(Self::with_account(db, http, account))
+    }
+
+    fn with_account(db: Database, http: HttpClient, account: TradingAccount) -> Self {
+        Self {
             db: db.clone(),
             http: http.clone(),
-            updater: Updater::from_db_and_client(db, http),
-        })
+            updater: Updater::for_account(db, http, account.id),
+            account,
+        }
+    }
+
+    /// Creates a trader for each enabled account, sharing one database.
+    pub async fn for_all_accounts() -> Result<Vec<Self>> {
+        let db = Database::new().await?;
+        db.get_trading_accounts()
+            .await?
+            .into_iter()
+            .map(|account| Self::for_account(db.clone(), HttpClient::new(), account))
+            .collect()
     }
 
     pub async fn process_data(&self, channel: Channel, item: WsData) {
@@ -75,7 +111,7 @@ impl Trader {
     }
 
     async fn handle_delisted_or_sold(&self, item: WsData) -> Result<()> {
-        if self.db.is_in_offers(item.id.parse()?).await? {
+        if self.db.get_offer_account(item.id.parse()?).await? == Some(self.account.id) {
             self.updater.update_balance().await?;
         }
         if let Err(MarketItemDeleteFailed(_)) = self.db.delete_market_item(item.id.parse()?).await {
@@ -125,34 +161,179 @@ impl Trader {
             bail!("Price stats are not reliable for skin_id: {}", skin_id);
         }
 
-        let balance = self.db.get_balance().await?;
+        let item_id = deal.id.parse()?;
+        if self.db.is_in_offers(item_id).await? {
+            bail!("Item {} is listed by one of our accounts", deal.id);
+        }
+
+        let balance = self.db.get_balance(self.account.id).await?;
         if !deal.is_affordable(balance) {
             bail!(
                 "{} exceeds our max price for our current balance",
                 deal.price
             );
         }
+        self.check_limits(&deal).await?;
 
         if !deal.is_profitable(mean) {
             bail!("Item is not profitable: {}", skin_id)
         }
 
+        self.purchase(deal, skin_id).await
+    }
+
+    /// Buys market item `item_id` for `price` without judging whether it is a good deal. Items
+    /// listed by our accounts and the limits of the account are still respected.
+    pub async fn buy_item(&self, item_id: i32, price: Money, skin_id: i32) -> Result<()> {
+        if self.db.is_in_offers(item_id).await? {
+            bail!("Item {item_id} is listed by one of our accounts");
+        }
+        let deal = MarketDeal::new(item_id.to_string(), price);
+        self.check_limits(&deal).await?;
+        self.purchase(deal, skin_id).await
+    }
+
+    /// Buys the item of `deal`, recording the purchase so its outcome is never lost.
+    async fn purchase(&self, deal: MarketDeal, skin_id: i32) -> Result<()> {
+        let item_id = deal.id.parse()?;
+        if !self
+            .db
+            .start_purchase(self.account.id, item_id, skin_id, deal.price)
+            .await?
+        {
+            bail!(
+                "Item {} was already bought or its purchase is unresolved",
+                deal.id
+            );
+        }
+
         match self.execute_purchase(deal.clone()).await {
-            Err(InternalService(endpoint)) => {
+            Ok(()) => self.complete_purchase(item_id).await,
+            Err(e @ (InternalService(_) | Error::HttpClient(_))) => {
                 warn!(
-                    "Failed to execute purchase for item {}. Updating database for {}...",
-                    deal.id, skin_id
+                    "Outcome of purchase of item {} is unknown ({e}). Reconciling...",
+                    deal.id
                 );
-                self.db.delete_market_item(deal.id.parse()?).await?;
-                Err(InternalService(endpoint))?
+                let Some(purchase) = self.db.get_purchase(item_id).await? else {
+                    bail!("Purchase of item {item_id} was not recorded");
+                };
+                match self.reconcile_purchase(&purchase).await? {
+                    PurchaseStatus::Confirmed => self.complete_purchase(item_id).await,
+                    _ => {
+                        if let Err(MarketItemDeleteFailed(_)) =
+                            self.db.delete_market_item(item_id).await
+                        {
+                            debug!("Item {item_id} was not in the database");
+                        }
+                        Err(e)?
+                    }
+                }
             }
-            Ok(()) => {
-                self.updater.update_balance().await?;
-                self.updater.list_inventory_items().await?;
-                Ok(())
+            Err(e) => {
+                self.db
+                    .set_purchase_status(item_id, PurchaseStatus::Failed)
+                    .await?;
+                Err(e)?
+            }
+        }
+    }
+
+    /// Enforces the risk limits of the account.
+    async fn check_limits(&self, deal: &MarketDeal) -> Result<()> {
+        if let Some(max_price) = self.account.max_purchase_price {
+            if deal.price > max_price {
+                bail!(
+                    "{} exceeds the max purchase price of account {}",
+                    deal.price,
+                    self.account.name
+                );
+            }
+        }
+        if let Some(limit) = self.account.daily_spend_limit {
+            let spent = self.db.get_daily_spend(self.account.id).await?;
+            if spent + deal.price > limit {
+                bail!(
+                    "Buying for {} would exceed the daily spend limit of account {}",
+                    deal.price,
+                    self.account.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn complete_purchase(&self, item_id: i32) -> Result<()> {
+        self.db
+            .set_purchase_status(item_id, PurchaseStatus::Confirmed)
+            .await?;
+        self.updater.update_balance().await?;
+        self.updater.list_inventory_items().await?;
+        Ok(())
+    }
+
+    /// Determines whether a purchase with an unknown outcome went through and records it.
+    ///
+    /// The purchase is confirmed once the item shows up in our inventory, our offers or our
+    /// transaction history, and failed if the item is still on the market or the purchase is
+    /// older than [`MAX_PENDING_AGE`]. Otherwise the item may still be in transfer and the
+    /// purchase stays pending. If any of these lookups fail, the purchase stays pending too.
+    async fn reconcile_purchase(&self, purchase: &Purchase) -> Result<PurchaseStatus> {
+        let id = purchase.item_id.to_string();
+        let owned = self
+            .http
+            .fetch_inventory()
+            .await?
+            .iter()
+            .chain(&self.http.fetch_offers().await?)
+            .any(|item| item.id == id)
+            || self
+                .http
+                .fetch_transactions()
+                .await?
+                .iter()
+                .any(|transaction| transaction.item_id() == Some(&id));
+
+        let status = if owned {
+            PurchaseStatus::Confirmed
+        } else if self
+            .http
+            .fetch_market_items_for_skin(purchase.skin_id)
+            .await?
+            .iter()
+            .any(|item| item.id == id)
+        {
+            PurchaseStatus::Failed
+        } else if OffsetDateTime::now_utc() - purchase.created_at > MAX_PENDING_AGE {
+            warn!("Giving up on purchase of item {id}, which is still unresolved");
+            PurchaseStatus::Failed
+        } else {
+            PurchaseStatus::Pending
+        };
+        info!("Purchase of item {id} reconciled as {status:?}");
+        self.db
+            .set_purchase_status(purchase.item_id, status)
+            .await?;
+        Ok(status)
+    }
+
+    /// Resolves purchases whose outcome could not be determined earlier. A purchase that cannot
+    /// be looked up stays pending until the next attempt.
+    pub async fn reconcile_pending_purchases(&self) -> Result<()> {
+        let mut confirmed = false;
+        for purchase in self.db.get_pending_purchases(self.account.id).await? {
+            match self.reconcile_purchase(&purchase).await {
+                Ok(status) => confirmed |= status == PurchaseStatus::Confirmed,
+                Err(e) => error!(
+                    "Error reconciling purchase of item {}: {e}",
+                    purchase.item_id
+                ),
             }
-            other => Ok(other?),
         }
+        if confirmed {
+            self.updater.update_balance().await?;
+            self.updater.list_inventory_items().await?;
+        }
+        Ok(())
     }
 
     fn are_stats_reliable(stats: &Stats) -> bool {
@@ -161,11 +342,13 @@ impl Trader {
 
     async fn find_best_market_deal(&self, skin_id: i32) -> Result<Option<MarketDeal>> {
         let market_list = self.db.get_market_items(skin_id).await?;
+        let own_offers: HashSet<_> = map(self.db.get_offers(skin_id).await?, |item| item.id);
 
         Ok(market_list
             .into_iter()
+            .filter(|data| !own_offers.contains(&data.id))
             .map(|data| MarketDeal::new(data.id.to_string(), data.price))
-            .min_by(|a, b| a.price.partial_cmp(&b.price).unwrap_or(Ordering::Equal)))
+            .min_by_key(|deal| deal.price.mills()))
     }
 
     async fn execute_purchase(&self, deal: MarketDeal) -> crate::Result<()> {
@@ -175,6 +358,7 @@ impl Trader {
     }
 
     pub async fn purchase_best_items(&self) -> Result<()> {
+        self.reconcile_pending_purchases().await?;
         let skin_ids = self
             .db
             .get_skins_by_sale_count(MIN_SALE_COUNT as i64)
@@ -191,20 +375,21 @@ impl Trader {
 #[derive(Clone, Debug)]
 struct MarketDeal {
     id: String,
-    price: f64,
+    price: Money,
 }
 
 impl MarketDeal {
-    fn new(id: String, price: f64) -> Self {
+    fn new(id: String, price: Money) -> Self {
         Self { id, price }
     }
-    fn is_affordable(&self, balance: f64) -> bool {
-        self.price <= (MAX_PRICE_BALANCE_THRESHOLD * balance)
+    fn is_affordable(&self, balance: Money) -> bool {
+        self.price <= balance.scale(MAX_PRICE_BALANCE_THRESHOLD, Rounding::Down)
     }
 
+    /// Whether reselling at a discount on `mean_price`, in mills, leaves our margin after fees.
     fn is_profitable(&self, mean_price: f64) -> bool {
-        let sale_price = (1.0 - Updater::SELLING_DISCOUNT) * mean_price;
-        let fee = (SALES_FEE * sale_price).max(10.0); // Fee is always at least 1 cent
-        self.price * (1.0 + MIN_PROFIT_MARGIN) <= (sale_price - fee)
+        let sale_price = Money::from_mills(mean_price.round() as i64)
+            .scale(1.0 - Updater::SELLING_DISCOUNT, Rounding::Down);
+        fees::schedule().is_profitable(self.price, sale_price, MIN_PROFIT_MARGIN)
     }
 }
@@ -1,9 +1,9 @@
 use crate::http::ItemPrice;
 use crate::Result;
-use crate::{db, http, Database, HttpClient};
+use crate::{db, http, Database, HttpClient, TradingAccount, DEFAULT_ACCOUNT_ID};
+use common::{Money, Rounding};
 use futures::future::try_join;
 use futures::{stream, StreamExt};
-use std::cmp::max;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 const MAX_TASKS: usize = 10;
@@ -12,20 +12,32 @@ const MAX_TASKS: usize = 10;
 pub struct Updater {
     db: Database,
     client: HttpClient,
+    /// Account whose balance and offers are kept up to date
+    account_id: i32,
 }
 
 impl Updater {
     pub const SELLING_DISCOUNT: f64 = 0.0;
 
     pub async fn new() -> Result<Self> {
-        Ok(Self {
-            db: Database::new().await?,
-            client: HttpClient::new(),
-        })
+        let api_key = TradingAccount::default().api_key()?;
+        Ok(Self::from_db_and_client(
+            Database::new().await?,
+            HttpClient::new().with_api_key(api_key),
+        ))
     }
 
     pub fn from_db_and_client(db: Database, client: HttpClient) -> Self {
-        Self { db, client }
+        Self::for_account(db, client, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// Creates an updater for `account_id`, whose credentials `client` must use.
+    pub fn for_account(db: Database, client: HttpClient, account_id: i32) -> Self {
+        Self {
+            db,
+            client,
+            account_id,
+        }
     }
 
     async fn fetch_skins(&self) -> Result<Vec<db::Skin>> {
@@ -213,15 +225,15 @@ impl Updater {
 
         for item in items {
             if let Ok(stat) = self.db.get_price_statistics(item.skin_id).await {
-                let mut price =
-                    ((1.0 - Self::SELLING_DISCOUNT) * stat.mean_price.unwrap()).round() as u32;
+                let mut price = Money::from_mills(stat.mean_price.unwrap().round() as i64)
+                    .scale(1.0 - Self::SELLING_DISCOUNT, Rounding::Nearest);
                 if let Some(cheapest_competitor) = self.db.get_cheapest_price(item.skin_id).await? {
                     // sell at 1 cent below the cheapest competitor if still more than the mean
-                    price = max(price, cheapest_competitor as u32 - 10);
+                    price = price.max(cheapest_competitor - Money::from_cents(1));
                 }
-                // Bitskins UI appears to round up to the nearest 10 anyway, so we might as well
-                price = (price + 9) / 10 * 10;
-                if price != item.price.round() as u32 {
+                // Bitskins UI appears to round up to whole cents anyway, so we might as well
+                price = price.round_to_cents(Rounding::Up);
+                if price != item.price {
                     result.push(ItemPrice::new(item.id.to_string(), price));
                 }
             }
@@ -230,11 +242,26 @@ impl Updater {
         Ok(result)
     }
 
-    pub async fn list_inventory_items(&self) -> Result<()> {
+    async fn fetch_inventory(&self) -> Result<Vec<db::MarketItem>> {
         let inventory = self.client.fetch_inventory().await?;
-        let items: Vec<db::MarketItem> = inventory.into_iter().map(|item| item.into()).collect();
+        Ok(inventory.into_iter().map(|item| item.into()).collect())
+    }
+
+    /// Prices at which [`Updater::list_inventory_items`] would list the items in our inventory.
+    pub async fn get_inventory_listings(&self) -> Result<Vec<ItemPrice>> {
+        self.get_listing_prices(self.fetch_inventory().await?).await
+    }
+
+    /// Price changes [`Updater::update_offer_prices`] would make to our offers.
+    pub async fn get_offer_price_updates(&self) -> Result<Vec<ItemPrice>> {
+        let offers = self.db.get_account_offers(self.account_id).await?;
+        self.get_listing_prices(offers).await
+    }
+
+    pub async fn list_inventory_items(&self) -> Result<()> {
+        let items = self.fetch_inventory().await?;
         for item in &items {
-            self.db.insert_offer(item.clone()).await?;
+            self.db.insert_offer(self.account_id, item.clone()).await?;
         }
         let item_prices = self.get_listing_prices(items).await?;
         if !item_prices.is_empty() {
@@ -245,14 +272,13 @@ impl Updater {
     }
 
     pub async fn update_offer_prices(&self) -> Result<()> {
-        let offers = self.db.get_all_offers().await?;
-        let updates = self.get_listing_prices(offers).await?;
+        let updates = self.get_offer_price_updates().await?;
         if !updates.is_empty() {
             log::info!("Updating prices: {updates:?}");
             self.client.update_market_offers(&updates).await?;
             for update in updates {
                 self.db
-                    .update_market_item_price(update.id.parse()?, update.price as f64)
+                    .update_market_item_price(update.id.parse()?, update.price)
                     .await?;
             }
         }
@@ -267,15 +293,15 @@ impl Updater {
 
     pub async fn sync_offered_items(&self) -> Result<()> {
         log::info!("Syncing offered items");
-        self.db.delete_all_offers().await?;
+        self.db.delete_account_offers(self.account_id).await?;
         for offer in self.client.fetch_offers().await? {
-            self.db.insert_offer(offer.into()).await?;
+            self.db.insert_offer(self.account_id, offer.into()).await?;
         }
         Ok(())
     }
 
     pub async fn update_balance(&self) -> Result<()> {
         let balance = self.client.fetch_balance().await?;
-        self.db.update_balance(balance).await
+        self.db.update_balance(self.account_id, balance).await
     }
 }
@@ -1,11 +1,13 @@
 //! WebSocket client for real-time communication with the BitSkins API.
 
 use crate::{Error, Result};
+use common::lenient;
+use common::money::serde_option_mills;
+use common::{quarantine, Money, Secret};
 use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::env;
 use std::future::Future;
 use tokio::net::TcpStream;
 use tokio_tungstenite::tungstenite::Message;
@@ -22,7 +24,7 @@ const CHANNELS: [Channel; 3] = [
     Channel::DelistedOrSold,
 ];
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum Channel {
     Listed,
@@ -44,7 +46,7 @@ enum WsAction {
     WsUnsubAll,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Deserialize, Debug)]
 pub struct WsData {
     pub asset_id: String,
     pub id: String,
@@ -58,14 +60,19 @@ pub struct WsData {
     pub float_value: Option<f64>,
     pub name: Option<String>,
     pub paint_seed: Option<i32>,
-    pub price: Option<f64>,
+    #[serde(default, with = "serde_option_mills")]
+    pub price: Option<Money>,
+    #[serde(default, deserialize_with = "lenient::option_number")]
     pub suggested_price: Option<i32>,
     pub tradehold: Option<i32>,
-    pub old_price: Option<i32>,
+    #[serde(default, with = "serde_option_mills")]
+    pub old_price: Option<Money>,
 }
 
 /// A WebSocket client for communicating with the BitSkins API.
 pub struct WsClient<H> {
+    url: String,
+    api_key: Option<Secret>,
     write: WriteSocket,
     read: ReadSocket,
     handler: H,
@@ -82,14 +89,29 @@ where
     ///
     /// A `Result` containing the `WsClient` if successful, or an error if the connection fails.
     pub async fn connect(handler: H) -> Result<Self> {
-        let (write, read) = connect_async(WEB_SOCKET_URL).await?.0.split();
+        Self::connect_to(WEB_SOCKET_URL, handler).await
+    }
+
+    /// Establishes a connection to the WebSocket server at `url`.
+    ///
+    /// Reconnects made by [`WsClient::start`] reuse the same URL.
+    pub async fn connect_to(url: &str, handler: H) -> Result<Self> {
+        let (write, read) = connect_async(url).await?.0.split();
         Ok(Self {
+            url: url.to_string(),
+            api_key: None,
             write,
             read,
             handler,
         })
     }
 
+    /// Authenticates with `api_key`, including after reconnects.
+    pub fn with_api_key(mut self, api_key: Secret) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
     /// Sends an action to the WebSocket server.
     async fn send_action<S: Serialize>(&mut self, action: WsAction, data: S) -> Result<()> {
         let message = json!([action, data]).to_string();
@@ -116,8 +138,8 @@ where
             if let Ok(WsAction::WsAuthApikey) = WsAction::deserialize(action) {
                 self.setup_channels().await?
             } else if let Ok(channel) = Channel::deserialize(action) {
-                let ws_data =
-                    WsData::deserialize(data).map_err(|_| Error::Deserialize(data.to_string()))?;
+                let endpoint = format!("ws/{}", action.as_str().unwrap_or_default());
+                let ws_data = quarantine::from_value("bitskins", &endpoint, data)?;
                 (self.handler)(channel, ws_data).await;
             }
         } else {
@@ -137,7 +159,8 @@ where
     }
 
     async fn authenticate(&mut self) -> Result<()> {
-        self.send_action(WsAction::WsAuthApikey, env::var("BITSKIN_API_KEY")?)
+        let api_key = self.api_key.clone().ok_or(Error::MissingApiKey)?;
+        self.send_action(WsAction::WsAuthApikey, api_key.expose())
             .await
     }
 
@@ -163,7 +186,9 @@ where
                 }
                 Err(_) => {
                     log::info!("Got disconnected, reconnecting..");
-                    self = Self::connect(self.handler).await?;
+                    let api_key = self.api_key;
+                    self = Self::connect_to(&self.url, self.handler).await?;
+                    self.api_key = api_key;
                     self.authenticate().await?;
                 }
                 _ => {}
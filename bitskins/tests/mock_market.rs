@@ -0,0 +1,488 @@
+//! End-to-end flows against the in-process fake BitSkins server.
+use anyhow::Result;
+use bitskins::mock::{MockListing, MockServer};
+use bitskins::preflight;
+use bitskins::scheduler::Scheduler;
+use bitskins::trader::Trader;
+use bitskins::{
+    Database, Endpoint, JobOutcome, PurchaseStatus, TradingAccount, Updater, WsClient,
+    DEFAULT_ACCOUNT_ID,
+};
+use common::preflight::Report;
+use common::Money;
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const SKIN_ID: i32 = 1;
+const MEAN_PRICE: f64 = 1000.0;
+const BALANCE: i32 = 100_000;
+
+async fn setup(pool: PgPool) -> (MockServer, Database) {
+    std::env::set_var("BITSKIN_API_KEY", "test");
+    let mock = MockServer::start().await;
+    {
+        let mut state = mock.state();
+        state.balance = BALANCE;
+        state.add_skin(SKIN_ID, "AK-47 | Redline (Field-Tested)");
+        state.add_sales(SKIN_ID, MEAN_PRICE, 500);
+    }
+    (mock, Database::from_pool(pool))
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn sync_data_populates_database(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    mock.state()
+        .market
+        .push(MockListing::new("100", SKIN_ID, 1200.0));
+    mock.state()
+        .offers
+        .push(MockListing::new("200", SKIN_ID, 1100.0));
+
+    Updater::from_db_and_client(db.clone(), mock.client())
+        .sync_data()
+        .await?;
+
+    assert_eq!(db.get_sales_by_skin_id(SKIN_ID).await?.len(), 500);
+    assert_eq!(
+        db.get_balance(DEFAULT_ACCOUNT_ID).await?,
+        Money::from_mills(BALANCE as i64)
+    );
+    assert_eq!(
+        db.get_price_statistics(SKIN_ID).await?.sale_count,
+        Some(500)
+    );
+    assert!(db.get_market_item(100).await?.is_some());
+    assert!(db.is_in_offers(200).await?);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn trader_buys_profitable_listing_from_websocket(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    let trader = Trader::from_db_and_client(db.clone(), mock.client());
+    trader.updater.sync_data().await?;
+
+    let ws = WsClient::connect_to(&mock.ws_url(), |channel, data| {
+        trader.process_data(channel, data)
+    })
+    .await?
+    .with_api_key(TradingAccount::default().api_key()?);
+
+    tokio::select! {
+        result = ws.start() => panic!("WebSocket client stopped: {result:?}"),
+        _ = async {
+            mock.wait_until(|s| s.subscriptions.len() == 3).await;
+            mock.list(MockListing::new("300", SKIN_ID, 950.0));
+            mock.list(MockListing::new("301", SKIN_ID, 700.0));
+            mock.wait_until(|s| !s.offers.is_empty()).await;
+        } => {}
+    }
+
+    assert_eq!(
+        db.get_balance(DEFAULT_ACCOUNT_ID).await?,
+        Money::from_mills(BALANCE as i64 - 700)
+    );
+    let state = mock.state();
+    assert_eq!(
+        state.purchases,
+        vec![MockListing::new("301", SKIN_ID, 700.0)]
+    );
+    assert_eq!(
+        state.offers,
+        vec![MockListing::new("301", SKIN_ID, MEAN_PRICE)]
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn scheduled_task_purchases_best_items(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    mock.state()
+        .market
+        .extend([400, 401].map(|id| MockListing::new(&id.to_string(), SKIN_ID, id as f64 + 300.0)));
+    let trader = Trader::from_db_and_client(db, mock.client());
+    trader.updater.sync_data().await?;
+
+    let scheduler = Scheduler::new(trader).await?;
+    scheduler
+        .schedule_task("buy", "* * * * * *", |trader| async move {
+            trader.purchase_best_items().await
+        })
+        .await?;
+    scheduler.start().await?;
+
+    mock.wait_until(|s| !s.purchases.is_empty()).await;
+    assert_eq!(mock.state().purchases[0].id, "400");
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn purchases_are_not_retried_after_ambiguous_failures(pool: PgPool) -> Result<()> {
+    let (mock, _db) = setup(pool).await;
+    let client = mock.client();
+    mock.state()
+        .market
+        .push(MockListing::new("500", SKIN_ID, 900.0));
+    {
+        let mut state = mock.state();
+        state.fail_next(Endpoint::ProfileBalance, StatusCode::BAD_GATEWAY);
+        state.fail_next(Endpoint::BuySingle, StatusCode::BAD_GATEWAY);
+    }
+
+    assert_eq!(
+        client.fetch_balance().await?,
+        Money::from_mills(BALANCE as i64)
+    );
+    assert_eq!(mock.state().request_count(Endpoint::ProfileBalance), 2);
+
+    assert!(client
+        .buy_item("500", Money::from_mills(900))
+        .await
+        .is_err());
+    assert_eq!(mock.state().request_count(Endpoint::BuySingle), 1);
+
+    mock.state()
+        .fail_next(Endpoint::ProfileBalance, StatusCode::UNAUTHORIZED);
+    assert!(client.fetch_balance().await.is_err());
+    assert_eq!(mock.state().request_count(Endpoint::ProfileBalance), 3);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn ambiguous_purchases_are_reconciled(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    mock.state().market.extend([
+        MockListing::new("600", SKIN_ID, 700.0),
+        MockListing::new("601", SKIN_ID, 720.0),
+    ]);
+    let trader = Trader::from_db_and_client(db.clone(), mock.client());
+    trader.updater.sync_data().await?;
+
+    // The purchase goes through but the response is lost
+    mock.state()
+        .lose_next_response(Endpoint::BuySingle, StatusCode::BAD_GATEWAY);
+    trader.purchase_best_items().await?;
+
+    let purchase = db.get_purchase(600).await?.unwrap();
+    assert_eq!(purchase.status, PurchaseStatus::Confirmed);
+    assert_eq!(
+        db.get_balance(DEFAULT_ACCOUNT_ID).await?,
+        Money::from_mills(BALANCE as i64 - 700)
+    );
+    assert!(db.is_in_offers(600).await?);
+
+    // The next purchase never reaches the market
+    mock.state()
+        .fail_next(Endpoint::BuySingle, StatusCode::BAD_GATEWAY);
+    trader.purchase_best_items().await?;
+
+    let purchase = db.get_purchase(601).await?.unwrap();
+    assert_eq!(purchase.status, PurchaseStatus::Failed);
+
+    let state = mock.state();
+    assert_eq!(state.request_count(Endpoint::BuySingle), 2);
+    assert_eq!(
+        state.purchases,
+        vec![MockListing::new("600", SKIN_ID, 700.0)]
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn purchases_in_transfer_stay_pending_until_resolved(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool.clone()).await;
+    mock.state().market.extend([
+        MockListing::new("600", SKIN_ID, 700.0),
+        MockListing::new("601", SKIN_ID, 720.0),
+    ]);
+    let trader = Trader::from_db_and_client(db.clone(), mock.client());
+    trader.updater.sync_data().await?;
+
+    // Both purchases go through, but the items take a while to arrive
+    mock.state().hold_deliveries = true;
+    for _ in 0..2 {
+        mock.state()
+            .lose_next_response(Endpoint::BuySingle, StatusCode::BAD_GATEWAY);
+        trader.purchase_best_items().await?;
+    }
+    for id in [600, 601] {
+        assert_eq!(
+            db.get_purchase(id).await?.unwrap().status,
+            PurchaseStatus::Pending
+        );
+    }
+
+    // One item arrives, the other never will
+    {
+        let mut state = mock.state();
+        state.in_transfer.retain(|listing| listing.id == "600");
+        state.deliver();
+    }
+    trader.reconcile_pending_purchases().await?;
+    assert_eq!(
+        db.get_purchase(600).await?.unwrap().status,
+        PurchaseStatus::Confirmed
+    );
+    assert_eq!(
+        db.get_purchase(601).await?.unwrap().status,
+        PurchaseStatus::Pending
+    );
+
+    sqlx::query("UPDATE Purchase SET created_at = now() - INTERVAL '2 days' WHERE item_id = 601")
+        .execute(&pool)
+        .await?;
+    trader.reconcile_pending_purchases().await?;
+    assert_eq!(
+        db.get_purchase(601).await?.unwrap().status,
+        PurchaseStatus::Failed
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn overdue_jobs_run_at_startup(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    let trader = Trader::from_db_and_client(db.clone(), mock.client());
+    let runs = Arc::new(AtomicUsize::new(0));
+    let yearly = "0 0 0 1 1 *";
+
+    for _ in 0..3 {
+        let scheduler = Scheduler::new(trader.clone()).await?;
+        let counter = runs.clone();
+        scheduler
+            .schedule_task("yearly", yearly, move |_| {
+                let first = counter.fetch_add(1, Ordering::SeqCst) == 0;
+                async move {
+                    if first {
+                        anyhow::bail!("broken");
+                    }
+                    Ok(())
+                }
+            })
+            .await?;
+        scheduler.start().await?;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+
+    // The failed first run left the job overdue, so the second scheduler ran it again; the
+    // third saw its next run in the future
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+    let job = db.get_job("yearly").await?.unwrap();
+    assert_eq!(job.last_outcome, Some(JobOutcome::Succeeded));
+    assert!(job.next_run_at.unwrap() > job.last_run_at.unwrap());
+    let history = db.get_job_runs("yearly", 10).await?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].outcome, JobOutcome::Succeeded);
+    assert_eq!(history[1].error.as_deref(), Some("broken"));
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn overdue_jobs_are_caught_up_once_across_schedulers(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    let trader = Trader::from_db_and_client(db.clone(), mock.client());
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    // Both schedulers find the job overdue before either of them runs it
+    let mut schedulers = Vec::new();
+    for _ in 0..2 {
+        let scheduler = Scheduler::new(trader.clone()).await?;
+        let counter = runs.clone();
+        scheduler
+            .schedule_task("shared", "0 0 0 1 1 *", move |_| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .await?;
+        schedulers.push(scheduler);
+    }
+    for scheduler in schedulers {
+        scheduler.start().await?;
+    }
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert_eq!(db.get_job_runs("shared", 10).await?.len(), 1);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn job_runs_never_overlap_across_schedulers(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    let trader = Trader::from_db_and_client(db.clone(), mock.client());
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..2 {
+        let scheduler = Scheduler::new(trader.clone()).await?;
+        let counter = runs.clone();
+        scheduler
+            .schedule_task("slow", "* * * * * *", move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                }
+            })
+            .await?;
+        scheduler.start().await?;
+    }
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let history = db.get_job_runs("slow", 10).await?;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].outcome, JobOutcome::Running);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn accounts_respect_limits_and_skip_each_others_listings(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool.clone()).await;
+    std::env::set_var("BITSKIN_API_KEY_SECOND", "test");
+    sqlx::query(
+        "INSERT INTO trading_account (market, name, credentials, max_purchase_price)
+         VALUES ('bitskins', 'second', 'BITSKIN_API_KEY_SECOND', 710)",
+    )
+    .execute(&pool)
+    .await?;
+    mock.state().market.extend([
+        MockListing::new("600", SKIN_ID, 700.0),
+        MockListing::new("601", SKIN_ID, 720.0),
+    ]);
+
+    let mut account = db.get_trading_accounts().await?.pop().unwrap();
+    assert_eq!(account.name, "second");
+    let unconfigured = TradingAccount {
+        credentials: "BITSKIN_API_KEY_MISSING".to_string(),
+        ..account.clone()
+    };
+    assert!(Trader::for_account(db.clone(), mock.client(), unconfigured).is_err());
+    let trader = Trader::for_account(db.clone(), mock.client(), account.clone())?;
+    trader.updater.sync_data().await?;
+    // The cheapest listing belongs to the default account
+    sqlx::query("INSERT INTO Offer (item_id, account_id) VALUES (600, $1)")
+        .bind(DEFAULT_ACCOUNT_ID)
+        .execute(&pool)
+        .await?;
+
+    trader.purchase_best_items().await?;
+    assert!(mock.state().purchases.is_empty());
+
+    account.max_purchase_price = Some(Money::from_mills(800));
+    let trader = Trader::for_account(db.clone(), mock.client(), account.clone())?;
+    trader.purchase_best_items().await?;
+
+    assert_eq!(
+        mock.state().purchases,
+        vec![MockListing::new("601", SKIN_ID, 720.0)]
+    );
+    assert_eq!(db.get_purchase(601).await?.unwrap().account_id, account.id);
+    assert_eq!(
+        db.get_balance(account.id).await?,
+        Money::from_mills(BALANCE as i64 - 720)
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn preflight_reports_misconfigured_accounts(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool.clone()).await;
+    let mut report = Report::default();
+    preflight::check(&mut report, &db, &mock.client()).await;
+    assert!(report.passed(), "{report}");
+
+    sqlx::query(
+        "INSERT INTO trading_account (market, name, credentials)
+         VALUES ('bitskins', 'unconfigured', 'BITSKIN_API_KEY_UNCONFIGURED')",
+    )
+    .execute(&pool)
+    .await?;
+    let mut report = Report::default();
+    preflight::check(&mut report, &db, &mock.client()).await;
+
+    let failed: Vec<_> = report
+        .checks
+        .iter()
+        .filter(|check| check.outcome.is_err())
+        .map(|check| check.name.as_str())
+        .collect();
+    assert_eq!(
+        failed,
+        ["unconfigured: authentication", "unconfigured: Account row"]
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn manual_purchase_skips_profit_checks_but_not_limits(pool: PgPool) -> Result<()> {
+    let (mock, db) = setup(pool).await;
+    mock.state().market.extend([
+        MockListing::new("700", SKIN_ID, 1500.0),
+        MockListing::new("701", SKIN_ID, 2500.0),
+    ]);
+    let account = TradingAccount {
+        max_purchase_price: Some(Money::from_mills(2000)),
+        ..TradingAccount::default()
+    };
+    let trader = Trader::for_account(db.clone(), mock.client(), account)?;
+    trader.updater.sync_data().await?;
+
+    // Above the mean price, so never bought automatically
+    trader
+        .buy_item(700, Money::from_mills(1500), SKIN_ID)
+        .await?;
+    assert!(trader
+        .buy_item(701, Money::from_mills(2500), SKIN_ID)
+        .await
+        .is_err());
+
+    assert_eq!(
+        mock.state().purchases,
+        vec![MockListing::new("700", SKIN_ID, 1500.0)]
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn sampled_responses_match_the_structs(pool: PgPool) -> Result<()> {
+    let (mock, _db) = setup(pool).await;
+    mock.list(MockListing::new("1", SKIN_ID, MEAN_PRICE));
+
+    let samples = mock.client().sample_responses(SKIN_ID).await?;
+
+    assert!(samples.iter().any(|sample| !sample.objects.is_empty()));
+    for sample in samples {
+        assert!(sample.errors.is_empty(), "{sample:?}");
+    }
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn job_locks_dropped_without_release_are_freed(pool: PgPool) -> Result<()> {
+    let db = Database::from_pool(pool.clone());
+    let lock = db.try_lock_job("panicky").await?.unwrap();
+    assert!(db.try_lock_job("panicky").await?.is_none());
+
+    // As when the job panics: the lock goes away with its connection rather than staying with
+    // the pool. The server notices the closed connection asynchronously
+    drop(lock);
+    for _ in 0..50 {
+        let held: i64 = sqlx::query_scalar(
+            "SELECT count(*) FROM pg_locks JOIN pg_database ON pg_database.oid = database
+                 WHERE locktype = 'advisory' AND datname = current_database()",
+        )
+        .fetch_one(&pool)
+        .await?;
+        if held == 0 {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    anyhow::bail!("lock still held")
+}
@@ -0,0 +1,14 @@
+use bitskins::Endpoint;
+
+#[test]
+fn only_purchases_are_not_idempotent() {
+    assert!(!Endpoint::BuySingle.is_idempotent());
+    for endpoint in [
+        Endpoint::SearchCsgo,
+        Endpoint::DelistSingle,
+        Endpoint::UpdatePriceSingle,
+        Endpoint::ProfileBalance,
+    ] {
+        assert!(endpoint.is_idempotent(), "{endpoint}");
+    }
+}
@@ -0,0 +1,378 @@
+//! The catalog table and the market tables it links.
+use crate::name::{ItemName, Wear};
+use crate::steam::{ReferencePrice, SteamClient};
+use crate::Result;
+use common::Money;
+use serde::Serialize;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::env;
+
+const MAX_CONNECTIONS: u32 = 5;
+/// DMarket's ID of CS2, the only game BitSkins trades
+const DMARKET_CS2_GAME_ID: &str = "a8db";
+
+/// An item in the catalog and where the markets list it.
+#[derive(Clone, Debug, Serialize)]
+pub struct CatalogItem {
+    pub id: i32,
+    pub name: ItemName,
+    pub bitskins_skin_id: Option<i32>,
+    /// Game and title of the item on DMarket
+    pub dmarket_title: Option<(String, String)>,
+}
+
+struct CatalogRow {
+    id: i32,
+    base: String,
+    wear: Option<String>,
+    stattrak: bool,
+    souvenir: bool,
+    star: bool,
+    phase: Option<String>,
+    bitskins_skin_id: Option<i32>,
+    dmarket_game_id: Option<String>,
+    dmarket_title: Option<String>,
+}
+
+impl From<CatalogRow> for CatalogItem {
+    fn from(row: CatalogRow) -> Self {
+        Self {
+            id: row.id,
+            name: ItemName {
+                base: row.base,
+                wear: row.wear.as_deref().and_then(Wear::parse),
+                stattrak: row.stattrak,
+                souvenir: row.souvenir,
+                star: row.star,
+                phase: row.phase,
+            },
+            bitskins_skin_id: row.bitskins_skin_id,
+            dmarket_title: row.dmarket_game_id.zip(row.dmarket_title),
+        }
+    }
+}
+
+/// What a [`Catalog::sync`] found.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncSummary {
+    pub items: i64,
+    pub bitskins: i64,
+    pub dmarket: i64,
+    /// Items listed on both markets
+    pub linked: i64,
+}
+
+/// Handles the catalog of items across markets.
+#[derive(Clone)]
+pub struct Catalog {
+    pool: PgPool,
+}
+
+impl Catalog {
+    /// Connects to the database at `DATABASE_URL`.
+    pub async fn new() -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect(&env::var("DATABASE_URL")?)
+            .await?;
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Wraps an existing connection pool, e.g. one provided by `sqlx::test`.
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Adds the skins and titles the markets know to the catalog and links the ones naming the
+    /// same item. Links are rebuilt from scratch, so improvements to the normalization apply to
+    /// items already in the catalog.
+    pub async fn sync(&self) -> Result<SyncSummary> {
+        let skins = sqlx::query!("SELECT id, name FROM Skin")
+            .fetch_all(&self.pool)
+            .await?;
+        let titles = sqlx::query_scalar!(
+            "SELECT title FROM dmarket_game_titles WHERE game_id = $1",
+            DMARKET_CS2_GAME_ID
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE catalog_item
+            SET bitskins_skin_id = NULL, dmarket_game_id = NULL, dmarket_title = NULL
+            "#
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for skin in skins {
+            let id = Self::upsert(&mut tx, &ItemName::parse(&skin.name)).await?;
+            sqlx::query!(
+                "UPDATE catalog_item SET bitskins_skin_id = $1 WHERE id = $2",
+                skin.id,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        for title in titles {
+            let id = Self::upsert(&mut tx, &ItemName::parse(&title)).await?;
+            sqlx::query!(
+                "UPDATE catalog_item SET dmarket_game_id = $1, dmarket_title = $2 WHERE id = $3",
+                DMARKET_CS2_GAME_ID,
+                title,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let summary = sqlx::query_as!(
+            SyncSummary,
+            r#"
+            SELECT
+                COUNT(*) AS "items!",
+                COUNT(bitskins_skin_id) AS "bitskins!",
+                COUNT(dmarket_title) AS "dmarket!",
+                COUNT(*) FILTER (
+                    WHERE bitskins_skin_id IS NOT NULL AND dmarket_title IS NOT NULL
+                ) AS "linked!"
+            FROM catalog_item
+            "#
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        log::info!(
+            "Catalog has {} items, {} on both markets",
+            summary.items,
+            summary.linked
+        );
+        Ok(summary)
+    }
+
+    /// Adds `name` to the catalog unless it is already there, returning the ID of its item.
+    async fn upsert(tx: &mut Transaction<'_, Postgres>, name: &ItemName) -> Result<i32> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            INSERT INTO catalog_item (key, base, wear, stattrak, souvenir, star, phase)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (key) DO UPDATE SET key = EXCLUDED.key
+            RETURNING id
+            "#,
+            name.key(),
+            name.base,
+            name.wear.map(Wear::as_str),
+            name.stattrak,
+            name.souvenir,
+            name.star,
+            name.phase
+        )
+        .fetch_one(&mut **tx)
+        .await?)
+    }
+
+    /// The item named `name`, however a market spells it.
+    pub async fn find(&self, name: &str) -> Result<Option<CatalogItem>> {
+        let row = sqlx::query_as!(
+            CatalogRow,
+            r#"
+            SELECT id, base, wear, stattrak, souvenir, star, phase, bitskins_skin_id,
+                dmarket_game_id, dmarket_title
+            FROM catalog_item
+            WHERE key = $1
+            "#,
+            ItemName::parse(name).key()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(CatalogItem::from))
+    }
+
+    pub async fn get(&self, id: i32) -> Result<Option<CatalogItem>> {
+        let row = sqlx::query_as!(
+            CatalogRow,
+            r#"
+            SELECT id, base, wear, stattrak, souvenir, star, phase, bitskins_skin_id,
+                dmarket_game_id, dmarket_title
+            FROM catalog_item
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(CatalogItem::from))
+    }
+
+    /// The item of BitSkins skin `skin_id`.
+    pub async fn by_bitskins_skin(&self, skin_id: i32) -> Result<Option<CatalogItem>> {
+        let row = sqlx::query_as!(
+            CatalogRow,
+            r#"
+            SELECT id, base, wear, stattrak, souvenir, star, phase, bitskins_skin_id,
+                dmarket_game_id, dmarket_title
+            FROM catalog_item
+            WHERE bitskins_skin_id = $1
+            "#,
+            skin_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(CatalogItem::from))
+    }
+
+    /// The item of `title` in DMarket game `game_id`.
+    pub async fn by_dmarket_title(
+        &self,
+        game_id: &str,
+        title: &str,
+    ) -> Result<Option<CatalogItem>> {
+        let row = sqlx::query_as!(
+            CatalogRow,
+            r#"
+            SELECT id, base, wear, stattrak, souvenir, star, phase, bitskins_skin_id,
+                dmarket_game_id, dmarket_title
+            FROM catalog_item
+            WHERE dmarket_game_id = $1 AND dmarket_title = $2
+            "#,
+            game_id,
+            title
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(CatalogItem::from))
+    }
+
+    /// Items listed on both markets.
+    pub async fn get_linked(&self) -> Result<Vec<CatalogItem>> {
+        let rows = sqlx::query_as!(
+            CatalogRow,
+            r#"
+            SELECT id, base, wear, stattrak, souvenir, star, phase, bitskins_skin_id,
+                dmarket_game_id, dmarket_title
+            FROM catalog_item
+            WHERE bitskins_skin_id IS NOT NULL AND dmarket_title IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(CatalogItem::from).collect())
+    }
+
+    /// Fetches Steam reference prices for the `limit` items whose prices are oldest. Doppler
+    /// phases share a name on Steam and therefore a reference.
+    pub async fn sync_steam_prices(&self, client: &SteamClient, limit: i64) -> Result<usize> {
+        let rows = sqlx::query_as!(
+            CatalogRow,
+            r#"
+            SELECT ci.id, ci.base, ci.wear, ci.stattrak, ci.souvenir, ci.star, ci.phase,
+                ci.bitskins_skin_id, ci.dmarket_game_id, ci.dmarket_title
+            FROM catalog_item ci
+            LEFT JOIN steam_reference_price srp ON srp.catalog_item_id = ci.id
+            WHERE ci.bitskins_skin_id IS NOT NULL OR ci.dmarket_title IS NOT NULL
+            ORDER BY srp.fetched_at NULLS FIRST, ci.id
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut references = HashMap::new();
+        let mut stored = 0;
+        for item in rows.into_iter().map(CatalogItem::from) {
+            let name = item.name.to_string();
+            if !references.contains_key(&name) {
+                let reference = match fetch_reference(client, &name).await {
+                    Ok(reference) => reference,
+                    Err(e) => {
+                        log::error!("Error fetching Steam prices of {name}: {e}");
+                        continue;
+                    }
+                };
+                references.insert(name.clone(), reference);
+            }
+            if let Some(reference) = &references[&name] {
+                self.store_reference_price(item.id, reference).await?;
+                stored += 1;
+            }
+        }
+        log::info!("Stored Steam reference prices of {stored} items");
+        Ok(stored)
+    }
+
+    pub async fn store_reference_price(
+        &self,
+        catalog_item_id: i32,
+        reference: &ReferencePrice,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO steam_reference_price (
+                catalog_item_id, median_price, lowest_price, daily_volume, monthly_volume, fetched_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (catalog_item_id) DO UPDATE SET
+                median_price = EXCLUDED.median_price,
+                lowest_price = EXCLUDED.lowest_price,
+                daily_volume = EXCLUDED.daily_volume,
+                monthly_volume = EXCLUDED.monthly_volume,
+                fetched_at = EXCLUDED.fetched_at
+            "#,
+            catalog_item_id,
+            reference.median_price.mills(),
+            reference.lowest_price.map(Money::mills),
+            reference.daily_volume,
+            reference.monthly_volume,
+            reference.fetched_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_reference_price(
+        &self,
+        catalog_item_id: i32,
+    ) -> Result<Option<ReferencePrice>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT median_price, lowest_price, daily_volume, monthly_volume, fetched_at
+            FROM steam_reference_price
+            WHERE catalog_item_id = $1
+            "#,
+            catalog_item_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| ReferencePrice {
+            median_price: Money::from_mills(row.median_price),
+            lowest_price: row.lowest_price.map(Money::from_mills),
+            daily_volume: row.daily_volume,
+            monthly_volume: row.monthly_volume,
+            fetched_at: row.fetched_at,
+        }))
+    }
+}
+
+/// The reference price of `market_hash_name` from its overview and history on Steam.
+async fn fetch_reference(
+    client: &SteamClient,
+    market_hash_name: &str,
+) -> Result<Option<ReferencePrice>> {
+    let overview = client.price_overview(market_hash_name).await?;
+    let history = client.price_history(market_hash_name).await?;
+    Ok(ReferencePrice::from_market(
+        overview.as_ref(),
+        history.as_ref(),
+        OffsetDateTime::now_utc(),
+    ))
+}
@@ -0,0 +1,26 @@
+use std::{env, io};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("EnvVar error: {0}")]
+    EnvVar(#[from] env::VarError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("HTTP request error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Failed to deserialize response: {0}")]
+    Deserialize(#[from] common::quarantine::ParseError),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Steam error: {0}")]
+    Steam(String),
+}
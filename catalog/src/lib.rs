@@ -0,0 +1,20 @@
+//! One canonical item for every item the markets trade.
+//!
+//! BitSkins knows items as skins, DMarket as titles within a game, and both spell market hash
+//! names slightly differently. The catalog normalizes the names into an [`ItemName`] and links
+//! the skins and titles that refer to the same item, so analytics and cross-market features can
+//! refer to a single [`CatalogItem`].
+//!
+//! Steam Community Market prices of catalog items serve as a [`ReferencePrice`] that purchases on
+//! the other markets are checked against.
+mod db;
+mod error;
+pub mod name;
+pub mod steam;
+
+pub use db::{Catalog, CatalogItem, SyncSummary};
+pub use error::Error;
+pub use name::{ItemName, Wear};
+pub use steam::{ReferencePrice, SteamClient};
+
+pub type Result<T> = std::result::Result<T, Error>;
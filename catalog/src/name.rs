@@ -0,0 +1,206 @@
+//! Market hash names and their normalized form.
+use serde::Serialize;
+use std::fmt;
+
+const STAR: &str = "★";
+const STATTRAK: &str = "StatTrak™";
+const SOUVENIR: &str = "Souvenir";
+
+/// Variants of Doppler finishes, which Steam lists under a single name
+const PHASES: [&str; 8] = [
+    "Phase 1",
+    "Phase 2",
+    "Phase 3",
+    "Phase 4",
+    "Ruby",
+    "Sapphire",
+    "Black Pearl",
+    "Emerald",
+];
+
+/// How worn a skin is, from its float value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum Wear {
+    FactoryNew,
+    MinimalWear,
+    FieldTested,
+    WellWorn,
+    BattleScarred,
+}
+
+impl Wear {
+    pub const ALL: [Self; 5] = [
+        Self::FactoryNew,
+        Self::MinimalWear,
+        Self::FieldTested,
+        Self::WellWorn,
+        Self::BattleScarred,
+    ];
+
+    /// The wear as it appears in market hash names.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::FactoryNew => "Factory New",
+            Self::MinimalWear => "Minimal Wear",
+            Self::FieldTested => "Field-Tested",
+            Self::WellWorn => "Well-Worn",
+            Self::BattleScarred => "Battle-Scarred",
+        }
+    }
+
+    fn abbreviation(self) -> &'static str {
+        match self {
+            Self::FactoryNew => "FN",
+            Self::MinimalWear => "MW",
+            Self::FieldTested => "FT",
+            Self::WellWorn => "WW",
+            Self::BattleScarred => "BS",
+        }
+    }
+
+    /// Parses a wear written out, with or without the hyphen, or abbreviated.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().replace('-', " ");
+        Self::ALL.into_iter().find(|wear| {
+            text.eq_ignore_ascii_case(&wear.as_str().replace('-', " "))
+                || text.eq_ignore_ascii_case(wear.abbreviation())
+        })
+    }
+}
+
+impl fmt::Display for Wear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A market hash name taken apart, e.g. `★ StatTrak™ Karambit | Doppler (Factory New)` with
+/// phase `Phase 2`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct ItemName {
+    /// The name without any of the other parts, e.g. `Karambit | Doppler`
+    pub base: String,
+    pub wear: Option<Wear>,
+    pub stattrak: bool,
+    pub souvenir: bool,
+    /// Knives and gloves, whose names start with a star
+    pub star: bool,
+    /// Variant of a Doppler finish, e.g. `Phase 2` or `Ruby`
+    pub phase: Option<String>,
+}
+
+impl ItemName {
+    /// Takes apart a name as any market spells it. The phase may follow the finish, as in
+    /// `Doppler Phase 2`, or the whole name, as in `Doppler (Factory New) - Phase 2`.
+    pub fn parse(name: &str) -> Self {
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut rest = name.as_str();
+
+        let star = strip_prefix(&mut rest, STAR);
+        let stattrak = strip_prefix(&mut rest, STATTRAK) || strip_prefix(&mut rest, "StatTrak");
+        let souvenir = strip_prefix(&mut rest, SOUVENIR);
+
+        let mut phase = None;
+        if let Some((head, tail)) = rest.rsplit_once(" - ") {
+            if let Some(found) = find_phase(tail) {
+                phase = Some(found);
+                rest = head;
+            }
+        }
+
+        let mut wear = None;
+        if let Some((head, tail)) = rest.rsplit_once(" (") {
+            if let Some(found) = tail.strip_suffix(')').and_then(Wear::parse) {
+                wear = Some(found);
+                rest = head;
+            }
+        }
+
+        if phase.is_none() {
+            for candidate in PHASES {
+                let finish = rest
+                    .len()
+                    .checked_sub(candidate.len() + 1)
+                    .filter(|&at| rest.is_char_boundary(at))
+                    .map(|at| rest.split_at(at))
+                    .filter(|(head, tail)| {
+                        tail.starts_with(' ')
+                            && tail[1..].eq_ignore_ascii_case(candidate)
+                            && head.to_lowercase().ends_with("doppler")
+                    })
+                    .map(|(head, _)| head);
+                if let Some(head) = finish {
+                    phase = Some(candidate.to_string());
+                    rest = head;
+                    break;
+                }
+            }
+        }
+
+        Self {
+            base: rest.trim().to_string(),
+            wear,
+            stattrak,
+            souvenir,
+            star,
+            phase,
+        }
+    }
+
+    /// Identifies the item however a market spells its name: case, whitespace, the star and
+    /// where the phase goes make no difference.
+    pub fn key(&self) -> String {
+        let mut key = String::new();
+        if self.stattrak {
+            key.push_str("stattrak ");
+        }
+        if self.souvenir {
+            key.push_str("souvenir ");
+        }
+        key.push_str(&self.base);
+        if let Some(wear) = self.wear {
+            key.push_str(&format!(" ({wear})"));
+        }
+        if let Some(phase) = &self.phase {
+            key.push_str(&format!(" - {phase}"));
+        }
+        key.to_lowercase()
+    }
+}
+
+/// The market hash name as Steam spells it, which leaves out the phase.
+impl fmt::Display for ItemName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.star {
+            write!(f, "{STAR} ")?;
+        }
+        if self.stattrak {
+            write!(f, "{STATTRAK} ")?;
+        }
+        if self.souvenir {
+            write!(f, "{SOUVENIR} ")?;
+        }
+        write!(f, "{}", self.base)?;
+        if let Some(wear) = self.wear {
+            write!(f, " ({wear})")?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_prefix(rest: &mut &str, prefix: &str) -> bool {
+    match rest.strip_prefix(prefix) {
+        Some(tail) => {
+            *rest = tail.trim_start();
+            true
+        }
+        None => false,
+    }
+}
+
+fn find_phase(text: &str) -> Option<String> {
+    PHASES
+        .into_iter()
+        .find(|phase| text.trim().eq_ignore_ascii_case(phase))
+        .map(str::to_string)
+}
@@ -0,0 +1,350 @@
+//! Reference prices from the Steam Community Market.
+//!
+//! Steam is the deepest market for most items, so its prices tell whether a price on another
+//! market is out of line. The price overview gives the lowest listing and the median of the last
+//! day's sales, the price history (which needs a logged-in session) the sales of every hour.
+use crate::error::Error;
+use crate::Result;
+use common::{quarantine, secrets, Failure, Limit, Money, RateLimiter, RetryPolicy, Rounding};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, Time};
+use tokio::time::sleep;
+
+pub(crate) const BASE_URL: &str = "https://steamcommunity.com";
+/// Steam's app ID of CS2
+pub const CS2_APP_ID: u32 = 730;
+/// Steam's ID of the US dollar
+const CURRENCY_USD: u32 = 1;
+/// Session cookie the price history requires
+const LOGIN_COOKIE: &str = "STEAM_LOGIN_SECURE";
+
+/// Sales the median of the history is taken over
+const HISTORY_WINDOW: time::Duration = time::Duration::days(30);
+/// References older than this are not trusted
+const MAX_AGE: time::Duration = time::Duration::days(7);
+/// Items selling less than this on Steam in a month are too illiquid to value
+const MIN_MONTHLY_VOLUME: i32 = 30;
+
+/// Lowest listing and the median of the last day's sales of an item.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PriceOverview {
+    #[serde(default)]
+    pub success: bool,
+    #[serde(default, deserialize_with = "steam_price")]
+    pub lowest_price: Option<Money>,
+    #[serde(default, deserialize_with = "steam_price")]
+    pub median_price: Option<Money>,
+    /// Sales in the last day
+    #[serde(default, deserialize_with = "steam_volume")]
+    pub volume: Option<i32>,
+}
+
+/// Median price and volume of the sales of an item, one point per hour or day.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PriceHistory {
+    #[serde(default)]
+    pub success: bool,
+    #[serde(default)]
+    pub prices: Vec<HistoryPoint>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryPoint {
+    pub time: OffsetDateTime,
+    pub price: Money,
+    pub volume: i32,
+}
+
+/// Points are sent as `["Oct 18 2026 01: +0", 1.234, "12"]`.
+impl<'de> Deserialize<'de> for HistoryPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+        let (time, price, volume) = <(String, f64, String)>::deserialize(deserializer)?;
+        Ok(Self {
+            time: parse_history_time(&time)
+                .ok_or_else(|| D::Error::custom(format!("invalid time {time}")))?,
+            price: Money::from_dollars(price, Rounding::Nearest),
+            volume: parse_volume(&volume)
+                .ok_or_else(|| D::Error::custom(format!("invalid volume {volume}")))?,
+        })
+    }
+}
+
+fn parse_history_time(text: &str) -> Option<OffsetDateTime> {
+    let (date, hour) = text.rsplit_once(' ')?.0.rsplit_once(' ')?;
+    let date = Date::parse(date, format_description!("[month repr:short] [day] [year]")).ok()?;
+    let hour = hour.strip_suffix(':')?.parse().ok()?;
+    Some(
+        date.with_time(Time::from_hms(hour, 0, 0).ok()?)
+            .assume_utc(),
+    )
+}
+
+/// Prices are formatted for display, e.g. `$1,234.56`.
+fn parse_price(text: &str) -> Option<Money> {
+    let amount: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    Money::parse_dollars(&amount).ok()
+}
+
+fn parse_volume(text: &str) -> Option<i32> {
+    text.replace(',', "").trim().parse().ok()
+}
+
+fn steam_price<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Money>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?
+        .as_deref()
+        .and_then(parse_price))
+}
+
+fn steam_volume<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<i32>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?
+        .as_deref()
+        .and_then(parse_volume))
+}
+
+/// Responses for an item as stored in a fixture file.
+#[derive(Deserialize, Debug, Clone, Default)]
+struct Fixture {
+    overview: Option<PriceOverview>,
+    history: Option<PriceHistory>,
+}
+
+#[derive(Clone)]
+enum Source {
+    Live {
+        client: reqwest::Client,
+        base_url: String,
+        login: Option<secrets::Secret>,
+        limiter: RateLimiter<()>,
+        retry_policy: RetryPolicy,
+    },
+    /// Responses by market hash name
+    Fixtures(HashMap<String, Fixture>),
+}
+
+/// Fetches prices from the Steam Community Market, or from recorded responses in tests.
+#[derive(Clone)]
+pub struct SteamClient {
+    source: Source,
+}
+
+impl SteamClient {
+    /// Creates a client for the live market. The price history is only fetched when the
+    /// `STEAM_LOGIN_SECURE` secret holds a session cookie.
+    pub fn new() -> Self {
+        Self::with_base_url(BASE_URL)
+    }
+
+    /// Creates a client that sends every request to `base_url` instead of the live market.
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            source: Source::Live {
+                client: reqwest::Client::new(),
+                base_url: base_url.trim_end_matches('/').to_string(),
+                login: secrets::get(LOGIN_COOKIE).ok(),
+                // Steam allows about 20 requests a minute before blocking for a while
+                limiter: RateLimiter::new([((), Limit::new(1.0 / 3.0, 1))]),
+                retry_policy: RetryPolicy::default(),
+            },
+        }
+    }
+
+    /// Creates a client answering from a JSON file mapping market hash names to their
+    /// `overview` and `history` responses. Items missing from the file have no prices.
+    pub fn from_fixtures(path: impl AsRef<Path>) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self {
+            source: Source::Fixtures(serde_json::from_str(&text)?),
+        })
+    }
+
+    /// The price overview of `market_hash_name`, if Steam has one.
+    pub async fn price_overview(&self, market_hash_name: &str) -> Result<Option<PriceOverview>> {
+        let overview = match &self.source {
+            Source::Fixtures(fixtures) => fixtures
+                .get(market_hash_name)
+                .and_then(|fixture| fixture.overview.clone()),
+            Source::Live { .. } => self.get("/market/priceoverview/", market_hash_name).await?,
+        };
+        Ok(overview.filter(|overview| overview.success))
+    }
+
+    /// The price history of `market_hash_name`, if Steam has one and we are logged in.
+    pub async fn price_history(&self, market_hash_name: &str) -> Result<Option<PriceHistory>> {
+        let history = match &self.source {
+            Source::Fixtures(fixtures) => fixtures
+                .get(market_hash_name)
+                .and_then(|fixture| fixture.history.clone()),
+            Source::Live { login: None, .. } => None,
+            Source::Live { .. } => self.get("/market/pricehistory/", market_hash_name).await?,
+        };
+        Ok(history.filter(|history| history.success))
+    }
+
+    /// Sends a request for `market_hash_name`, treating a missing item as no response. Every
+    /// request only reads prices, so failures are retried as long as the policy allows.
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        market_hash_name: &str,
+    ) -> Result<Option<T>> {
+        let Source::Live {
+            client,
+            base_url,
+            login,
+            limiter,
+            retry_policy,
+        } = &self.source
+        else {
+            return Ok(None);
+        };
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            limiter.acquire(()).await;
+            let mut request = client.get(format!("{base_url}{path}")).query(&[
+                ("appid", CS2_APP_ID.to_string()),
+                ("currency", CURRENCY_USD.to_string()),
+                ("market_hash_name", market_hash_name.to_string()),
+            ]);
+            if let Some(login) = login {
+                request = request.header(
+                    reqwest::header::COOKIE,
+                    format!("steamLoginSecure={}", login.expose()),
+                );
+            }
+
+            let (failure, error) = match request.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    limiter.record((), status, response.headers());
+                    match status {
+                        reqwest::StatusCode::NOT_FOUND => return Ok(None),
+                        status if status.is_success() => {
+                            let text = response.text().await?;
+                            return Ok(Some(quarantine::from_str("steam", path, &text)?));
+                        }
+                        status => (
+                            Failure::Status(status),
+                            Error::Steam(format!("{status} for {path} of {market_hash_name}")),
+                        ),
+                    }
+                }
+                Err(e) => (
+                    Failure::Transport {
+                        sent: !e.is_connect(),
+                    },
+                    e.into(),
+                ),
+            };
+
+            if !retry_policy.should_retry(attempt, &failure, true) {
+                log::warn!("Request to {path} failed after {attempt} attempt(s): {failure}");
+                return Err(error);
+            }
+            let delay = retry_policy.backoff(attempt);
+            log::warn!("Request to {path} failed ({failure}). Retrying in {delay:?}");
+            sleep(delay).await;
+        }
+    }
+}
+
+impl Default for SteamClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What Steam says an item is worth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferencePrice {
+    /// Median of the sales of the last 30 days, or of the last day without a history
+    pub median_price: Money,
+    pub lowest_price: Option<Money>,
+    /// Sales in the last day, if Steam reported them
+    pub daily_volume: Option<i32>,
+    /// Sales in the last 30 days, if the history is known
+    pub monthly_volume: Option<i32>,
+    pub fetched_at: OffsetDateTime,
+}
+
+impl ReferencePrice {
+    /// Combines the responses for an item, if they contain any price at all.
+    pub fn from_market(
+        overview: Option<&PriceOverview>,
+        history: Option<&PriceHistory>,
+        now: OffsetDateTime,
+    ) -> Option<Self> {
+        let recent: Vec<_> = history
+            .map(|history| {
+                history
+                    .prices
+                    .iter()
+                    .filter(|point| point.time >= now - HISTORY_WINDOW)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let median_price = weighted_median(&recent)
+            .or(overview.and_then(|overview| overview.median_price))
+            .or(overview.and_then(|overview| overview.lowest_price))?;
+        Some(Self {
+            median_price,
+            lowest_price: overview.and_then(|overview| overview.lowest_price),
+            daily_volume: overview.and_then(|overview| overview.volume),
+            monthly_volume: history.map(|_| recent.iter().map(|point| point.volume).sum()),
+            fetched_at: now,
+        })
+    }
+
+    /// Whether the reference is too old to be trusted at `now`.
+    pub fn is_stale(&self, now: OffsetDateTime) -> bool {
+        now - self.fetched_at > MAX_AGE
+    }
+
+    /// Explains why buying for `price` looks like a mistake given the reference, if it does:
+    /// other markets rarely sell above Steam, and an item that hardly sells on Steam cannot be
+    /// valued with confidence. Stale references raise no objections, and neither does an
+    /// unknown volume.
+    pub fn objection(&self, price: Money, now: OffsetDateTime) -> Option<String> {
+        if self.is_stale(now) {
+            return None;
+        }
+        if price > self.median_price {
+            return Some(format!(
+                "{price} is above the Steam median of {}",
+                self.median_price
+            ));
+        }
+        let monthly_volume = self
+            .monthly_volume
+            .or(self.daily_volume.map(|volume| volume.saturating_mul(30)));
+        monthly_volume
+            .filter(|volume| *volume < MIN_MONTHLY_VOLUME)
+            .map(|volume| format!("only {volume} sales on Steam in the last 30 days"))
+    }
+}
+
+/// Price at which half of the volume sold lower and half higher.
+fn weighted_median(points: &[&HistoryPoint]) -> Option<Money> {
+    let mut points = points.to_vec();
+    points.sort_by_key(|point| point.price.mills());
+    let total: i64 = points.iter().map(|point| i64::from(point.volume)).sum();
+    let mut seen = 0;
+    points.into_iter().find_map(|point| {
+        seen += i64::from(point.volume);
+        (total > 0 && seen * 2 >= total).then_some(point.price)
+    })
+}
@@ -0,0 +1,118 @@
+use catalog::{Catalog, ItemName, Wear};
+use sqlx::PgPool;
+
+const CS2_GAME_ID: &str = "a8db";
+
+#[test]
+fn names_are_taken_apart() {
+    let name = ItemName::parse("★ StatTrak™ Karambit | Doppler (Factory New) - Phase 2");
+    assert_eq!(name.base, "Karambit | Doppler");
+    assert_eq!(name.wear, Some(Wear::FactoryNew));
+    assert!(name.star && name.stattrak && !name.souvenir);
+    assert_eq!(name.phase.as_deref(), Some("Phase 2"));
+    assert_eq!(
+        name.to_string(),
+        "★ StatTrak™ Karambit | Doppler (Factory New)"
+    );
+
+    let name = ItemName::parse("Souvenir AWP | Dragon Lore (Battle-Scarred)");
+    assert_eq!(name.base, "AWP | Dragon Lore");
+    assert_eq!(name.wear, Some(Wear::BattleScarred));
+    assert!(name.souvenir && !name.stattrak && !name.star);
+
+    let name = ItemName::parse("Operation Breakout Weapon Case");
+    assert_eq!(name.base, "Operation Breakout Weapon Case");
+    assert_eq!(name.wear, None);
+}
+
+#[test]
+fn spellings_of_an_item_share_a_key() {
+    let key = ItemName::parse("★ Karambit | Doppler (Factory New) - Ruby").key();
+    assert_eq!(
+        ItemName::parse("Karambit  |  Doppler Ruby (factory new)").key(),
+        key
+    );
+    assert_eq!(ItemName::parse("★ Karambit | Doppler Ruby (FN)").key(), key);
+    assert_ne!(
+        ItemName::parse("★ Karambit | Doppler (Factory New) - Phase 1").key(),
+        key
+    );
+    assert_ne!(
+        ItemName::parse("StatTrak™ AK-47 | Redline (Field-Tested)").key(),
+        ItemName::parse("AK-47 | Redline (Field-Tested)").key()
+    );
+    assert_eq!(Wear::parse("field tested"), Some(Wear::FieldTested));
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn sync_links_skins_to_titles(pool: PgPool) -> sqlx::Result<()> {
+    for (id, name) in [
+        (1, "★ Karambit | Doppler (Factory New) - Phase 2"),
+        (2, "AK-47 | Redline (Field-Tested)"),
+    ] {
+        sqlx::query("INSERT INTO Skin (id, name, class_id) VALUES ($1, $2, '')")
+            .bind(id)
+            .bind(name)
+            .execute(&pool)
+            .await?;
+    }
+    for title in [
+        "★ Karambit | Doppler Phase 2 (Factory New)",
+        "AWP | Asiimov (Field-Tested)",
+    ] {
+        sqlx::query("INSERT INTO dmarket_game_titles (game_id, title) VALUES ($1, $2)")
+            .bind(CS2_GAME_ID)
+            .bind(title)
+            .execute(&pool)
+            .await?;
+    }
+
+    let catalog = Catalog::from_pool(pool);
+    let summary = catalog.sync().await.unwrap();
+    assert_eq!(
+        (
+            summary.items,
+            summary.bitskins,
+            summary.dmarket,
+            summary.linked
+        ),
+        (3, 2, 2, 1)
+    );
+    // Syncing again neither duplicates items nor loses links
+    let summary = catalog.sync().await.unwrap();
+    assert_eq!((summary.items, summary.linked), (3, 1));
+
+    let item = catalog
+        .by_bitskins_skin(1)
+        .await
+        .unwrap()
+        .expect("skin is in the catalog");
+    assert_eq!(
+        item.dmarket_title,
+        Some((
+            CS2_GAME_ID.to_string(),
+            "★ Karambit | Doppler Phase 2 (Factory New)".to_string()
+        ))
+    );
+    let found = catalog
+        .by_dmarket_title(CS2_GAME_ID, "★ Karambit | Doppler Phase 2 (Factory New)")
+        .await
+        .unwrap();
+    assert_eq!(found.map(|found| found.id), Some(item.id));
+    let found = catalog
+        .find("karambit | doppler phase 2 (fn)")
+        .await
+        .unwrap();
+    assert_eq!(found.map(|found| found.id), Some(item.id));
+    assert!(catalog
+        .find("AWP | Asiimov (Field-Tested)")
+        .await
+        .unwrap()
+        .is_some());
+    assert!(catalog.find("M4A4 | Howl").await.unwrap().is_none());
+
+    let linked = catalog.get_linked().await.unwrap();
+    assert_eq!(linked.len(), 1);
+    assert_eq!(linked[0].name.phase.as_deref(), Some("Phase 2"));
+    Ok(())
+}
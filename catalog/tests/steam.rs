@@ -0,0 +1,117 @@
+use catalog::steam::PriceOverview;
+use catalog::{Catalog, ReferencePrice, SteamClient};
+use common::Money;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::PgPool;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/steam.json");
+const REDLINE: &str = "AK-47 | Redline (Field-Tested)";
+
+#[tokio::test]
+async fn references_combine_overview_and_history() {
+    let client = SteamClient::from_fixtures(FIXTURES).unwrap();
+    let overview = client.price_overview(REDLINE).await.unwrap();
+    let history = client.price_history(REDLINE).await.unwrap();
+    let now = OffsetDateTime::from_unix_timestamp(1_711_324_800).unwrap(); // 2024-03-25
+
+    let reference = ReferencePrice::from_market(overview.as_ref(), history.as_ref(), now).unwrap();
+    // The January sales are outside the window
+    assert_eq!(reference.median_price, Money::from_cents(1_300));
+    assert_eq!(reference.monthly_volume, Some(55));
+    assert_eq!(reference.lowest_price, Some(Money::from_cents(1_250)));
+    assert_eq!(reference.daily_volume, Some(1_204));
+
+    assert_eq!(reference.objection(Money::from_cents(1_200), now), None);
+    assert!(reference.objection(Money::from_cents(1_301), now).is_some());
+    let later = now + time::Duration::days(8);
+    assert_eq!(reference.objection(Money::from_cents(1_301), later), None);
+
+    assert!(client
+        .price_overview("AWP | Asiimov (Field-Tested)")
+        .await
+        .unwrap()
+        .is_none());
+    assert!(client
+        .price_overview("M4A4 | Howl")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn only_known_low_volumes_raise_objections() {
+    let now = OffsetDateTime::now_utc();
+    let mut overview = PriceOverview {
+        success: true,
+        lowest_price: Some(Money::from_cents(1_050)),
+        median_price: Some(Money::from_cents(1_000)),
+        volume: None,
+    };
+
+    let reference = ReferencePrice::from_market(Some(&overview), None, now).unwrap();
+    assert_eq!(reference.median_price, Money::from_cents(1_000));
+    assert_eq!(reference.daily_volume, None);
+    assert_eq!(reference.monthly_volume, None);
+    assert_eq!(reference.objection(Money::from_cents(900), now), None);
+
+    overview.volume = Some(0);
+    let reference = ReferencePrice::from_market(Some(&overview), None, now).unwrap();
+    assert!(reference
+        .objection(Money::from_cents(900), now)
+        .is_some_and(|reason| reason.contains("only 0 sales on Steam")));
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn sync_stores_references_of_catalog_items(pool: PgPool) -> sqlx::Result<()> {
+    for (id, name) in [
+        (1, REDLINE),
+        (2, "★ Karambit | Doppler (Factory New) - Phase 2"),
+        (3, "★ Karambit | Doppler (Factory New) - Ruby"),
+        (4, "AWP | Asiimov (Field-Tested)"),
+    ] {
+        sqlx::query("INSERT INTO Skin (id, name, class_id) VALUES ($1, $2, '')")
+            .bind(id)
+            .bind(name)
+            .execute(&pool)
+            .await?;
+    }
+    let catalog = Catalog::from_pool(pool);
+    catalog.sync().await.unwrap();
+
+    let client = SteamClient::from_fixtures(FIXTURES).unwrap();
+    assert_eq!(catalog.sync_steam_prices(&client, 10).await.unwrap(), 3);
+
+    let now = OffsetDateTime::now_utc();
+    let redline = catalog.by_bitskins_skin(1).await.unwrap().unwrap();
+    let reference = catalog
+        .get_reference_price(redline.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reference.median_price, Money::from_cents(1_234));
+    assert!(reference
+        .objection(Money::from_cents(1_235), now)
+        .is_some_and(|reason| reason.contains("above the Steam median")));
+
+    // Both phases get the reference of the name Steam lists them under
+    for skin_id in [2, 3] {
+        let doppler = catalog.by_bitskins_skin(skin_id).await.unwrap().unwrap();
+        let reference = catalog
+            .get_reference_price(doppler.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reference.median_price, Money::from_cents(120_000));
+        // Steam reports no volume for them, which is no reason to object
+        assert_eq!(reference.daily_volume, None);
+        assert_eq!(reference.objection(Money::from_cents(100_000), now), None);
+    }
+
+    let asiimov = catalog.by_bitskins_skin(4).await.unwrap().unwrap();
+    assert!(catalog
+        .get_reference_price(asiimov.id)
+        .await
+        .unwrap()
+        .is_none());
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! Options and output shared by the command-line interfaces of the market binaries.
+use clap::Args;
+use serde::Serialize;
+use std::fmt::Debug;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("No enabled {0} accounts")]
+    NoAccounts(&'static str),
+
+    #[error("No enabled {0} account named {1}")]
+    UnknownAccount(&'static str, String),
+
+    #[error("Failed to print the result as JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Options every subcommand accepts.
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
+    /// Account to act as, the first enabled one by default
+    #[arg(long, global = true)]
+    pub account: Option<String>,
+
+    /// Show what would be done without changing anything on the market
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+}
+
+impl GlobalArgs {
+    /// Picks the trader of the selected account from those of the enabled accounts of `market`,
+    /// `name` giving the account name of a trader.
+    pub fn select<T>(
+        &self,
+        market: &'static str,
+        traders: Vec<T>,
+        name: impl Fn(&T) -> &str,
+    ) -> Result<T> {
+        let mut traders = traders.into_iter();
+        match &self.account {
+            Some(account) => traders
+                .find(|trader| name(trader) == account)
+                .ok_or_else(|| Error::UnknownAccount(market, account.clone())),
+            None => traders.next().ok_or(Error::NoAccounts(market)),
+        }
+    }
+
+    /// Prints `value` as JSON or in its debug representation.
+    pub fn print<T: Serialize + Debug>(&self, value: &T) -> Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            println!("{value:#?}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+//! Raw API responses sampled to notice when a market changes the shape of its data.
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Objects returned by one endpoint, checked against the struct they are deserialized into.
+#[derive(Debug)]
+pub struct ResponseSample {
+    /// Path of the endpoint, followed by the JSON pointer to the objects within the response
+    pub name: String,
+    pub objects: Vec<Value>,
+    /// Why objects could not be deserialized, one entry per rejected object
+    pub errors: Vec<String>,
+}
+
+impl ResponseSample {
+    /// Samples the objects at the JSON `pointer` of `response`, a single object or an array of
+    /// them, and checks each one deserializes into `T`.
+    pub fn new<T: DeserializeOwned>(endpoint: &str, response: &Value, pointer: &str) -> Self {
+        let name = format!("{endpoint} {pointer}").trim_end().to_string();
+        let objects = match response.pointer(pointer) {
+            Some(Value::Array(objects)) => objects.clone(),
+            Some(object) => vec![object.clone()],
+            None => {
+                return Self {
+                    errors: vec![format!("Nothing at {pointer:?} in the response")],
+                    name,
+                    objects: Vec::new(),
+                }
+            }
+        };
+        let errors = objects
+            .iter()
+            .filter_map(|object| T::deserialize(object).err())
+            .map(|e| e.to_string())
+            .collect();
+
+        Self {
+            name,
+            objects,
+            errors,
+        }
+    }
+}
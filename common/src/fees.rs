@@ -0,0 +1,92 @@
+//! Fees markets charge on sales, and what they leave of a sale price.
+//!
+//! Every market charges a rate of the sale price rounded up to whole cents, with a minimum fee.
+//! The rate may depend on the price, e.g. a reduced rate on a title that only applies within a
+//! price range. Profit calculations go through a [`FeeSchedule`] so they all agree on the fee.
+use crate::money::{Money, Rounding};
+
+/// A rate that applies to sale prices within a range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bracket {
+    /// Lowest price the rate applies to, if any
+    pub min_price: Option<Money>,
+    /// Highest price the rate applies to, if any
+    pub max_price: Option<Money>,
+    pub rate: f64,
+}
+
+impl Bracket {
+    /// A rate that applies to any price.
+    pub fn any_price(rate: f64) -> Self {
+        Self {
+            min_price: None,
+            max_price: None,
+            rate,
+        }
+    }
+
+    pub fn contains(&self, price: Money) -> bool {
+        self.min_price.is_none_or(|min| price >= min)
+            && self.max_price.is_none_or(|max| price <= max)
+    }
+}
+
+/// The fees of a market on the sale of an item.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeSchedule {
+    /// Rate charged when no bracket applies
+    pub rate: f64,
+    /// Least the market charges on a sale
+    pub min_fee: Money,
+    /// Rates for specific price ranges, the first matching one applies
+    pub brackets: Vec<Bracket>,
+}
+
+impl FeeSchedule {
+    /// A schedule charging `rate` on every price, but at least `min_fee`.
+    pub fn flat(rate: f64, min_fee: Money) -> Self {
+        Self {
+            rate,
+            min_fee,
+            brackets: Vec::new(),
+        }
+    }
+
+    /// Adds a rate taking precedence over the default and previously added brackets.
+    pub fn with_bracket(mut self, bracket: Bracket) -> Self {
+        self.brackets.insert(0, bracket);
+        self
+    }
+
+    /// The rate charged on a sale for `price`.
+    pub fn rate(&self, price: Money) -> f64 {
+        self.brackets
+            .iter()
+            .find(|bracket| bracket.contains(price))
+            .map_or(self.rate, |bracket| bracket.rate)
+    }
+
+    /// The fee charged on a sale for `price`.
+    pub fn fee(&self, price: Money) -> Money {
+        price.fee(self.rate(price), self.min_fee)
+    }
+
+    /// What a sale for `price` leaves after the fee.
+    pub fn proceeds(&self, price: Money) -> Money {
+        price - self.fee(price)
+    }
+
+    /// Whether buying for `price` and selling for `sale_price` earns at least `margin` of the
+    /// purchase price.
+    pub fn is_profitable(&self, price: Money, sale_price: Money, margin: f64) -> bool {
+        price.scale(1.0 + margin, Rounding::Up) <= self.proceeds(sale_price)
+    }
+
+    /// The most we can pay, in whole cents, for an item we sell for `sale_price` to still earn
+    /// `margin`.
+    pub fn max_purchase_price(&self, sale_price: Money, margin: f64) -> Money {
+        self.proceeds(sale_price)
+            .scale(1.0 / (1.0 + margin), Rounding::Down)
+            .round_to_cents(Rounding::Down)
+    }
+}
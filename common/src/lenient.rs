@@ -0,0 +1,79 @@
+//! Deserializers that tolerate unexpected data instead of rejecting the whole payload.
+use crate::quarantine::{self, ParseError};
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// A list whose elements that fail to parse are quarantined and left out, so one malformed
+/// element doesn't discard the rest of the page.
+#[derive(Debug)]
+pub struct List<T>(pub Vec<T>);
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for List<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<Value>::deserialize(deserializer)?;
+        let mut items = Vec::with_capacity(values.len());
+        for (index, value) in values.into_iter().enumerate() {
+            match serde_path_to_error::deserialize(&value) {
+                Ok(item) => items.push(item),
+                Err(e) => {
+                    let mut error = ParseError::from(e);
+                    error.path = match error.path.as_str() {
+                        "." => format!("[{index}]"),
+                        path => format!("[{index}].{path}"),
+                    };
+                    quarantine::store_skipped(&error, &value);
+                }
+            }
+        }
+        Ok(Self(items))
+    }
+}
+
+/// A `Vec` field parsed as a [`List`], for use with `#[serde(deserialize_with)]`.
+pub fn list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    List::deserialize(deserializer).map(|list| list.0)
+}
+
+/// A number that may also arrive as a string holding one.
+pub fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + FromStr,
+    T::Err: Display,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(text) => text.trim().parse().map_err(D::Error::custom),
+        value => T::deserialize(value).map_err(D::Error::custom),
+    }
+}
+
+/// An optional [`number`]. Needs `#[serde(default)]` for the field to be omittable.
+pub fn option_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + FromStr,
+    T::Err: Display,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => number(value).map(Some).map_err(D::Error::custom),
+    }
+}
+
+/// A string that may also arrive as a number, kept as the number's text.
+pub fn string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(text) => Ok(text),
+        Value::Number(number) => Ok(number.to_string()),
+        value => Err(D::Error::custom(format!(
+            "invalid type: {value}, expected a string or a number"
+        ))),
+    }
+}
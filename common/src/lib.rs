@@ -1,6 +1,23 @@
 use env_logger::Builder;
 use log::LevelFilter;
 
+pub mod cli;
+pub mod drift;
+pub mod fees;
+pub mod lenient;
+pub mod money;
+pub mod preflight;
+pub mod quarantine;
+pub mod rate_limiter;
+pub mod retry;
+pub mod secrets;
+
+pub use fees::{Bracket, FeeSchedule};
+pub use money::{Money, Rounding};
+pub use rate_limiter::{Limit, LimiterStats, RateLimiter};
+pub use retry::{Failure, RetryPolicy};
+pub use secrets::Secret;
+
 pub fn setup_env() {
     dotenvy::dotenv().ok();
     Builder::new().filter_level(LevelFilter::Info).init();
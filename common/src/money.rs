@@ -0,0 +1,387 @@
+//! Amounts of money as integers, so prices add up exactly and every rounding is deliberate.
+//!
+//! Amounts are kept in thousandths of the currency unit, mills for the dollar, the finest any
+//! market quotes: BitSkins prices in mills and DMarket prices in cents. Money columns in the
+//! database are `BIGINT` mills.
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Neg, Sub};
+use std::str::FromStr;
+
+const MILLS_PER_CENT: i64 = 10;
+const MILLS_PER_UNIT: i64 = 1000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Usd,
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Usd => write!(f, "USD"),
+        }
+    }
+}
+
+/// How to round an amount that falls between two representable ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Towards negative infinity
+    Down,
+    /// Towards positive infinity
+    Up,
+    /// To the closest, halfway cases away from zero
+    Nearest,
+}
+
+impl Rounding {
+    fn divide(self, dividend: i64, divisor: i64) -> i64 {
+        let quotient = dividend.div_euclid(divisor);
+        let remainder = dividend.rem_euclid(divisor);
+        match self {
+            Self::Down => quotient,
+            Self::Up if remainder > 0 => quotient + 1,
+            Self::Up => quotient,
+            Self::Nearest if 2 * remainder > divisor => quotient + 1,
+            Self::Nearest if 2 * remainder == divisor && dividend > 0 => quotient + 1,
+            Self::Nearest => quotient,
+        }
+    }
+
+    fn apply(self, value: f64) -> f64 {
+        // Keeps noise such as 0.07 * 100 = 7.000000000000001 from rounding up to 8
+        let value = (value * 1e6).round() / 1e6;
+        match self {
+            Self::Down => value.floor(),
+            Self::Up => value.ceil(),
+            Self::Nearest => value.round(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("Cannot combine {0} with {1}")]
+    CurrencyMismatch(Currency, Currency),
+
+    #[error("Amount out of range")]
+    Overflow,
+
+    #[error("Invalid amount {0:?}")]
+    Invalid(String),
+}
+
+/// An amount of money in a currency.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Money {
+    mills: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub const ZERO: Self = Self::from_mills(0);
+
+    /// An amount of dollars in thousandths.
+    pub const fn from_mills(mills: i64) -> Self {
+        Self {
+            mills,
+            currency: Currency::Usd,
+        }
+    }
+
+    /// An amount of dollars in cents.
+    pub const fn from_cents(cents: i64) -> Self {
+        Self::from_mills(cents * MILLS_PER_CENT)
+    }
+
+    /// An amount of dollars, rounded to whole mills.
+    pub fn from_dollars(dollars: f64, rounding: Rounding) -> Self {
+        Self::from_mills(rounding.apply(dollars * MILLS_PER_UNIT as f64) as i64)
+    }
+
+    /// Parses a decimal amount of dollars such as `"12.34"` exactly, rejecting anything finer
+    /// than a mill.
+    pub fn parse_dollars(text: &str) -> Result<Self, Error> {
+        let invalid = || Error::Invalid(text.to_string());
+        let text = text.trim();
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => (-1, digits),
+            None => (1, text),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || fraction.len() > 3
+            || !whole
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let whole: i64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        let fraction: i64 = format!("{fraction:0<3}").parse().map_err(|_| invalid())?;
+        whole
+            .checked_mul(MILLS_PER_UNIT)
+            .and_then(|mills| mills.checked_add(fraction))
+            .map(|mills| Self::from_mills(sign * mills))
+            .ok_or(Error::Overflow)
+    }
+
+    /// Parses a whole number of cents such as `"1234"`.
+    pub fn parse_cents(text: &str) -> Result<Self, Error> {
+        let cents: i64 = text
+            .trim()
+            .parse()
+            .map_err(|_| Error::Invalid(text.to_string()))?;
+        cents
+            .checked_mul(MILLS_PER_CENT)
+            .map(Self::from_mills)
+            .ok_or(Error::Overflow)
+    }
+
+    pub fn currency(self) -> Currency {
+        self.currency
+    }
+
+    pub fn mills(self) -> i64 {
+        self.mills
+    }
+
+    /// The amount in whole cents.
+    pub fn cents(self, rounding: Rounding) -> i64 {
+        rounding.divide(self.mills, MILLS_PER_CENT)
+    }
+
+    /// The amount in dollars, for statistics and display. Loses exactness for huge amounts.
+    pub fn dollars(self) -> f64 {
+        self.mills as f64 / MILLS_PER_UNIT as f64
+    }
+
+    /// The amount rounded to whole cents.
+    pub fn round_to_cents(self, rounding: Rounding) -> Self {
+        Self {
+            mills: self.cents(rounding) * MILLS_PER_CENT,
+            ..self
+        }
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.mills > 0
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, Error> {
+        self.same_currency(other)?;
+        self.mills
+            .checked_add(other.mills)
+            .map(|mills| Self { mills, ..self })
+            .ok_or(Error::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, Error> {
+        self.same_currency(other)?;
+        self.mills
+            .checked_sub(other.mills)
+            .map(|mills| Self { mills, ..self })
+            .ok_or(Error::Overflow)
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Result<Self, Error> {
+        self.mills
+            .checked_mul(factor)
+            .map(|mills| Self { mills, ..self })
+            .ok_or(Error::Overflow)
+    }
+
+    /// The amount multiplied by `factor`, such as a fee rate or a margin, rounded to whole mills.
+    pub fn scale(self, factor: f64, rounding: Rounding) -> Self {
+        Self {
+            mills: rounding.apply(self.mills as f64 * factor) as i64,
+            ..self
+        }
+    }
+
+    /// The fee charged on a sale for this amount: `rate` of it rounded up to whole cents, but at
+    /// least `min_fee`.
+    pub fn fee(self, rate: f64, min_fee: Self) -> Self {
+        self.scale(rate, Rounding::Up)
+            .round_to_cents(Rounding::Up)
+            .max(min_fee)
+    }
+
+    /// What is left of a sale for this amount after the [`fee`](Self::fee).
+    pub fn after_fee(self, rate: f64, min_fee: Self) -> Self {
+        self - self.fee(rate, min_fee)
+    }
+
+    /// The larger of two amounts of the same currency.
+    pub fn max(self, other: Self) -> Self {
+        if other > self {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// The smaller of two amounts of the same currency.
+    pub fn min(self, other: Self) -> Self {
+        if other < self {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn same_currency(self, other: Self) -> Result<(), Error> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(Error::CurrencyMismatch(self.currency, other.currency))
+        }
+    }
+}
+
+/// Amounts in different currencies are unordered.
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (self.currency == other.currency).then(|| self.mills.cmp(&other.mills))
+    }
+}
+
+/// Reads amounts stored in the database, where money is kept in mills.
+impl From<i64> for Money {
+    fn from(mills: i64) -> Self {
+        Self::from_mills(mills)
+    }
+}
+
+/// Panics on overflow or mixed currencies, like integer arithmetic in debug builds. Use
+/// [`Money::checked_add`] where either is possible.
+impl Add for Money {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(other).expect("money addition failed")
+    }
+}
+
+/// Panics on overflow or mixed currencies, see [`Money::checked_sub`].
+impl Sub for Money {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(other).expect("money subtraction failed")
+    }
+}
+
+impl Neg for Money {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            mills: -self.mills,
+            ..self
+        }
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.mills < 0 { "-" } else { "" };
+        let mills = self.mills.unsigned_abs();
+        let whole = mills / MILLS_PER_UNIT as u64;
+        let fraction = mills % MILLS_PER_UNIT as u64;
+        let fraction = if fraction.is_multiple_of(MILLS_PER_CENT as u64) {
+            format!("{:02}", fraction / MILLS_PER_CENT as u64)
+        } else {
+            format!("{fraction:03}")
+        };
+        write!(f, "{sign}{whole}.{fraction} {}", self.currency)
+    }
+}
+
+impl FromStr for Money {
+    type Err = Error;
+
+    /// Parses an amount of dollars, see [`Money::parse_dollars`].
+    fn from_str(text: &str) -> Result<Self, Error> {
+        Self::parse_dollars(text)
+    }
+}
+
+/// Serde adapters for the ways markets send amounts, for use with `#[serde(with)]`.
+pub mod serde_mills {
+    use super::*;
+
+    /// A number of mills, possibly sent as a string.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        let mills: f64 = crate::lenient::number(deserializer)?;
+        Ok(Money::from_mills(mills.round() as i64))
+    }
+
+    pub fn serialize<S: Serializer>(money: &Money, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(money.mills)
+    }
+}
+
+/// Optional [`serde_mills`]. Needs `#[serde(default)]` for the field to be omittable.
+pub mod serde_option_mills {
+    use super::*;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Money>, D::Error> {
+        let mills: Option<f64> = crate::lenient::option_number(deserializer)?;
+        Ok(mills.map(|mills| Money::from_mills(mills.round() as i64)))
+    }
+
+    pub fn serialize<S: Serializer>(
+        money: &Option<Money>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        money.map(Money::mills).serialize(serializer)
+    }
+}
+
+/// Whole cents sent as a string, such as `"1234"`.
+pub mod serde_cents {
+    use super::*;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        let text = crate::lenient::string(deserializer)?;
+        Money::parse_cents(&text).map_err(D::Error::custom)
+    }
+
+    pub fn serialize<S: Serializer>(money: &Money, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&money.cents(Rounding::Nearest).to_string())
+    }
+}
+
+/// Dollars sent as a decimal string or number, such as `"12.34"`.
+pub mod serde_dollars {
+    use super::*;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        let text = crate::lenient::string(deserializer)?;
+        Money::parse_dollars(&text).map_err(D::Error::custom)
+    }
+
+    pub fn serialize<S: Serializer>(money: &Money, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(money.dollars())
+    }
+}
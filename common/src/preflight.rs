@@ -0,0 +1,117 @@
+//! Pass/fail report of the startup self-check both binaries run with `preflight`, and the
+//! database checks they share.
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+
+/// Outcome of a single check, with details on success or the reason it failed.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Result<String, String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn pass(&mut self, name: impl Into<String>, detail: impl Display) {
+        self.record(name, Ok::<_, String>(detail));
+    }
+
+    pub fn fail(&mut self, name: impl Into<String>, error: impl Display) {
+        self.record(name, Err::<String, _>(error));
+    }
+
+    pub fn record<T: Display, E: Display>(
+        &mut self,
+        name: impl Into<String>,
+        result: Result<T, E>,
+    ) {
+        self.checks.push(Check {
+            name: name.into(),
+            outcome: result.map(|t| t.to_string()).map_err(|e| e.to_string()),
+        });
+    }
+
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.outcome.is_ok())
+    }
+
+    /// Prints the report and exits, with a non-zero code if any check failed.
+    pub fn exit(&self) -> ! {
+        print!("{self}");
+        std::process::exit(if self.passed() { 0 } else { 1 })
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                Ok(detail) => writeln!(f, "[PASS] {}: {detail}", check.name)?,
+                Err(error) => writeln!(f, "[FAIL] {}: {error}", check.name)?,
+            }
+        }
+        let failed = self.checks.iter().filter(|c| c.outcome.is_err()).count();
+        if failed == 0 {
+            writeln!(f, "All {} checks passed", self.checks.len())
+        } else {
+            writeln!(f, "{failed} of {} checks failed", self.checks.len())
+        }
+    }
+}
+
+/// Versions of the migrations that have not been applied successfully to the database.
+pub async fn get_pending_migrations(pool: &PgPool) -> sqlx::Result<Vec<i64>> {
+    let applied: HashSet<i64> =
+        // Not checked at compile time, the table is created by `sqlx migrate`
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .collect();
+    Ok(sqlx::migrate!("../migrations")
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
+
+/// Outcome of the migrations check: which migrations are not applied, if any.
+pub async fn check_migrations(pool: &PgPool) -> Result<String, String> {
+    match get_pending_migrations(pool)
+        .await
+        .map_err(|e| e.to_string())?[..]
+    {
+        [] => Ok("all applied".to_string()),
+        ref pending => Err(format!("not applied: {pending:?}")),
+    }
+}
+
+/// Outcome of the check that the balance of `account_id` has a row in `table`.
+pub async fn check_balance_row(
+    pool: &PgPool,
+    table: &str,
+    account_id: i32,
+) -> Result<String, String> {
+    match has_balance_row(pool, table, account_id).await {
+        Ok(true) => Ok("present".to_string()),
+        Ok(false) => Err(format!("no row for account_id {account_id}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Whether the balance of `account_id` has a row in `table`, the balance table of a market.
+pub async fn has_balance_row(pool: &PgPool, table: &str, account_id: i32) -> sqlx::Result<bool> {
+    // Not checked at compile time, as the table differs between the markets
+    sqlx::query_scalar(&format!(
+        "SELECT EXISTS(SELECT 1 FROM {table} WHERE account_id = $1)"
+    ))
+    .bind(account_id)
+    .fetch_one(pool)
+    .await
+}
@@ -0,0 +1,182 @@
+//! Payloads that could not be parsed, kept on disk for inspection instead of being dropped.
+//!
+//! Each payload is written as a JSON file to `QUARANTINE_DIR`, `quarantine` by default, along
+//! with the market and endpoint it came from, when it arrived and where parsing failed. A payload
+//! already quarantined for the same endpoint is not written again, and at most
+//! [`MAX_FILES_PER_SOURCE`] files are written per market and endpoint while the process runs.
+//! Payloads beyond that are only counted, see [`dropped`].
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fmt, fs};
+
+const DEFAULT_DIR: &str = "quarantine";
+/// Most payloads written for a single market and endpoint
+pub const MAX_FILES_PER_SOURCE: usize = 100;
+
+/// Distinguishes payloads quarantined within the same instant
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// What has been quarantined so far, by market and endpoint
+static SOURCES: LazyLock<Mutex<HashMap<(String, String), Quarantined>>> =
+    LazyLock::new(Default::default);
+
+tokio::task_local! {
+    /// Market and endpoint of the payload being parsed
+    static SOURCE: (String, String);
+}
+
+#[derive(Default)]
+struct Quarantined {
+    /// Hashes of the payloads written
+    written: HashSet<u64>,
+    /// Payloads not written because they were duplicates or over the limit
+    dropped: usize,
+}
+
+/// Why a payload could not be parsed.
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct ParseError {
+    /// Where in the payload parsing failed, e.g. `list[3].price`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at `{}`", self.message, self.path)
+    }
+}
+
+impl<E: fmt::Display> From<serde_path_to_error::Error<E>> for ParseError {
+    fn from(error: serde_path_to_error::Error<E>) -> Self {
+        Self {
+            path: error.path().to_string(),
+            message: error.inner().to_string(),
+        }
+    }
+}
+
+/// Parses the response `text` of `endpoint` on `market`, quarantining it if that fails.
+pub fn from_str<T: DeserializeOwned>(
+    market: &str,
+    endpoint: &str,
+    text: &str,
+) -> Result<T, ParseError> {
+    let result = with_source(market, endpoint, || {
+        let mut deserializer = serde_json::Deserializer::from_str(text);
+        let value = serde_path_to_error::deserialize(&mut deserializer)?;
+        deserializer.end().map_err(|e| ParseError {
+            path: ".".to_string(),
+            message: e.to_string(),
+        })?;
+        Ok(value)
+    });
+    result.inspect_err(|error| {
+        let payload = serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.into()));
+        store(market, endpoint, error, &payload);
+    })
+}
+
+/// Parses an already decoded `payload` of `endpoint` on `market`, quarantining it if that fails.
+pub fn from_value<T: DeserializeOwned>(
+    market: &str,
+    endpoint: &str,
+    payload: &Value,
+) -> Result<T, ParseError> {
+    with_source(market, endpoint, || {
+        Ok(serde_path_to_error::deserialize(payload)?)
+    })
+    .inspect_err(|error| store(market, endpoint, error, payload))
+}
+
+/// Runs `parse` with `market` and `endpoint` as the source of the payloads it skips.
+fn with_source<T>(market: &str, endpoint: &str, parse: impl FnOnce() -> T) -> T {
+    SOURCE.sync_scope((market.to_string(), endpoint.to_string()), parse)
+}
+
+/// Quarantines part of the payload being parsed by [`from_str`] or [`from_value`], which was
+/// skipped so the rest could be kept.
+pub(crate) fn store_skipped(error: &ParseError, payload: &Value) {
+    let (market, endpoint) = SOURCE
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| ("unknown".to_string(), "unknown".to_string()));
+    store(&market, &endpoint, error, payload);
+}
+
+/// Payloads of `endpoint` on `market` that were not written, as duplicates or over the limit.
+pub fn dropped(market: &str, endpoint: &str) -> usize {
+    SOURCES
+        .lock()
+        .unwrap()
+        .get(&(market.to_string(), endpoint.to_string()))
+        .map_or(0, |quarantined| quarantined.dropped)
+}
+
+/// Writes `payload` to the quarantine directory unless it is a duplicate or over the limit.
+/// Failing to write it is logged, as losing the payload is no reason to fail the request it came
+/// from.
+pub fn store(market: &str, endpoint: &str, error: &ParseError, payload: &Value) {
+    log::warn!("Quarantining payload from {market} {endpoint}: {error}");
+
+    let mut hasher = DefaultHasher::new();
+    payload.to_string().hash(&mut hasher);
+    let hash = hasher.finish();
+    {
+        let mut sources = SOURCES.lock().unwrap();
+        let quarantined = sources
+            .entry((market.to_string(), endpoint.to_string()))
+            .or_default();
+        if quarantined.written.contains(&hash) || quarantined.written.len() >= MAX_FILES_PER_SOURCE
+        {
+            quarantined.dropped += 1;
+            return;
+        }
+        quarantined.written.insert(hash);
+        if quarantined.written.len() == MAX_FILES_PER_SOURCE {
+            log::warn!(
+                "Quarantined {MAX_FILES_PER_SOURCE} payloads from {market} {endpoint}, \
+                only counting further ones"
+            );
+        }
+    }
+
+    let received_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let name: String = format!("{market}{endpoint}")
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let file = dir().join(format!(
+        "{}-{}-{name}.json",
+        received_at.as_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let record = json!({
+        "market": market,
+        "endpoint": endpoint,
+        "received_at": received_at.as_secs(),
+        "path": error.path,
+        "error": error.message,
+        "payload": payload,
+    });
+
+    let written = fs::create_dir_all(dir()).and_then(|_| fs::write(&file, record.to_string()));
+    if let Err(e) = written {
+        log::error!("Failed to quarantine payload to {file:?}: {e}");
+    }
+}
+
+/// Directory quarantined payloads are written to.
+pub fn dir() -> PathBuf {
+    env::var("QUARANTINE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| DEFAULT_DIR.into())
+}
@@ -0,0 +1,232 @@
+//! Async token-bucket rate limiting shared by the market clients.
+//!
+//! Each client splits its endpoints into groups with their own budget. A request waits for a
+//! token from its group, and the response is fed back so the bucket can honour `Retry-After`
+//! and rate-limit headers and slow down after `429 Too Many Requests`.
+use http::{HeaderMap, StatusCode};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::{sleep, Instant};
+
+/// Fraction of the current rate kept after a `429`
+const BACKOFF_FACTOR: f64 = 0.5;
+/// The rate never drops below this fraction of the configured one
+const MIN_RATE_FRACTION: f64 = 0.1;
+/// Fraction of the configured rate regained after each successful response
+const RECOVERY_STEP: f64 = 0.05;
+/// Reset values above this are unix timestamps rather than delays
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+const RETRY_AFTER: &str = "retry-after";
+const REMAINING_HEADERS: [&str; 2] = ["x-ratelimit-remaining", "ratelimit-remaining"];
+const RESET_HEADERS: [&str; 2] = ["x-ratelimit-reset", "ratelimit-reset"];
+
+/// Budget for one group of endpoints.
+#[derive(Clone, Copy, Debug)]
+pub struct Limit {
+    /// Sustained requests per second
+    pub per_second: f64,
+    /// Requests that may be sent at once after a quiet period
+    pub burst: u32,
+}
+
+impl Limit {
+    pub const fn new(per_second: f64, burst: u32) -> Self {
+        Self { per_second, burst }
+    }
+
+    /// A limit that allows `requests` per second, all of which may be sent concurrently.
+    pub const fn per_second(requests: u32) -> Self {
+        Self::new(requests as f64, requests)
+    }
+}
+
+/// Counters describing how much a group has been throttled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LimiterStats {
+    pub requests: u64,
+    /// Number of `429` responses received
+    pub throttled: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+    /// Current requests per second, lower than the configured rate after `429`s
+    pub rate: f64,
+}
+
+impl LimiterStats {
+    pub fn average_wait(&self) -> Duration {
+        if self.requests == 0 {
+            return Duration::ZERO;
+        }
+        self.total_wait.div_f64(self.requests as f64)
+    }
+}
+
+struct Bucket {
+    limit: Limit,
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    blocked_until: Option<Instant>,
+    stats: LimiterStats,
+}
+
+impl Bucket {
+    fn new(limit: Limit) -> Self {
+        Self {
+            limit,
+            rate: limit.per_second,
+            tokens: limit.burst as f64,
+            refilled_at: Instant::now(),
+            blocked_until: None,
+            stats: LimiterStats::default(),
+        }
+    }
+
+    /// Takes a token, or returns how long to wait before trying again.
+    fn try_acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        if let Some(until) = self.blocked_until.filter(|until| *until > now) {
+            return Err(until - now);
+        }
+
+        let elapsed = (now - self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.limit.burst.max(1) as f64);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    fn block_for(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.blocked_until = Some(self.blocked_until.map_or(until, |b| b.max(until)));
+    }
+
+    fn record(&mut self, status: StatusCode, headers: &HeaderMap) {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            self.stats.throttled += 1;
+            let floor = self.limit.per_second * MIN_RATE_FRACTION;
+            self.rate = (self.rate * BACKOFF_FACTOR).max(floor);
+            self.tokens = 0.0;
+            let delay =
+                retry_after(headers).unwrap_or_else(|| Duration::from_secs_f64(1.0 / self.rate));
+            self.block_for(delay);
+            return;
+        }
+
+        if status.is_success() {
+            let step = self.limit.per_second * RECOVERY_STEP;
+            self.rate = (self.rate + step).min(self.limit.per_second);
+        }
+
+        if let Some(delay) = retry_after(headers) {
+            self.block_for(delay);
+        }
+
+        if header_value(headers, &REMAINING_HEADERS) == Some(0) {
+            if let Some(delay) = header_value(headers, &RESET_HEADERS).map(reset_delay) {
+                self.block_for(delay);
+            }
+        }
+    }
+}
+
+fn header_value(headers: &HeaderMap, names: &[&str]) -> Option<u64> {
+    names
+        .iter()
+        .filter_map(|name| headers.get(*name))
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|value| value.trim().parse::<f64>().ok())
+        .map(|value| value.max(0.0).ceil() as u64)
+}
+
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    header_value(headers, &[RETRY_AFTER]).map(Duration::from_secs)
+}
+
+/// Converts a reset header, which is either a delay in seconds or a unix timestamp, to a delay.
+fn reset_delay(reset: u64) -> Duration {
+    if reset < EPOCH_THRESHOLD {
+        return Duration::from_secs(reset);
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Duration::from_secs(reset.saturating_sub(now))
+}
+
+/// Rate limiter with an independent token bucket per endpoint group.
+///
+/// Cloning is cheap and clones share their buckets.
+#[derive(Clone)]
+pub struct RateLimiter<G> {
+    buckets: Arc<HashMap<G, Mutex<Bucket>>>,
+}
+
+impl<G: Copy + Debug + Eq + Hash> RateLimiter<G> {
+    pub fn new<I: IntoIterator<Item = (G, Limit)>>(limits: I) -> Self {
+        Self {
+            buckets: Arc::new(
+                limits
+                    .into_iter()
+                    .map(|(group, limit)| (group, Mutex::new(Bucket::new(limit))))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn bucket(&self, group: G) -> &Mutex<Bucket> {
+        self.buckets
+            .get(&group)
+            .unwrap_or_else(|| panic!("No rate limit configured for {group:?}"))
+    }
+
+    /// Waits until a request to `group` is within budget and returns how long that took.
+    pub async fn acquire(&self, group: G) -> Duration {
+        let start = Instant::now();
+
+        loop {
+            let result = self.bucket(group).lock().unwrap().try_acquire();
+            match result {
+                Ok(()) => break,
+                Err(wait) => sleep(wait).await,
+            }
+        }
+
+        let waited = start.elapsed();
+        let stats = &mut self.bucket(group).lock().unwrap().stats;
+        stats.requests += 1;
+        stats.total_wait += waited;
+        stats.max_wait = stats.max_wait.max(waited);
+        waited
+    }
+
+    /// Feeds a response back into the bucket of `group`.
+    pub fn record(&self, group: G, status: StatusCode, headers: &HeaderMap) {
+        self.bucket(group).lock().unwrap().record(status, headers);
+    }
+
+    pub fn stats(&self) -> HashMap<G, LimiterStats> {
+        self.buckets
+            .iter()
+            .map(|(group, bucket)| {
+                let bucket = bucket.lock().unwrap();
+                let stats = LimiterStats {
+                    rate: bucket.rate,
+                    ..bucket.stats
+                };
+                (*group, stats)
+            })
+            .collect()
+    }
+}
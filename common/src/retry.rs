@@ -0,0 +1,98 @@
+//! Bounded retries with exponential backoff for the market clients.
+//!
+//! Failures are classified by whether retrying can help at all and whether the server may
+//! already have acted on the request. Requests that are not idempotent, such as purchases, are
+//! only retried when the server certainly did not process them.
+use http::StatusCode;
+use std::fmt;
+use std::time::Duration;
+
+/// Why a request failed.
+#[derive(Clone, Copy, Debug)]
+pub enum Failure {
+    /// The server answered with a non-success status
+    Status(StatusCode),
+    /// No response was received. `sent` is false when the connection could not be established,
+    /// so the server never saw the request.
+    Transport { sent: bool },
+}
+
+impl Failure {
+    /// Whether sending the request again could succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Status(status) => {
+                *status == StatusCode::TOO_MANY_REQUESTS
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || status.is_server_error()
+            }
+            Self::Transport { .. } => true,
+        }
+    }
+
+    /// Whether the server may have processed the request despite the failure.
+    pub fn is_ambiguous(&self) -> bool {
+        match self {
+            Self::Status(status) => {
+                status.is_server_error() && *status != StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::Transport { sent } => *sent,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Status(status) => write!(f, "status {status}"),
+            Self::Transport { sent: true } => write!(f, "no response"),
+            Self::Transport { sent: false } => write!(f, "connection failed"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that gives up after the first failure.
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Whether to try again after `attempt` (starting at 1) failed with `failure`. A request is
+    /// `idempotent` when sending it twice has the same effect as sending it once; requests that
+    /// are not are only retried when the server certainly did not process them.
+    pub fn should_retry(&self, attempt: u32, failure: &Failure, idempotent: bool) -> bool {
+        attempt < self.max_attempts
+            && failure.is_retryable()
+            && (idempotent || !failure.is_ambiguous())
+    }
+
+    /// Delay before the attempt following `attempt`: exponential with full jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        exponential.mul_f64(rand::random::<f64>())
+    }
+}
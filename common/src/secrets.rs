@@ -0,0 +1,200 @@
+//! Credentials loaded once at startup instead of read from the environment on every request.
+//!
+//! Secrets come from the first source that defines them:
+//! 1. the file named by `SECRETS_FILE`, which must only be accessible by its owner
+//! 2. the file named by `SECRETS_ENCRYPTED_FILE`, decrypted with the hex key in `SECRETS_KEY`
+//! 3. the environment
+//!
+//! Both files contain `NAME=value` lines. Blank lines and lines starting with `#` are ignored.
+//! The encrypted file holds the hex encoded nonce followed by the ChaCha20-Poly1305 ciphertext,
+//! as produced by [`encrypt`].
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::{env, fmt, fs, io};
+use thiserror::Error;
+
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+
+static SECRETS: OnceLock<Secrets> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Secret {0} is not set in the secrets files or the environment")]
+    Missing(String),
+
+    #[error("Failed to read secrets file {0}: {1}")]
+    Io(PathBuf, io::Error),
+
+    #[error("Secrets file {0} is accessible by other users (mode {1:o}), run `chmod 600` on it")]
+    InsecurePermissions(PathBuf, u32),
+
+    #[error("Malformed line {1} in secrets file {0}, expected NAME=value")]
+    Malformed(PathBuf, usize),
+
+    #[error("Invalid secrets key: {0}")]
+    Key(String),
+
+    #[error("Failed to decrypt secrets file {0}, the key is wrong or the file is corrupted")]
+    Decrypt(PathBuf),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A credential that never shows up in `Debug` or `Display` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(Arc<str>);
+
+impl Secret {
+    pub fn new(value: &str) -> Self {
+        Self(value.into())
+    }
+
+    /// The actual value, to be sent to the market and nowhere else.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+/// Secrets read from the configured files, falling back to the environment.
+#[derive(Debug, Default)]
+pub struct Secrets {
+    values: HashMap<String, Secret>,
+}
+
+impl Secrets {
+    /// Loads the secrets files configured through the environment.
+    pub fn load() -> Result<Self> {
+        let mut secrets = Self::default();
+        if let Some(path) = env::var_os("SECRETS_ENCRYPTED_FILE") {
+            let key = env::var("SECRETS_KEY")
+                .map_err(|_| Error::Key("SECRETS_KEY is not set".to_string()))?;
+            secrets
+                .values
+                .extend(Self::from_encrypted_file(path, &key)?.values);
+        }
+        if let Some(path) = env::var_os("SECRETS_FILE") {
+            secrets.values.extend(Self::from_file(path)?.values);
+        }
+        Ok(secrets)
+    }
+
+    /// Reads a plain text secrets file, refusing it if other users could read it.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        check_permissions(path)?;
+        let text = fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        parse(path, &text)
+    }
+
+    /// Reads a secrets file encrypted by [`encrypt`] with the hex encoded `key`.
+    pub fn from_encrypted_file(path: impl AsRef<Path>, key: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let cipher = cipher(key)?;
+        let encoded = fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        let data = hex::decode(encoded.trim()).map_err(|_| Error::Decrypt(path.to_path_buf()))?;
+        if data.len() < NONCE_LENGTH {
+            return Err(Error::Decrypt(path.to_path_buf()));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Decrypt(path.to_path_buf()))?;
+        let text = String::from_utf8(plaintext).map_err(|_| Error::Decrypt(path.to_path_buf()))?;
+        parse(path, &text)
+    }
+
+    /// Looks up `name`, failing with an error naming it if no source defines it.
+    pub fn get(&self, name: &str) -> Result<Secret> {
+        if let Some(secret) = self.values.get(name) {
+            return Ok(secret.clone());
+        }
+        match env::var(name) {
+            Ok(value) if !value.is_empty() => Ok(Secret::new(&value)),
+            _ => Err(Error::Missing(name.to_string())),
+        }
+    }
+}
+
+/// Loads the secrets files. Called at startup so a broken file stops the service right away.
+///
+/// Later calls keep the secrets loaded first.
+pub fn init() -> Result<()> {
+    let secrets = Secrets::load()?;
+    SECRETS.set(secrets).ok();
+    Ok(())
+}
+
+/// Looks up `name` in the secrets loaded by [`init`], loading them first if necessary.
+pub fn get(name: &str) -> Result<Secret> {
+    if SECRETS.get().is_none() {
+        init()?;
+    }
+    SECRETS.get().expect("secrets are initialized").get(name)
+}
+
+/// Encrypts the contents of a secrets file with the hex encoded `key`.
+pub fn encrypt(plaintext: &str, key: &str) -> Result<String> {
+    let nonce: [u8; NONCE_LENGTH] = rand::random();
+    let ciphertext = cipher(key)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| Error::Key("encryption failed".to_string()))?;
+    Ok(hex::encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+fn cipher(key: &str) -> Result<ChaCha20Poly1305> {
+    let key = hex::decode(key.trim()).map_err(|e| Error::Key(e.to_string()))?;
+    if key.len() != KEY_LENGTH {
+        return Err(Error::Key(format!("expected {KEY_LENGTH} bytes")));
+    }
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn parse(path: &Path, text: &str) -> Result<Secrets> {
+    let mut values = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| Error::Malformed(path.to_path_buf(), i + 1))?;
+        values.insert(name.trim().to_string(), Secret::new(value.trim()));
+    }
+    Ok(Secrets { values })
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)
+        .map_err(|e| Error::Io(path.to_path_buf(), e))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        return Err(Error::InsecurePermissions(path.to_path_buf(), mode & 0o777));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use common::drift::ResponseSample;
+use serde_json::json;
+use std::collections::HashMap;
+
+type Prices = HashMap<String, i64>;
+
+#[test]
+fn samples_report_objects_that_no_longer_deserialize() {
+    let response = json!({"list": [{"price": 1}, {"price": "1.00"}, {"price": 2}]});
+    let sample = ResponseSample::new::<Prices>("/market/search", &response, "/list");
+
+    assert_eq!(sample.name, "/market/search /list");
+    assert_eq!(sample.objects.len(), 3);
+    assert_eq!(sample.errors.len(), 1);
+}
+
+#[test]
+fn samples_take_single_objects_and_missing_pointers() {
+    let response = json!({"price": 1});
+    let sample = ResponseSample::new::<Prices>("/balance", &response, "");
+    assert_eq!(sample.name, "/balance");
+    assert_eq!(sample.objects.len(), 1);
+    assert!(sample.errors.is_empty());
+
+    let sample = ResponseSample::new::<Prices>("/balance", &response, "/list");
+    assert!(sample.objects.is_empty());
+    assert_eq!(sample.errors.len(), 1);
+}
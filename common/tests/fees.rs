@@ -0,0 +1,47 @@
+use common::{Bracket, FeeSchedule, Money};
+
+fn cents(cents: i64) -> Money {
+    Money::from_cents(cents)
+}
+
+#[test]
+fn fees_have_a_minimum() {
+    let fees = FeeSchedule::flat(0.1, cents(1));
+    assert_eq!(fees.fee(cents(1_000)), cents(100));
+    assert_eq!(fees.fee(Money::from_mills(1_001)), cents(11));
+    assert_eq!(fees.fee(cents(3)), cents(1));
+    assert_eq!(fees.proceeds(cents(3)), cents(2));
+}
+
+#[test]
+fn brackets_apply_within_their_price_range() {
+    let fees = FeeSchedule::flat(0.1, cents(1))
+        .with_bracket(Bracket {
+            min_price: Some(cents(500)),
+            max_price: Some(cents(2_000)),
+            rate: 0.02,
+        })
+        .with_bracket(Bracket {
+            min_price: Some(cents(1_000)),
+            max_price: None,
+            rate: 0.05,
+        });
+
+    assert_eq!(fees.rate(cents(499)), 0.1);
+    assert_eq!(fees.rate(cents(500)), 0.02);
+    // The bracket added last takes precedence where they overlap
+    assert_eq!(fees.rate(cents(1_000)), 0.05);
+    assert_eq!(fees.rate(cents(100_000)), 0.05);
+    assert_eq!(fees.fee(cents(600)), cents(12));
+}
+
+#[test]
+fn max_purchase_price_leaves_the_margin() {
+    let fees = FeeSchedule::flat(0.1, cents(1));
+    let sale_price = cents(1_000);
+    let max_price = fees.max_purchase_price(sale_price, 0.2);
+
+    assert_eq!(max_price, cents(750));
+    assert!(fees.is_profitable(max_price, sale_price, 0.2));
+    assert!(!fees.is_profitable(max_price + cents(1), sale_price, 0.2));
+}
@@ -0,0 +1,102 @@
+use common::money::{serde_cents, serde_dollars, serde_mills, Error};
+use common::{Money, Rounding};
+use serde::Deserialize;
+use serde_json::json;
+
+#[test]
+fn decimal_amounts_parse_exactly() {
+    assert_eq!(Money::parse_dollars("12.34"), Ok(Money::from_cents(1234)));
+    assert_eq!(Money::parse_dollars("0.005"), Ok(Money::from_mills(5)));
+    assert_eq!(Money::parse_dollars("-.5"), Ok(Money::from_cents(-50)));
+    assert_eq!(Money::parse_dollars("3"), Ok(Money::from_cents(300)));
+    assert_eq!(Money::parse_cents("1234"), Ok(Money::from_cents(1234)));
+
+    assert!(matches!(
+        Money::parse_dollars("0.0001"),
+        Err(Error::Invalid(_))
+    ));
+    assert!(matches!(
+        Money::parse_dollars("1.2.3"),
+        Err(Error::Invalid(_))
+    ));
+    assert!(matches!(Money::parse_dollars("."), Err(Error::Invalid(_))));
+    assert!(matches!(Money::parse_cents("12.5"), Err(Error::Invalid(_))));
+}
+
+#[test]
+fn rounding_is_explicit() {
+    let amount = Money::from_mills(1_235);
+    assert_eq!(amount.cents(Rounding::Down), 123);
+    assert_eq!(amount.cents(Rounding::Up), 124);
+    assert_eq!(amount.cents(Rounding::Nearest), 124);
+    assert_eq!((-amount).cents(Rounding::Down), -124);
+    assert_eq!((-amount).cents(Rounding::Up), -123);
+    assert_eq!(Money::from_mills(1_234).cents(Rounding::Nearest), 123);
+
+    // Float noise must not push an exact amount over the edge
+    assert_eq!(
+        Money::from_dollars(0.07, Rounding::Up),
+        Money::from_cents(7)
+    );
+    assert_eq!(
+        Money::from_cents(1_000).scale(1.1, Rounding::Down),
+        Money::from_cents(1_100)
+    );
+}
+
+#[test]
+fn fees_round_up_and_respect_the_minimum() {
+    let price = Money::from_mills(1_001);
+    assert_eq!(price.fee(0.1, Money::ZERO), Money::from_cents(11));
+    assert_eq!(price.after_fee(0.1, Money::ZERO), Money::from_mills(891));
+
+    let cheap = Money::from_cents(5);
+    assert_eq!(cheap.fee(0.1, Money::from_cents(1)), Money::from_cents(1));
+}
+
+#[test]
+fn arithmetic_is_checked() {
+    let total: Money = [1, 2, 3].into_iter().map(Money::from_cents).sum();
+    assert_eq!(total, Money::from_cents(6));
+    assert_eq!(
+        Money::from_mills(i64::MAX).checked_add(total),
+        Err(Error::Overflow)
+    );
+    assert_eq!(
+        Money::from_mills(i64::MIN).checked_sub(total),
+        Err(Error::Overflow)
+    );
+    assert_eq!(total.checked_mul(i64::MAX), Err(Error::Overflow));
+    assert!(Money::from_cents(2) > Money::from_cents(1));
+    assert_eq!(
+        Money::from_cents(2).min(Money::from_cents(1)),
+        Money::from_cents(1)
+    );
+}
+
+#[test]
+fn amounts_display_with_their_currency() {
+    assert_eq!(Money::from_cents(123).to_string(), "1.23 USD");
+    assert_eq!(Money::from_mills(1_235).to_string(), "1.235 USD");
+    assert_eq!(Money::from_cents(-5).to_string(), "-0.05 USD");
+    assert_eq!("1.5".parse(), Ok(Money::from_cents(150)));
+}
+
+#[derive(Deserialize)]
+struct Prices {
+    #[serde(with = "serde_mills")]
+    mills: Money,
+    #[serde(with = "serde_cents")]
+    cents: Money,
+    #[serde(with = "serde_dollars")]
+    dollars: Money,
+}
+
+#[test]
+fn market_amounts_deserialize() {
+    let prices: Prices =
+        serde_json::from_value(json!({"mills": "1235", "cents": 123, "dollars": "1.23"})).unwrap();
+    assert_eq!(prices.mills, Money::from_mills(1_235));
+    assert_eq!(prices.cents, Money::from_cents(123));
+    assert_eq!(prices.dollars, Money::from_cents(123));
+}
@@ -0,0 +1,113 @@
+use common::lenient::{self, List};
+use common::quarantine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Deserialize, Debug)]
+struct Listing {
+    id: String,
+    #[serde(deserialize_with = "lenient::number")]
+    price: f64,
+}
+
+#[derive(Deserialize, Debug)]
+struct Page {
+    #[serde(deserialize_with = "lenient::list")]
+    list: Vec<Listing>,
+}
+
+/// Points the quarantine at a directory shared by the tests in this file
+fn setup() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("quarantine-{}", std::process::id()));
+    std::env::set_var("QUARANTINE_DIR", &dir);
+    dir
+}
+
+/// Quarantined records for `endpoint`
+fn quarantined(endpoint: &str) -> Vec<Value> {
+    let Ok(entries) = fs::read_dir(setup()) else {
+        return Vec::new();
+    };
+    entries
+        .map(|entry| entry.unwrap().path())
+        .map(|path: PathBuf| serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap())
+        .filter(|record: &Value| record["endpoint"] == endpoint)
+        .collect()
+}
+
+#[test]
+fn malformed_list_elements_are_quarantined_and_skipped() {
+    setup();
+    let text = json!({"list": [
+        {"id": "1", "price": 1.5},
+        {"id": "2", "price": "2.5"},
+        {"id": "3", "price": null},
+        "garbage",
+    ]})
+    .to_string();
+
+    let page: Page = quarantine::from_str("test", "/page", &text).unwrap();
+
+    let prices: Vec<f64> = page.list.iter().map(|listing| listing.price).collect();
+    assert_eq!(prices, [1.5, 2.5]);
+    assert_eq!(page.list[1].id, "2");
+    let mut paths: Vec<Value> = quarantined("/page")
+        .into_iter()
+        .map(|record| record["path"].clone())
+        .collect();
+    paths.sort_by_key(|path| path.to_string());
+    assert_eq!(paths, [json!("[2].price"), json!("[3]")]);
+}
+
+#[test]
+fn unparseable_payloads_are_quarantined_with_the_error_path() {
+    setup();
+    let text = json!({"list": {"id": "1"}}).to_string();
+
+    let error = quarantine::from_str::<Page>("test", "/broken", &text).unwrap_err();
+
+    assert_eq!(error.path, "list");
+    let records = quarantined("/broken");
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["market"], "test");
+    assert_eq!(records[0]["payload"], json!({"list": {"id": "1"}}));
+}
+
+#[test]
+fn lists_keep_everything_that_parses() {
+    setup();
+    let List(prices) = serde_json::from_value::<List<u32>>(json!([1, 2, 3])).unwrap();
+    assert_eq!(prices, [1, 2, 3]);
+}
+
+#[test]
+fn repeated_payloads_are_written_once() {
+    setup();
+    let text = json!({"list": {"id": "repeated"}}).to_string();
+
+    for _ in 0..3 {
+        quarantine::from_str::<Page>("test", "/repeated", &text).unwrap_err();
+    }
+
+    assert_eq!(quarantined("/repeated").len(), 1);
+    assert_eq!(quarantine::dropped("test", "/repeated"), 2);
+}
+
+#[test]
+fn payloads_over_the_limit_are_only_counted() {
+    setup();
+    for i in 0..quarantine::MAX_FILES_PER_SOURCE + 5 {
+        let text = json!({"list": {"id": i}}).to_string();
+        quarantine::from_str::<Page>("test", "/flood", &text).unwrap_err();
+    }
+
+    assert_eq!(
+        quarantined("/flood").len(),
+        quarantine::MAX_FILES_PER_SOURCE
+    );
+    assert_eq!(quarantine::dropped("test", "/flood"), 5);
+    // Other endpoints have limits of their own
+    assert_eq!(quarantine::dropped("test", "/broken"), 0);
+}
@@ -0,0 +1,106 @@
+use common::{Limit, RateLimiter};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use std::time::Duration;
+
+/// Timers fire on whole milliseconds, so waits may be up to one longer than computed
+fn assert_waited(waited: Duration, expected: Duration) {
+    assert!(
+        waited >= expected && waited <= expected + Duration::from_millis(2),
+        "waited {waited:?} instead of {expected:?}"
+    );
+}
+
+fn rate(limiter: &RateLimiter<()>) -> f64 {
+    limiter.stats()[&()].rate
+}
+
+#[tokio::test(start_paused = true)]
+async fn bursts_are_sent_at_once_and_then_refilled() {
+    let limiter = RateLimiter::new([((), Limit::new(2.0, 3))]);
+
+    for _ in 0..3 {
+        assert_eq!(limiter.acquire(()).await, Duration::ZERO);
+    }
+    assert_waited(limiter.acquire(()).await, Duration::from_millis(500));
+
+    // A quiet period refills the bucket up to the burst, not beyond it
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    for _ in 0..3 {
+        assert_eq!(limiter.acquire(()).await, Duration::ZERO);
+    }
+    assert_waited(limiter.acquire(()).await, Duration::from_millis(500));
+}
+
+#[tokio::test(start_paused = true)]
+async fn retry_after_delays_the_next_request() {
+    let limiter = RateLimiter::new([((), Limit::per_second(10))]);
+    limiter.acquire(()).await;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("retry-after", HeaderValue::from_static("5"));
+    limiter.record((), StatusCode::OK, &headers);
+
+    assert_waited(limiter.acquire(()).await, Duration::from_secs(5));
+    assert_eq!(limiter.acquire(()).await, Duration::ZERO);
+}
+
+#[tokio::test(start_paused = true)]
+async fn throttling_halves_the_rate_until_responses_succeed() {
+    let limiter = RateLimiter::new([((), Limit::per_second(10))]);
+    let headers = HeaderMap::new();
+
+    limiter.record((), StatusCode::TOO_MANY_REQUESTS, &headers);
+    assert_eq!(rate(&limiter), 5.0);
+    limiter.record((), StatusCode::TOO_MANY_REQUESTS, &headers);
+    assert_eq!(rate(&limiter), 2.5);
+
+    // Without a Retry-After the next request waits for one request at the lowered rate
+    assert_waited(limiter.acquire(()).await, Duration::from_millis(400));
+
+    // Each success regains 5% of the configured rate, up to the configured rate
+    limiter.record((), StatusCode::OK, &headers);
+    assert!((rate(&limiter) - 3.0).abs() < 1e-9);
+    for _ in 0..20 {
+        limiter.record((), StatusCode::OK, &headers);
+    }
+    assert_eq!(rate(&limiter), 10.0);
+
+    // Errors other than a 429 neither lower nor raise the rate
+    limiter.record((), StatusCode::TOO_MANY_REQUESTS, &headers);
+    limiter.record((), StatusCode::INTERNAL_SERVER_ERROR, &headers);
+    assert_eq!(rate(&limiter), 5.0);
+
+    // The rate never drops below a tenth of the configured one
+    for _ in 0..10 {
+        limiter.record((), StatusCode::TOO_MANY_REQUESTS, &headers);
+    }
+    assert_eq!(rate(&limiter), 1.0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn stats_count_requests_throttling_and_waits() {
+    let limiter = RateLimiter::new([
+        ("search", Limit::new(1.0, 1)),
+        ("buy", Limit::per_second(5)),
+    ]);
+
+    for _ in 0..3 {
+        limiter.acquire("search").await;
+    }
+    limiter.record("search", StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new());
+
+    let stats = limiter.stats();
+    let search = stats["search"];
+    assert_eq!(search.requests, 3);
+    assert_eq!(search.throttled, 1);
+    assert_waited(search.total_wait, Duration::from_secs(2));
+    assert_waited(search.max_wait, Duration::from_secs(1));
+    assert_waited(search.average_wait(), Duration::from_millis(666));
+    assert_eq!(search.rate, 0.5);
+
+    let buy = stats["buy"];
+    assert_eq!(buy.requests, 0);
+    assert_eq!(buy.throttled, 0);
+    assert_eq!(buy.average_wait(), Duration::ZERO);
+    assert_eq!(buy.rate, 5.0);
+}
@@ -0,0 +1,72 @@
+use common::{Failure, RetryPolicy};
+use http::StatusCode;
+use std::time::Duration;
+
+#[test]
+fn failures_are_classified_by_status_and_whether_they_were_sent() {
+    let cases = [
+        (Failure::Status(StatusCode::TOO_MANY_REQUESTS), true, false),
+        (Failure::Status(StatusCode::REQUEST_TIMEOUT), true, false),
+        (
+            Failure::Status(StatusCode::INTERNAL_SERVER_ERROR),
+            true,
+            true,
+        ),
+        (Failure::Status(StatusCode::BAD_GATEWAY), true, true),
+        (
+            Failure::Status(StatusCode::SERVICE_UNAVAILABLE),
+            true,
+            false,
+        ),
+        (Failure::Status(StatusCode::BAD_REQUEST), false, false),
+        (Failure::Status(StatusCode::NOT_FOUND), false, false),
+        (Failure::Transport { sent: false }, true, false),
+        (Failure::Transport { sent: true }, true, true),
+    ];
+    for (failure, retryable, ambiguous) in cases {
+        assert_eq!(failure.is_retryable(), retryable, "{failure}");
+        assert_eq!(failure.is_ambiguous(), ambiguous, "{failure}");
+    }
+}
+
+#[test]
+fn ambiguous_failures_are_only_retried_when_idempotent() {
+    let policy = RetryPolicy::default();
+    let ambiguous = Failure::Status(StatusCode::INTERNAL_SERVER_ERROR);
+    let unsent = Failure::Transport { sent: false };
+
+    assert!(policy.should_retry(1, &ambiguous, true));
+    assert!(!policy.should_retry(1, &ambiguous, false));
+    assert!(!policy.should_retry(1, &Failure::Transport { sent: true }, false));
+    assert!(policy.should_retry(1, &unsent, false));
+    assert!(policy.should_retry(1, &Failure::Status(StatusCode::SERVICE_UNAVAILABLE), false));
+
+    assert!(!policy.should_retry(policy.max_attempts, &unsent, true));
+    assert!(!RetryPolicy::never().should_retry(1, &unsent, true));
+    assert!(!policy.should_retry(1, &Failure::Status(StatusCode::BAD_REQUEST), true));
+}
+
+#[test]
+fn backoff_stays_within_the_capped_exponential() {
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+    };
+    for attempt in 1..=40 {
+        let cap = policy
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(policy.max_delay);
+        let delays: Vec<_> = (0..200).map(|_| policy.backoff(attempt)).collect();
+        assert!(
+            delays.iter().all(|delay| *delay <= cap),
+            "attempt {attempt}"
+        );
+        // Full jitter spreads the delays over the whole range
+        assert!(
+            delays.iter().any(|delay| *delay < cap / 2),
+            "attempt {attempt}"
+        );
+    }
+}
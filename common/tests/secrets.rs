@@ -0,0 +1,60 @@
+use common::secrets::{self, Error, Secrets};
+use common::Secret;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+fn write_file(name: &str, contents: &str, mode: u32) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    path
+}
+
+#[test]
+fn secrets_are_redacted() {
+    let secret = Secret::new("hunter2");
+    assert_eq!(secret.expose(), "hunter2");
+    assert!(!format!("{secret:?}").contains("hunter2"));
+    assert!(!format!("{secret}").contains("hunter2"));
+}
+
+#[test]
+fn secrets_file_must_be_private() {
+    let contents = "# BitSkins\nBITSKIN_API_KEY=abc\n\nDMARKET_API_KEY = def\n";
+    let path = write_file("secrets-private", contents, 0o600);
+    let secrets = Secrets::from_file(&path).unwrap();
+    assert_eq!(secrets.get("BITSKIN_API_KEY").unwrap().expose(), "abc");
+    assert_eq!(secrets.get("DMARKET_API_KEY").unwrap().expose(), "def");
+
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    assert!(matches!(
+        Secrets::from_file(&path),
+        Err(Error::InsecurePermissions(_, 0o644))
+    ));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn encrypted_secrets_file_round_trips() {
+    let encrypted = secrets::encrypt("BITSKIN_API_KEY=abc\n", KEY).unwrap();
+    let path = write_file("secrets-encrypted", &encrypted, 0o644);
+
+    let secrets = Secrets::from_encrypted_file(&path, KEY).unwrap();
+    assert_eq!(secrets.get("BITSKIN_API_KEY").unwrap().expose(), "abc");
+
+    let wrong_key = KEY.replace("00", "ff");
+    assert!(matches!(
+        Secrets::from_encrypted_file(&path, &wrong_key),
+        Err(Error::Decrypt(_))
+    ));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_secret_is_named_in_the_error() {
+    let error = Secrets::default().get("SECRETS_TEST_MISSING").unwrap_err();
+    assert!(error.to_string().contains("SECRETS_TEST_MISSING"));
+}
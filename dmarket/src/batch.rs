@@ -0,0 +1,386 @@
+//! Per-entry handling of DMarket's batch endpoints.
+//!
+//! Batch requests succeed as a whole even when some of their entries fail, so every entry of the
+//! response is inspected. Failures are classified, entries that can be fixed are resent with
+//! corrected input unless the caller refuses the correction, and a summary is logged for each
+//! batch.
+use crate::schema::{
+    CreateOffer, CreateOffersResponse, CreateTarget, CreateTargetsResponse, DeleteTarget,
+    DeleteTargetsResponse, EditOffer, EditOffersResponse, MarketError, MarketMoney,
+};
+use crate::Result;
+use common::{Money, Rounding};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+
+const MAX_CHUNK_SIZE: usize = 100;
+/// Rounds of corrections before giving up on an entry
+const MAX_CORRECTIONS: usize = 1;
+
+/// Why a single entry of a batch failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchFailure {
+    /// The price is outside of what the market accepts. Bounds are in dollars, when known.
+    PriceOutOfRange {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    ItemLocked,
+    InsufficientFunds,
+    /// The whole request failed, so the entry was never processed
+    Request(String),
+    Other {
+        code: String,
+        message: String,
+    },
+}
+
+impl BatchFailure {
+    fn label(&self) -> &str {
+        match self {
+            Self::PriceOutOfRange { .. } => "price out of range",
+            Self::ItemLocked => "item locked",
+            Self::InsufficientFunds => "insufficient funds",
+            Self::Request(_) => "request failed",
+            Self::Other { code, .. } => code,
+        }
+    }
+}
+
+impl From<&MarketError> for BatchFailure {
+    fn from(error: &MarketError) -> Self {
+        let text = format!("{} {}", error.code, error.message).to_lowercase();
+
+        if text.contains("lock") {
+            Self::ItemLocked
+        } else if text.contains("insufficient") || text.contains("not enough") {
+            Self::InsufficientFunds
+        } else if text.contains("price") && !text.contains("not found") {
+            price_bounds(&text)
+        } else {
+            Self::Other {
+                code: error.code.clone(),
+                message: error.message.clone(),
+            }
+        }
+    }
+}
+
+/// Reads the accepted price range from messages like "price must be between $0.02 and $100".
+fn price_bounds(text: &str) -> BatchFailure {
+    let numbers: Vec<f64> = text
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '$')
+        .filter_map(|word| word.trim_end_matches('.').parse().ok())
+        .collect();
+
+    let (min, max) = match numbers[..] {
+        [min, max, ..] => (Some(min), Some(max)),
+        [bound] if text.contains("max") || text.contains("high") => (None, Some(bound)),
+        [bound] => (Some(bound), None),
+        [] => (None, None),
+    };
+    BatchFailure::PriceOutOfRange { min, max }
+}
+
+impl fmt::Display for BatchFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PriceOutOfRange { min, max } => write!(
+                f,
+                "price out of range (min: {}, max: {})",
+                min.map_or("?".to_string(), |p| p.to_string()),
+                max.map_or("?".to_string(), |p| p.to_string()),
+            ),
+            Self::Request(error) => write!(f, "request failed: {error}"),
+            Self::Other { code, message } => write!(f, "{code}: {message}"),
+            _ => write!(f, "{}", self.label()),
+        }
+    }
+}
+
+/// Result of one entry, together with the request the market echoed back for it.
+pub struct EntryResult<R> {
+    pub request: R,
+    pub outcome: std::result::Result<(), BatchFailure>,
+}
+
+impl<R> EntryResult<R> {
+    fn new(request: R, successful: bool, error: Option<&MarketError>) -> Self {
+        let outcome = match (successful, error) {
+            (true, _) => Ok(()),
+            (false, Some(error)) => Err(error.into()),
+            (false, None) => Err(BatchFailure::Other {
+                code: "Unknown".to_string(),
+                message: "Unsuccessful without an error".to_string(),
+            }),
+        };
+        Self { request, outcome }
+    }
+}
+
+pub trait BatchResponse {
+    type Request;
+
+    fn into_results(self) -> Vec<EntryResult<Self::Request>>;
+}
+
+impl BatchResponse for CreateOffersResponse {
+    type Request = CreateOffer;
+
+    fn into_results(self) -> Vec<EntryResult<CreateOffer>> {
+        self.result
+            .into_iter()
+            .map(|r| EntryResult::new(r.create_offer, r.successful, r.error.as_ref()))
+            .collect()
+    }
+}
+
+impl BatchResponse for EditOffersResponse {
+    type Request = EditOffer;
+
+    fn into_results(self) -> Vec<EntryResult<EditOffer>> {
+        self.result
+            .into_iter()
+            .map(|r| EntryResult::new(r.edit_offer, r.successful, r.error.as_ref()))
+            .collect()
+    }
+}
+
+impl BatchResponse for CreateTargetsResponse {
+    type Request = CreateTarget;
+
+    fn into_results(self) -> Vec<EntryResult<CreateTarget>> {
+        self.result
+            .into_iter()
+            .map(|r| {
+                let target = CreateTarget {
+                    amount: r.create_target.amount.parse().unwrap_or(1),
+                    price: r.create_target.price,
+                    title: r.create_target.title,
+                    attrs: r.create_target.attrs,
+                };
+                EntryResult::new(target, r.successful, r.error.as_ref())
+            })
+            .collect()
+    }
+}
+
+impl BatchResponse for DeleteTargetsResponse {
+    type Request = DeleteTarget;
+
+    fn into_results(self) -> Vec<EntryResult<DeleteTarget>> {
+        self.result
+            .into_iter()
+            .map(|r| EntryResult::new(r.delete_target, r.successful, r.error.as_ref()))
+            .collect()
+    }
+}
+
+/// Entries that can be resent with different input after a failure.
+pub trait Correctable: Sized {
+    /// Returns the corrected entry, or `None` if the failure cannot be fixed by changing it.
+    fn correct(self, failure: &BatchFailure) -> Option<Self>;
+
+    /// The price of the entry, if it has one.
+    fn price(&self) -> Option<Money>;
+}
+
+/// Moves a price into the accepted range, rounded to cents.
+fn correct_price(price: &MarketMoney, failure: &BatchFailure) -> Option<MarketMoney> {
+    let BatchFailure::PriceOutOfRange { min, max } = failure else {
+        return None;
+    };
+    let mut amount = price.amount;
+    if let Some(min) = min.map(|min| Money::from_dollars(min, Rounding::Up)) {
+        if amount < min {
+            amount = min.round_to_cents(Rounding::Up);
+        }
+    }
+    if let Some(max) = max.map(|max| Money::from_dollars(max, Rounding::Down)) {
+        if amount > max {
+            amount = max.round_to_cents(Rounding::Down);
+        }
+    }
+    (amount != price.amount).then(|| MarketMoney::new(amount))
+}
+
+impl Correctable for CreateOffer {
+    fn correct(self, failure: &BatchFailure) -> Option<Self> {
+        let price = correct_price(&self.price, failure)?;
+        Some(Self { price, ..self })
+    }
+
+    fn price(&self) -> Option<Money> {
+        Some(self.price.amount)
+    }
+}
+
+impl Correctable for EditOffer {
+    fn correct(self, failure: &BatchFailure) -> Option<Self> {
+        let price = correct_price(&self.price, failure)?;
+        Some(Self { price, ..self })
+    }
+
+    fn price(&self) -> Option<Money> {
+        Some(self.price.amount)
+    }
+}
+
+impl Correctable for CreateTarget {
+    fn correct(self, failure: &BatchFailure) -> Option<Self> {
+        let price = correct_price(&self.price, failure)?;
+        Some(Self { price, ..self })
+    }
+
+    fn price(&self) -> Option<Money> {
+        Some(self.price.amount)
+    }
+}
+
+impl Correctable for DeleteTarget {
+    fn correct(self, _: &BatchFailure) -> Option<Self> {
+        None
+    }
+
+    fn price(&self) -> Option<Money> {
+        None
+    }
+}
+
+/// Outcome of a batch operation across all of its chunks.
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+    /// Entries that succeeded after their input was corrected, included in `succeeded`
+    pub corrected: usize,
+    pub failed: Vec<BatchFailure>,
+}
+
+impl BatchSummary {
+    pub fn merge(&mut self, other: BatchSummary) {
+        self.succeeded += other.succeeded;
+        self.corrected += other.corrected;
+        self.failed.extend(other.failed);
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} succeeded ({} after correction), {} failed",
+            self.succeeded,
+            self.corrected,
+            self.failed.len()
+        )?;
+
+        let mut counts = BTreeMap::new();
+        for failure in &self.failed {
+            *counts.entry(failure.label()).or_insert(0) += 1;
+        }
+        for (label, count) in counts {
+            write!(f, ", {count} {label}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats an optional price for the log.
+fn display_price(price: Option<Money>) -> String {
+    price.map_or("?".to_string(), |price| price.to_string())
+}
+
+/// Sends `requests` in chunks through `send`, resending entries that can be corrected when
+/// `accept` allows their corrected input, e.g. only prices that don't sell at a loss.
+///
+/// A failed request stops the operation, since the remaining chunks would most likely fail the
+/// same way. Its entries and the ones not yet sent are counted as failed.
+pub(crate) async fn process<R, T, F, Fut, A>(
+    operation: &str,
+    requests: Vec<R>,
+    send: F,
+    accept: A,
+) -> BatchSummary
+where
+    R: Correctable + fmt::Debug,
+    T: BatchResponse<Request = R>,
+    F: Fn(Vec<R>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+    A: Fn(&R) -> bool,
+{
+    let mut summary = BatchSummary::default();
+    let mut requests = requests;
+
+    for round in 0..=MAX_CORRECTIONS {
+        let mut corrections = Vec::new();
+
+        while !requests.is_empty() {
+            let rest = requests.split_off(MAX_CHUNK_SIZE.min(requests.len()));
+            let chunk = std::mem::replace(&mut requests, rest);
+            let chunk_size = chunk.len();
+
+            let response = match send(chunk).await {
+                Ok(response) => response,
+                Err(e) => {
+                    log::error!("Error in {operation}: {e}");
+                    let unsent = chunk_size + requests.len();
+                    let failure = BatchFailure::Request(e.to_string());
+                    summary.failed.extend(vec![failure; unsent]);
+                    log::info!("{operation}: {summary}");
+                    return summary;
+                }
+            };
+
+            for result in response.into_results() {
+                match result.outcome {
+                    Ok(()) => {
+                        summary.succeeded += 1;
+                        summary.corrected += usize::from(round > 0);
+                    }
+                    Err(failure) => {
+                        let request = format!("{:?}", result.request);
+                        let before = display_price(result.request.price());
+                        match (round < MAX_CORRECTIONS)
+                            .then(|| result.request.correct(&failure))
+                            .flatten()
+                        {
+                            Some(corrected) if accept(&corrected) => {
+                                log::warn!(
+                                    "{operation}: {failure}, correcting {request} from {before} \
+                                     to {}",
+                                    display_price(corrected.price())
+                                );
+                                corrections.push(corrected);
+                            }
+                            Some(corrected) => {
+                                log::warn!(
+                                    "{operation}: {failure}, refusing to correct {request} from \
+                                     {before} to {}",
+                                    display_price(corrected.price())
+                                );
+                                summary.failed.push(failure);
+                            }
+                            None => {
+                                log::warn!("{operation} failed for {request}: {failure}");
+                                summary.failed.push(failure);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if corrections.is_empty() {
+            break;
+        }
+        log::info!(
+            "{operation}: resending {} corrected entries",
+            corrections.len()
+        );
+        requests = corrections;
+    }
+
+    log::info!("{operation}: {summary}");
+    summary
+}
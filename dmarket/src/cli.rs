@@ -0,0 +1,160 @@
+//! Command-line interface for running the bot and for one-off manual operations.
+use anyhow::{ensure, Context, Result};
+use clap::{Parser, Subcommand};
+use common::cli::GlobalArgs;
+use common::Money;
+use dmarket::schema::{DeleteOffer, GameTitle};
+use dmarket::Trader;
+use log::info;
+use serde::Serialize;
+use std::fmt::Debug;
+
+#[derive(Parser)]
+#[command(about = "DMarket trading bot")]
+pub struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the bot (the default)
+    Run,
+    /// Check the configuration, the database and the API, then exit
+    Preflight,
+    /// Sync titles, sales, price statistics, fees and the balance into the database
+    Sync,
+    /// Show the price statistics of a title
+    Stats { title: String },
+    /// Buy the cheapest offer on a title that is not ours, skipping the profitability checks.
+    /// Takes a title rather than an offer ID, as DMarket only finds offers by title and offers
+    /// of the same title are interchangeable.
+    Buy {
+        title: String,
+        /// Most to pay in cents, the balance by default
+        #[arg(long)]
+        max_price: Option<i64>,
+    },
+    /// List the items in the inventory
+    ListInventory,
+    /// Update the prices of our offers
+    Reprice,
+    /// Take one of our offers off the market
+    Delist { offer_id: String },
+    /// Manage our targets
+    Targets {
+        #[command(subcommand)]
+        command: TargetsCommand,
+    },
+    /// Show the balance of the account
+    Balance,
+}
+
+#[derive(Subcommand)]
+pub enum TargetsCommand {
+    /// Show the targets that would be created and deleted to match the desired ones
+    Diff,
+}
+
+impl Cli {
+    /// Runs a one-off `command` and prints its result.
+    pub async fn execute(&self, command: &Command) -> Result<()> {
+        let trader = self.trader().await?;
+        match command {
+            Command::Run | Command::Preflight => unreachable!("handled by main"),
+            Command::Sync => {
+                trader.sync().await?;
+                self.print(&"Synced")
+            }
+            Command::Stats { title } => {
+                let game_title = self.game_title(&trader, title).await?;
+                self.print(&trader.db.get_price_statistics(&game_title).await?)
+            }
+            Command::Buy { title, max_price } => {
+                let game_title = self.game_title(&trader, title).await?;
+                let price = match max_price {
+                    Some(cents) => Money::from_cents(*cents),
+                    None => Money::parse_cents(&trader.client.get_balance().await?.usd)?,
+                };
+                let bought = if self.global.dry_run {
+                    info!("Dry run, not buying");
+                    trader.plan_purchase(game_title, price).await?
+                } else {
+                    trader.buy_game_title(game_title, price).await?
+                };
+                let bought = bought.with_context(|| format!("Nothing bought on {title}"))?;
+                self.print(&bought)
+            }
+            Command::ListInventory => {
+                let offers = trader.plan_listings().await?;
+                self.print(&offers)?;
+                if self.global.dry_run {
+                    info!("Dry run, not listing");
+                } else {
+                    trader.create_offers(offers).await?;
+                }
+                Ok(())
+            }
+            Command::Reprice => {
+                let offers = trader.plan_offer_updates().await?;
+                self.print(&offers)?;
+                if self.global.dry_run {
+                    info!("Dry run, not repricing");
+                } else {
+                    trader.edit_offers(offers).await?;
+                }
+                Ok(())
+            }
+            Command::Delist { offer_id } => {
+                let offer = trader
+                    .client
+                    .get_offers()
+                    .await?
+                    .iter()
+                    .find(|offer| &offer.offer.offer_id == offer_id)
+                    .map(DeleteOffer::from)
+                    .with_context(|| format!("Offer {offer_id} is not one of ours"))?;
+                if self.global.dry_run {
+                    info!("Dry run, not delisting");
+                } else {
+                    let response = trader
+                        .client
+                        .delete_offers(false, std::slice::from_ref(&offer))
+                        .await?;
+                    ensure!(
+                        response.result.iter().all(|r| r.fail.is_empty()),
+                        "Offer {offer_id} could not be delisted"
+                    );
+                }
+                self.print(&offer)
+            }
+            Command::Targets {
+                command: TargetsCommand::Diff,
+            } => self.print(&trader.plan_targets().await?),
+            Command::Balance => self.print(&trader.client.get_balance().await?),
+        }
+    }
+
+    /// The trader of the selected account.
+    async fn trader(&self) -> Result<Trader> {
+        let traders = Trader::for_all_accounts().await?;
+        Ok(self
+            .global
+            .select("DMarket", traders, |trader| &trader.account.name)?)
+    }
+
+    async fn game_title(&self, trader: &Trader, title: &str) -> Result<GameTitle> {
+        trader
+            .db
+            .get_game_title(title.to_string())
+            .await?
+            .with_context(|| format!("Unknown title {title}, run `sync` first"))
+    }
+
+    fn print<T: Serialize + Debug>(&self, value: &T) -> Result<()> {
+        Ok(self.global.print(value)?)
+    }
+}
@@ -1,26 +1,30 @@
 use crate::error::Error;
-use crate::rate_limiter::{RateLimiter, RateLimiterType, RateLimiters};
 use crate::schema::{
     Balance, BestPrices, BestPricesResponse, BuyOffer, BuyOffersResponse, CreateOffer,
     CreateOffersResponse, CreateTarget, CreateTargetsResponse, DeleteOffer, DeleteOffersResponse,
     DeleteTarget, DeleteTargetsResponse, EditOffer, EditOffersResponse, GameTitle,
     GetTargetsResponse, Item, ItemResponse, ListDefaultFee, ListFeeResponse, ListPersonalFee,
-    Offer, OfferMoney, PaginatedResponse, Sale, SaleResponse, Target,
+    Offer, OfferMoney, PaginatedResponse, Sale, SaleResponse, Target, TradingAccount,
 };
+use crate::signer::Signer;
 use crate::Result;
 use async_stream::try_stream;
-use futures::StreamExt;
-use futures::{stream, stream::TryStreamExt, Stream};
-use reqwest::header::HeaderValue;
-use reqwest::{Method, StatusCode};
+use common::drift::ResponseSample;
+use common::quarantine;
+use common::{Failure, Limit, LimiterStats, Money, RateLimiter, RetryPolicy};
+use futures::{stream::TryStreamExt, Stream};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Method;
 use serde::de::DeserializeOwned;
 use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
-use std::env;
-use url::Url;
+use std::sync::Arc;
+use tokio::time::sleep;
+use url::{Position, Url};
 use uuid::Uuid;
 
-const BASE_URL: &str = "https://api.dmarket.com";
+pub(crate) const BASE_URL: &str = "https://api.dmarket.com";
 
 pub const CSGO_GAME_ID: &str = "a8db";
 pub const TF2_GAME_ID: &str = "tf2";
@@ -35,20 +39,87 @@ const MARKET_LIMIT: usize = 100;
 const SALES_LIMIT: usize = 500;
 const BEST_PRICES_LIMIT: usize = 10000;
 
+/// Endpoints sharing a rate limit budget on DMarket's side
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestGroup {
+    Fee,
+    LastSales,
+    MarketItems,
+    Other,
+}
+
+impl RequestGroup {
+    const LIMITS: [(Self, Limit); 4] = [
+        (Self::Fee, Limit::per_second(110)),
+        (Self::LastSales, Limit::per_second(6)),
+        (Self::MarketItems, Limit::per_second(5)),
+        (Self::Other, Limit::per_second(20)),
+    ];
+
+    fn from_path(path: &str) -> Self {
+        match path.split('?').next().unwrap_or_default() {
+            "/exchange/v1/customized-fees" => Self::Fee,
+            "/trade-aggregator/v1/last-sales" => Self::LastSales,
+            "/exchange/v1/market/items" => Self::MarketItems,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Whether a request is idempotent in the sense of [`RetryPolicy::should_retry`]. Creating
+/// targets or offers and buying are not.
+pub fn is_idempotent(method: &Method, path: &str) -> bool {
+    match *method {
+        Method::GET | Method::DELETE => true,
+        _ => matches!(
+            path,
+            "/marketplace-api/v1/user-targets/delete" | "/marketplace-api/v1/user-offers/edit"
+        ),
+    }
+}
+
 #[derive(Clone)]
 pub struct Client {
     client: reqwest::Client,
-    request_limiters: RateLimiters,
+    base_url: Url,
+    signer: Arc<Signer>,
+    limiter: RateLimiter<RequestGroup>,
+    retry_policy: RetryPolicy,
 }
 
 impl Client {
+    /// Creates a client for the live API, signing requests with the key pair from the environment.
     pub fn new() -> Result<Self> {
+        Self::with_base_url(BASE_URL, Signer::from_env()?)
+    }
+
+    /// Creates a client signing its requests with the keys of `account`.
+    pub fn for_account(account: &TradingAccount) -> Result<Self> {
+        Self::with_base_url(BASE_URL, Signer::from_credentials(&account.credentials)?)
+    }
+
+    /// Creates a client that sends every request to `base_url` instead of the live API.
+    pub fn with_base_url(base_url: &str, signer: Signer) -> Result<Self> {
         Ok(Self {
             client: reqwest::Client::new(),
-            request_limiters: RateLimiter::request_limiters(),
+            base_url: Url::parse(base_url)?,
+            signer: Arc::new(signer),
+            limiter: RateLimiter::new(RequestGroup::LIMITS),
+            retry_policy: RetryPolicy::default(),
         })
     }
 
+    /// Replaces the policy deciding how often and how quickly failed requests are retried.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Wait-time statistics for each endpoint group.
+    pub fn rate_limit_stats(&self) -> HashMap<RequestGroup, LimiterStats> {
+        self.limiter.stats()
+    }
+
     async fn get<T: DeserializeOwned>(&self, path: &str, query: Value) -> Result<T> {
         let query = serde_qs::to_string(&query)?;
         self.request(Method::GET, &format!("{path}?{query}"), None)
@@ -73,48 +144,65 @@ impl Client {
         path: &str,
         body: Option<Value>,
     ) -> Result<T> {
-        let limiter_type = self.get_limiter_type(path);
-        self.wait_for_rate_limit(limiter_type).await;
-
-        let url = Url::parse(&format!("{BASE_URL}{path}"))?;
-
-        let mut request = self.client.request(method, url).header(
-            "Authorization",
-            HeaderValue::from_str(&env::var("DMARKET_AUTHORIZATION")?)?,
-        );
-
-        if let Some(body) = body {
-            request = request.json(&body);
-        }
+        let group = RequestGroup::from_path(path);
+        let idempotent = is_idempotent(&method, path);
+        let url = self.base_url.join(path)?;
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let mut attempt = 0;
 
         loop {
-            let response = request.try_clone().unwrap().send().await?;
-            if response.status().is_success() {
-                return Ok(response.json().await?);
-            } else if response.status() == StatusCode::TOO_MANY_REQUESTS {
-                log::warn!("Rate limit hit for path {path}");
-                self.wait_for_rate_limit(limiter_type).await;
-            } else {
-                return Err(Error::Response(response.status(), response.text().await?));
+            attempt += 1;
+            self.limiter.acquire(group).await;
+
+            // Signed per attempt, as the signature embeds the time it was made
+            let headers = self
+                .signer
+                .headers(&method, &url[Position::BeforePath..], &body)?;
+            let mut request = self
+                .client
+                .request(method.clone(), url.clone())
+                .headers(headers);
+
+            if !body.is_empty() {
+                request = request
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body.clone());
             }
-        }
-    }
 
-    fn get_limiter_type(&self, path: &str) -> RateLimiterType {
-        if path.contains("fees") {
-            RateLimiterType::Fee
-        } else if path.contains("last-sales") {
-            RateLimiterType::LastSales
-        } else if path.contains("market/items") {
-            RateLimiterType::MarketItems
-        } else {
-            RateLimiterType::Other
-        }
-    }
+            let (failure, error) = match request.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    self.limiter.record(group, status, response.headers());
+
+                    if status.is_success() {
+                        let text = response.text().await?;
+                        return Ok(quarantine::from_str("dmarket", url.path(), &text)?);
+                    }
+                    (
+                        Failure::Status(status),
+                        Error::Response(status, response.text().await?),
+                    )
+                }
+                Err(e) => (
+                    Failure::Transport {
+                        sent: !e.is_connect(),
+                    },
+                    e.into(),
+                ),
+            };
+
+            if !self
+                .retry_policy
+                .should_retry(attempt, &failure, idempotent)
+            {
+                log::warn!("Request to {path} failed after {attempt} attempt(s): {failure}");
+                return Err(error);
+            }
 
-    async fn wait_for_rate_limit(&self, limiter_type: RateLimiterType) {
-        let mut limiter = self.request_limiters[limiter_type as usize].lock().await;
-        limiter.wait().await;
+            let delay = self.retry_policy.backoff(attempt);
+            log::warn!("Request to {path} failed ({failure}). Retrying in {delay:?}");
+            sleep(delay).await;
+        }
     }
 
     async fn get_items_with_cursor<'a>(
@@ -165,26 +253,19 @@ impl Client {
         Ok(response.sales)
     }
 
-    pub async fn get_personal_fees(&self, game_id: &str) -> Result<Vec<ListPersonalFee>> {
+    /// The default fee of `game_id` and all reduced fees on its titles.
+    pub async fn get_fees(&self, game_id: &str) -> Result<ListFeeResponse> {
         let path = "/exchange/v1/customized-fees";
         let query = json!({
             "gameID": game_id,
             "limit": u32::MAX,
         });
 
-        let response = self.get::<ListFeeResponse>(path, query).await?;
-        Ok(response.reduced_fees)
+        self.get(path, query).await
     }
 
-    pub async fn get_default_fee(&self, game_id: &str) -> Result<ListDefaultFee> {
-        let path = "/exchange/v1/customized-fees";
-        let query = json!({
-            "gameID": game_id,
-            "limit": 1,
-        });
-
-        let response = self.get::<ListFeeResponse>(path, query).await?;
-        Ok(response.default_fee)
+    pub async fn get_personal_fees(&self, game_id: &str) -> Result<Vec<ListPersonalFee>> {
+        Ok(self.get_fees(game_id).await?.reduced_fees)
     }
 
     pub async fn get_balance(&self) -> Result<Balance> {
@@ -246,7 +327,8 @@ impl Client {
             let response: PaginatedResponse<T> = self.get(path, json!({"Cursor": cursor})).await?;
 
             items.extend(response.items);
-            if items.len() == response.total.parse::<usize>()? {
+            // Items that failed to parse are left out, so the count may never reach the total
+            if items.len() >= response.total.parse::<usize>()? || response.cursor.is_empty() {
                 break;
             }
 
@@ -294,7 +376,7 @@ impl Client {
         .await
     }
 
-    pub async fn create_offer(&self, item_id: Uuid, price: f64) -> Result<CreateOffersResponse> {
+    pub async fn create_offer(&self, item_id: Uuid, price: Money) -> Result<CreateOffersResponse> {
         self.create_offers(&[CreateOffer::new(item_id, price)])
             .await
     }
@@ -336,13 +418,93 @@ impl Client {
     }
 
     async fn get_all_items(&self, endpoint: &str) -> Result<Vec<Item>> {
-        stream::iter(GAME_IDS)
-            .then(|id| self.get_items(id, None, endpoint))
-            .try_concat()
-            .await
+        let mut items = Vec::new();
+        for id in GAME_IDS {
+            items.extend(self.get_items(id, None, endpoint).await?);
+        }
+        Ok(items)
+    }
+
+    /// Raw responses of the read-only endpoints, checked against the structs they are
+    /// deserialized into. Market items, sales and targets are sampled for `game_title`.
+    pub async fn sample_responses(&self, game_title: &GameTitle) -> Result<Vec<ResponseSample>> {
+        let title_query = json!({
+            "gameId": game_title.game_id,
+            "title": game_title.title,
+            "currency": CURRENCY_USD,
+            "limit": MARKET_LIMIT,
+        });
+        let game_query = json!({
+            "gameId": game_title.game_id,
+            "currency": CURRENCY_USD,
+            "limit": MARKET_LIMIT,
+        });
+
+        let mut samples = Vec::new();
+        for (path, query) in [
+            ("/exchange/v1/market/items", &title_query),
+            ("/exchange/v1/user/items", &game_query),
+            ("/exchange/v1/user/targets", &game_query),
+        ] {
+            let response: Value = self.get(path, query.clone()).await?;
+            samples.push(ResponseSample::new::<ItemResponse>(path, &response, ""));
+            samples.push(ResponseSample::new::<Item>(path, &response, "/objects"));
+        }
+
+        let path = "/trade-aggregator/v1/last-sales";
+        let response: Value = self.get(path, title_query.clone()).await?;
+        samples.push(ResponseSample::new::<Sale>(path, &response, "/sales"));
+
+        let path = "/order-book/v2/market-depth";
+        let response: Value = self.get(path, title_query).await?;
+        samples.push(ResponseSample::new::<GetTargetsResponse>(
+            path, &response, "",
+        ));
+
+        let path = "/exchange/v1/customized-fees";
+        let response: Value = self
+            .get(
+                path,
+                json!({"gameID": game_title.game_id, "limit": MARKET_LIMIT}),
+            )
+            .await?;
+        samples.push(ResponseSample::new::<ListDefaultFee>(
+            path,
+            &response,
+            "/defaultFee",
+        ));
+        samples.push(ResponseSample::new::<ListPersonalFee>(
+            path,
+            &response,
+            "/reducedFees",
+        ));
+
+        let path = "/price-aggregator/v1/aggregated-prices";
+        let response: Value = self.get(path, json!({})).await?;
+        samples.push(ResponseSample::new::<BestPricesResponse>(
+            path, &response, "",
+        ));
+
+        let path = "/marketplace-api/v1/user-offers";
+        let response: Value = self.get(path, json!({})).await?;
+        samples.push(ResponseSample::new::<PaginatedResponse<Value>>(
+            path, &response, "",
+        ));
+        samples.push(ResponseSample::new::<Offer>(path, &response, "/Items"));
+
+        let path = "/account/v1/balance";
+        let response: Value = self.get(path, json!({})).await?;
+        samples.push(ResponseSample::new::<Balance>(path, &response, ""));
+
+        Ok(samples)
     }
 
-    pub async fn get_best_offer(&self, game_title: &GameTitle) -> Result<Option<Item>> {
+    /// The cheapest offer on `game_title` that is not listed by one of `owners`.
+    pub async fn get_best_offer(
+        &self,
+        game_title: &GameTitle,
+        owners: &HashSet<Uuid>,
+    ) -> Result<Option<Item>> {
         let items = self
             .get_items(
                 &game_title.game_id,
@@ -353,7 +515,7 @@ impl Client {
 
         Ok(items
             .into_iter()
-            .filter(|i| i.title == game_title.title)
+            .filter(|i| i.title == game_title.title && !owners.contains(&i.owner))
             .min_by_key(|item| {
                 item.price
                     .as_ref()
@@ -1,8 +1,13 @@
 use crate::schema::*;
 use crate::Result;
+use catalog::ReferencePrice;
+use common::{map, Money};
 use sqlx::postgres::PgPoolOptions;
+use sqlx::types::time::OffsetDateTime;
 use sqlx::PgPool;
+use std::collections::HashSet;
 use std::env;
+use uuid::Uuid;
 
 const MAX_CONNECTIONS: u32 = 50;
 
@@ -18,7 +23,16 @@ impl Database {
             .connect(&env::var("DATABASE_URL")?)
             .await?;
 
-        Ok(Self { pool })
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Wraps an existing connection pool, e.g. one provided by `sqlx::test`.
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
     }
 
     pub async fn store_game_titles(&self, game_titles: Vec<GameTitle>) -> Result<()> {
@@ -69,10 +83,11 @@ impl Database {
                     tx_operation_type
                 )
                 VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT ON CONSTRAINT dmarket_sales_natural_key DO NOTHING
                 "#,
                 sale.game_title.game_id,
                 sale.game_title.title,
-                sale.price,
+                sale.price.mills(),
                 sale.date,
                 sale.tx_operation_type,
             )
@@ -84,7 +99,32 @@ impl Database {
         Ok(())
     }
 
-    pub async fn get_latest_date(&self, game_title: &GameTitle) -> Result<u64> {
+    /// All recorded sales of `game_title`, oldest first.
+    pub async fn get_sales(&self, game_title: &GameTitle) -> Result<Vec<Sale>> {
+        let records = sqlx::query!(
+            r#"
+            SELECT id, price, date, tx_operation_type
+            FROM dmarket_sales
+            WHERE game_id = $1 AND title = $2
+            ORDER BY date
+            "#,
+            game_title.game_id,
+            game_title.title
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(map(records, |r| Sale {
+            price: Money::from_mills(r.price),
+            date: r.date,
+            tx_operation_type: r.tx_operation_type,
+            id: r.id,
+            game_title: game_title.clone(),
+        }))
+    }
+
+    /// When the last recorded sale of `game_title` was made, the epoch if there is none.
+    pub async fn get_latest_date(&self, game_title: &GameTitle) -> Result<OffsetDateTime> {
         let latest_date = sqlx::query_scalar!(
             r#"
             SELECT max(date)
@@ -97,17 +137,14 @@ impl Database {
         .fetch_one(&self.pool)
         .await?;
 
-        Ok(latest_date
-            .map(|d| d.parse().unwrap_or_default())
-            .unwrap_or_default())
+        Ok(latest_date.unwrap_or(OffsetDateTime::UNIX_EPOCH))
     }
 
-    pub async fn store_reduced_fees(
-        &self,
-        game_id: &str,
-        fees: Vec<ListPersonalFee>,
-    ) -> Result<()> {
+    /// Replaces the fees of `game_id` with the ones currently charged, dropping reduced fees
+    /// that expired or were withdrawn.
+    pub async fn store_fees(&self, game_id: &str, fees: ListFeeResponse) -> Result<()> {
         let game_titles = fees
+            .reduced_fees
             .iter()
             .map(|f| GameTitle {
                 game_id: game_id.to_string(),
@@ -117,7 +154,28 @@ impl Database {
         self.store_game_titles(game_titles).await?;
         let mut tx = self.pool.begin().await?;
 
-        for fee in fees {
+        sqlx::query!(
+            r#"
+            INSERT INTO dmarket_default_fees (game_id, fraction, min_fee)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (game_id) DO UPDATE
+            SET fraction = EXCLUDED.fraction, min_fee = EXCLUDED.min_fee, updated_at = now()
+            "#,
+            game_id,
+            fees.default_fee.fraction,
+            fees.default_fee.min_amount.mills()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM dmarket_reduced_fees WHERE game_id = $1",
+            game_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for fee in fees.reduced_fees {
             sqlx::query!(
                 r#"
                 INSERT INTO dmarket_reduced_fees (
@@ -128,14 +186,16 @@ impl Database {
                     max_price,
                     min_price
                 )
-                VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING
+                SELECT $1, $2, $3::BIGINT, $4, $5, $6
+                WHERE $3::BIGINT > EXTRACT(EPOCH FROM now())
+                ON CONFLICT DO NOTHING
                 "#,
                 game_id,
                 fee.title,
                 fee.expires_at,
                 fee.fraction,
-                fee.max_price,
-                fee.min_price
+                fee.max_price.mills(),
+                fee.min_price.mills()
             )
             .execute(&mut *tx)
             .await?;
@@ -145,6 +205,22 @@ impl Database {
         Ok(())
     }
 
+    /// The default fee of `game_id`, if it was synced.
+    pub async fn get_default_fee(&self, game_id: &str) -> Result<Option<ListDefaultFee>> {
+        Ok(sqlx::query_as!(
+            ListDefaultFee,
+            r#"
+            SELECT fraction, min_fee AS min_amount
+            FROM dmarket_default_fees
+            WHERE game_id = $1
+            "#,
+            game_id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// The reduced fee on a title, unless it has expired.
     pub async fn get_reduced_fee(&self, game_title: &GameTitle) -> Result<Option<ListPersonalFee>> {
         Ok(sqlx::query_as!(
             ListPersonalFee,
@@ -156,7 +232,7 @@ impl Database {
                 max_price,
                 min_price
             FROM dmarket_reduced_fees
-            WHERE game_id = $1 AND title = $2
+            WHERE game_id = $1 AND title = $2 AND expires_at > EXTRACT(EPOCH FROM now())
             "#,
             game_title.game_id,
             game_title.title
@@ -173,10 +249,11 @@ impl Database {
                 SELECT
                     game_id,
                     title,
-                    LN(price::real) as log_price,
-                    date::integer as time
+                    LN(price / 1000.0)::FLOAT8 as log_price,
+                    EXTRACT(EPOCH FROM date)::FLOAT8 as time,
+                    date
                 FROM dmarket_sales
-                WHERE price::real > 0
+                WHERE price > 0
             ),
             price_quartiles AS (
                 SELECT
@@ -202,11 +279,12 @@ impl Database {
                 COUNT(*)::INTEGER as sale_count,
                 SUM(
                     CASE
-                        WHEN fs.time >= EXTRACT(EPOCH FROM (NOW() - INTERVAL '30 days')) THEN 1
+                        WHEN fs.date >= NOW() - INTERVAL '30 days' THEN 1
                         ELSE 0
                     END
                 )::INTEGER AS monthly_sales,
-                REGR_SLOPE(fs.log_price, fs.time) as price_slope
+                REGR_SLOPE(fs.log_price, fs.time) as price_slope,
+                STDDEV_SAMP(fs.log_price) as price_volatility
             FROM filtered_sales fs
             JOIN outlier_bounds ob ON fs.game_id = ob.game_id AND fs.title = ob.title
             WHERE fs.log_price BETWEEN ob.lower_bound AND ob.upper_bound
@@ -230,21 +308,24 @@ impl Database {
                     mean_price,
                     sale_count,
                     monthly_sales,
-                    price_slope 
+                    price_slope,
+                    price_volatility
                 )
-                VALUES ($1, $2, $3, $4, $5, $6)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
                 ON CONFLICT (game_id, title) DO UPDATE SET
                     mean_price = EXCLUDED.mean_price,
                     sale_count = EXCLUDED.sale_count,
                     monthly_sales = EXCLUDED.monthly_sales,
-                    price_slope = EXCLUDED.price_slope
+                    price_slope = EXCLUDED.price_slope,
+                    price_volatility = EXCLUDED.price_volatility
                 "#,
                 stat.game_id,
                 stat.title,
                 stat.mean_price,
                 stat.sale_count,
                 stat.monthly_sales,
-                stat.price_slope
+                stat.price_slope,
+                stat.price_volatility
             )
             .execute(&mut *tx)
             .await?;
@@ -263,7 +344,8 @@ impl Database {
                 mean_price,
                 sale_count,
                 monthly_sales,
-                price_slope
+                price_slope,
+                price_volatility
             FROM dmarket_game_titles
             WHERE game_id = $1 AND title = $2
             "#,
@@ -276,6 +358,33 @@ impl Database {
         Ok(stats)
     }
 
+    /// Steam reference price of `game_title`, if the catalog links it to one.
+    pub async fn get_steam_reference(
+        &self,
+        game_title: &GameTitle,
+    ) -> Result<Option<ReferencePrice>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT srp.median_price, srp.lowest_price, srp.daily_volume, srp.monthly_volume,
+                srp.fetched_at
+            FROM steam_reference_price srp
+            JOIN catalog_item ci ON ci.id = srp.catalog_item_id
+            WHERE ci.dmarket_game_id = $1 AND ci.dmarket_title = $2
+            "#,
+            game_title.game_id,
+            game_title.title
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| ReferencePrice {
+            median_price: Money::from_mills(row.median_price),
+            lowest_price: row.lowest_price.map(Money::from_mills),
+            daily_volume: row.daily_volume,
+            monthly_volume: row.monthly_volume,
+            fetched_at: row.fetched_at,
+        }))
+    }
+
     pub async fn get_game_title(&self, title: String) -> Result<Option<GameTitle>> {
         Ok(sqlx::query_as!(
             GameTitle,
@@ -286,16 +395,187 @@ impl Database {
         .await?)
     }
 
-    pub async fn get_balance(&self) -> Result<i32> {
-        Ok(sqlx::query_scalar!("SELECT balance FROM dmarket_account")
-            .fetch_one(&self.pool)
-            .await?)
+    pub async fn get_balance(&self, account_id: i32) -> Result<Money> {
+        Ok(sqlx::query_scalar!(
+            "SELECT balance FROM dmarket_account WHERE account_id = $1",
+            account_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Money::from_mills)
+        .unwrap_or_default())
     }
 
-    pub async fn update_balance(&self, balance: i32) -> Result<()> {
-        sqlx::query!("UPDATE dmarket_account SET balance = $1", balance)
-            .execute(&self.pool)
-            .await?;
+    pub async fn update_balance(&self, account_id: i32, balance: Money) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO dmarket_account (account_id, balance) VALUES ($1, $2)
+            ON CONFLICT (account_id) DO UPDATE SET balance = EXCLUDED.balance
+            "#,
+            account_id,
+            balance.mills()
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Enabled DMarket accounts, in the order they were added.
+    pub async fn get_trading_accounts(&self) -> Result<Vec<TradingAccount>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, credentials, owner_id, max_purchase_price, daily_spend_limit
+            FROM trading_account
+            WHERE market = 'dmarket' AND enabled
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| TradingAccount {
+                id: row.id,
+                name: row.name,
+                credentials: row.credentials,
+                owner_id: row.owner_id,
+                max_purchase_price: row.max_purchase_price.map(Money::from_mills),
+                daily_spend_limit: row.daily_spend_limit.map(Money::from_mills),
+            })
+            .collect())
+    }
+
+    /// User IDs of all of our DMarket accounts, including disabled ones whose offers may still
+    /// be listed.
+    pub async fn get_owner_ids(&self) -> Result<HashSet<Uuid>> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT owner_id AS "owner_id!"
+            FROM trading_account
+            WHERE market = 'dmarket' AND owner_id IS NOT NULL
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .collect())
+    }
+
+    /// Total price of the purchases of `account_id` in the last day that did not fail.
+    pub async fn get_daily_spend(&self, account_id: i32) -> Result<Money> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(price), 0)::BIGINT AS "spend!"
+            FROM dmarket_purchases
+            WHERE account_id = $1
+              AND status != 'failed'
+              AND created_at > now() - INTERVAL '1 day'
+            "#,
+            account_id
+        )
+        .fetch_one(&self.pool)
+        .await?
+        .into())
+    }
+
+    /// Records that we are about to buy an offer.
+    ///
+    /// Returns false if the offer was already bought or its purchase is still unresolved, in
+    /// which case it must not be bought again.
+    pub async fn start_purchase(&self, purchase: &Purchase) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO dmarket_purchases
+                (offer_id, account_id, item_id, game_id, title, price, status)
+            VALUES ($1, $2, $3, $4, $5, $6, 'pending')
+            ON CONFLICT (offer_id) DO UPDATE
+            SET price = EXCLUDED.price, status = 'pending', account_id = EXCLUDED.account_id,
+                created_at = now(), updated_at = now()
+            WHERE dmarket_purchases.status = 'failed'
+            "#,
+            purchase.offer_id,
+            purchase.account_id,
+            purchase.item_id,
+            purchase.game_id,
+            purchase.title,
+            purchase.price.mills(),
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    pub async fn set_purchase_status(&self, offer_id: Uuid, status: PurchaseStatus) -> Result<()> {
+        sqlx::query!(
+            "UPDATE dmarket_purchases SET status = $1, updated_at = now() WHERE offer_id = $2",
+            status as PurchaseStatus,
+            offer_id
+        )
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
+
+    pub async fn get_purchase(&self, offer_id: Uuid) -> Result<Option<Purchase>> {
+        Ok(sqlx::query_as!(
+            Purchase,
+            r#"
+            SELECT offer_id, account_id, item_id, game_id, title, price,
+                   status AS "status: PurchaseStatus", created_at
+            FROM dmarket_purchases
+            WHERE offer_id = $1
+            "#,
+            offer_id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Our confirmed purchase of `item_id`, i.e. what we paid for an item we hold.
+    pub async fn get_item_purchase(&self, item_id: Uuid) -> Result<Option<Purchase>> {
+        Ok(sqlx::query_as!(
+            Purchase,
+            r#"
+            SELECT offer_id, account_id, item_id, game_id, title, price,
+                   status AS "status: PurchaseStatus", created_at
+            FROM dmarket_purchases
+            WHERE item_id = $1 AND status = 'confirmed'
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+            item_id
+        )
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    pub async fn get_pending_purchases(&self, account_id: i32) -> Result<Vec<Purchase>> {
+        Ok(sqlx::query_as!(
+            Purchase,
+            r#"
+            SELECT offer_id, account_id, item_id, game_id, title, price,
+                   status AS "status: PurchaseStatus", created_at
+            FROM dmarket_purchases
+            WHERE status = 'pending' AND account_id = $1
+            ORDER BY created_at
+            "#,
+            account_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    pub async fn has_pending_purchase(&self, game_title: &GameTitle) -> Result<bool> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT 1 FROM dmarket_purchases
+            WHERE game_id = $1 AND title = $2 AND status = 'pending'
+            "#,
+            game_title.game_id,
+            game_title.title
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .is_some())
+    }
 }
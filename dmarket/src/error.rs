@@ -39,12 +39,18 @@ pub enum Error {
     #[error("EnvVar error: {0}")]
     EnvVar(#[from] env::VarError),
 
+    #[error("Secret error: {0}")]
+    Secret(#[from] common::secrets::Error),
+
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
 
     #[error("Couldn't convert query to string: {0}")]
     HttpQuery(#[from] serde_qs::Error),
 
+    #[error("Failed to deserialize response: {0}")]
+    Deserialize(#[from] common::quarantine::ParseError),
+
     #[error("Serde json error: {0}")]
     Json(#[from] serde_json::Error),
 
@@ -56,4 +62,7 @@ pub enum Error {
 
     #[error("Parse error: {0}")]
     ParseUuid(#[from] uuid::Error),
+
+    #[error("Invalid amount: {0}")]
+    Money(#[from] common::money::Error),
 }
@@ -0,0 +1,35 @@
+//! DMarket's fees on sales.
+//!
+//! Each game has a default rate and minimum fee, synced from the market. A title may also have
+//! a reduced rate until it expires, which only applies to sale prices within a range.
+use crate::client::CSGO_GAME_ID;
+use crate::schema::{ListDefaultFee, ListPersonalFee};
+use crate::Result;
+use common::{Bracket, FeeSchedule, Money};
+
+/// Rates charged before the default fee of a game was synced
+const CS_GO_DEFAULT_FEE: f64 = 0.1;
+const DEFAULT_FEE: f64 = 0.05;
+const DEFAULT_MIN_FEE: Money = Money::from_cents(1);
+
+/// The fees on a title of `game_id`, given the `default` fee of the game and the `reduced` fee
+/// on the title.
+pub fn schedule(
+    game_id: &str,
+    default: Option<&ListDefaultFee>,
+    reduced: Option<&ListPersonalFee>,
+) -> Result<FeeSchedule> {
+    let mut schedule = match default {
+        Some(fee) => FeeSchedule::flat(fee.fraction.parse()?, fee.min_amount),
+        None if game_id == CSGO_GAME_ID => FeeSchedule::flat(CS_GO_DEFAULT_FEE, DEFAULT_MIN_FEE),
+        None => FeeSchedule::flat(DEFAULT_FEE, DEFAULT_MIN_FEE),
+    };
+    if let Some(fee) = reduced {
+        schedule = schedule.with_bracket(Bracket {
+            min_price: Some(fee.min_price),
+            max_price: Some(fee.max_price),
+            rate: fee.fraction.parse()?,
+        });
+    }
+    Ok(schedule)
+}
@@ -0,0 +1,90 @@
+//! Choosing which offers to buy when flipping.
+//!
+//! Every profitable offer is evaluated and scored before anything is bought, so a limited
+//! balance goes to the best opportunities instead of the ones that happen to come first. The
+//! chosen offers are then bought in orders of several offers each.
+use crate::schema::GameTitle;
+use common::{FeeSchedule, Money};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Most offers bought in a single order
+pub const MAX_OFFERS_PER_ORDER: usize = 20;
+/// Monthly sales at which an item is expected to sell without delay
+const LIQUID_MONTHLY_SALES: f64 = 300.0;
+
+/// How good an opportunity buying for `price` is: the return per dollar spent after the fee,
+/// discounted for titles that sell slowly or whose price is uncertain. `volatility` is the
+/// standard deviation of the log sale prices.
+pub fn score(
+    price: Money,
+    list_price: Money,
+    fees: &FeeSchedule,
+    monthly_sales: i32,
+    volatility: f64,
+) -> f64 {
+    if price <= Money::ZERO {
+        return 0.0;
+    }
+    let expected_return = (fees.proceeds(list_price) - price).mills() as f64 / price.mills() as f64;
+    let liquidity = (f64::from(monthly_sales) / LIQUID_MONTHLY_SALES).min(1.0);
+    expected_return * liquidity / (1.0 + volatility)
+}
+
+/// An offer worth buying and what we expect to earn on it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Candidate {
+    pub game_title: GameTitle,
+    pub offer_id: Uuid,
+    pub item_id: Uuid,
+    pub price: Money,
+    /// Price we expect to sell the item for
+    pub list_price: Money,
+    /// What selling for the list price leaves after the fee and the purchase price
+    pub expected_profit: Money,
+    /// See [`score`]
+    pub score: f64,
+}
+
+/// What a round of flipping may spend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+    /// Balance left within the daily spend limit of the account
+    pub available: Money,
+    /// Most the account pays for a single item, if limited
+    pub max_price: Option<Money>,
+}
+
+/// Picks the candidates to buy within `budget`, best score first. A candidate that no longer
+/// fits leaves the budget to cheaper ones further down.
+pub fn allocate(mut candidates: Vec<Candidate>, budget: Budget) -> Vec<Candidate> {
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.price.mills().cmp(&b.price.mills()))
+    });
+    let mut available = budget.available;
+    let mut chosen = Vec::new();
+    for candidate in candidates {
+        if budget.max_price.is_some_and(|max| candidate.price > max) {
+            log::warn!(
+                "Not buying {}: {} exceeds the max purchase price",
+                candidate.game_title.title,
+                candidate.price
+            );
+            continue;
+        }
+        if candidate.price > available {
+            continue;
+        }
+        available = available - candidate.price;
+        log::info!(
+            "Chose {} for {} with score {:.3}",
+            candidate.game_title.title,
+            candidate.price,
+            candidate.score
+        );
+        chosen.push(candidate);
+    }
+    chosen
+}
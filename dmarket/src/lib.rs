@@ -1,12 +1,22 @@
+pub mod batch;
 pub mod client;
 mod db;
 mod error;
-mod rate_limiter;
+pub mod fees;
+pub mod flip;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod preflight;
+pub mod scheduler;
 pub mod schema;
+mod signer;
+pub mod targets;
 pub mod trader;
 
-pub use client::{Client, GAME_IDS};
+pub use batch::{BatchFailure, BatchSummary};
+pub use client::{Client, RequestGroup, GAME_IDS};
 pub use db::Database;
+pub use signer::Signer;
 pub use trader::Trader;
 
 pub type Result<T> = std::result::Result<T, error::Error>;
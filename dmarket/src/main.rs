@@ -1,17 +1,41 @@
-use anyhow::Result;
+mod cli;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use cli::{Cli, Command};
+use dmarket::scheduler::Scheduler;
 use dmarket::Trader;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
     common::setup_env();
-    let trader = Trader::new().await?;
+    if let Some(Command::Preflight) = cli.command {
+        dmarket::preflight::run().await.exit();
+    }
+    common::secrets::init()?;
+    match &cli.command {
+        None | Some(Command::Run) => start_dmarket().await,
+        Some(command) => cli.execute(command).await,
+    }
+}
 
-    loop {
-        trader.sync().await?;
-        trader.flip().await?;
-        trader.update_offers().await?;
-        trader.list_inventory().await?;
-        trader.delete_targets().await?;
-        trader.create_targets().await?;
+async fn start_dmarket() -> Result<()> {
+    let traders = Trader::for_all_accounts().await?;
+    if traders.is_empty() {
+        bail!("No enabled DMarket accounts");
     }
+
+    for (i, trader) in traders.into_iter().enumerate() {
+        let scheduler = Scheduler::new(trader).await?;
+        // Market data is shared, so only the first account collects it
+        if i == 0 {
+            scheduler.schedule_market_tasks().await?;
+        }
+        scheduler.schedule_account_tasks().await?;
+        scheduler.start().await?;
+    }
+
+    tokio::signal::ctrl_c().await?;
+    Ok(())
 }
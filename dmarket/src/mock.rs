@@ -0,0 +1,849 @@
+//! In-process fake of the DMarket API.
+//!
+//! The server keeps a scriptable [`MarketState`] in memory and implements the paths used by
+//! [`Client`], including cursor pagination and injected `429 Too Many Requests` responses, so
+//! `Trader` flows can be integration-tested without the live service.
+use crate::schema::DEFAULT_OWNER_ID;
+use crate::{Client, Signer};
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+const PAGE_SIZE: usize = 100;
+/// Seed of the only key pair the fake server accepts signatures from
+const SECRET_KEY: [u8; 32] = [7; 32];
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An item on the fake market or in our inventory. Prices are in cents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockItem {
+    pub item_id: Uuid,
+    pub offer_id: Uuid,
+    pub game_id: String,
+    pub title: String,
+    pub price: u64,
+    pub owner: Uuid,
+}
+
+/// One of our offers. Prices are in dollars, as in `MarketMoney`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockOffer {
+    pub offer_id: Uuid,
+    pub item: MockItem,
+    pub price: f64,
+}
+
+/// One of our buy orders. Prices are in dollars, as in `MarketMoney`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockTarget {
+    pub target_id: Uuid,
+    pub game_id: String,
+    pub title: String,
+    pub price: f64,
+}
+
+/// A buy order placed by someone else, as shown in the market depth. Prices are in cents.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockBid {
+    pub game_id: String,
+    pub title: String,
+    pub price: u64,
+    pub amount: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct MockSale {
+    pub game_id: String,
+    pub title: String,
+    pub price: String,
+    pub date: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct MockFee {
+    pub game_id: String,
+    pub title: String,
+    pub fraction: String,
+    pub expires_at: i64,
+    pub min_price: i64,
+    pub max_price: i64,
+}
+
+/// Everything the fake server knows about the market and our account.
+pub struct MarketState {
+    /// Balance in cents
+    pub balance: i64,
+    pub default_fee: String,
+    pub market: Vec<MockItem>,
+    pub inventory: Vec<MockItem>,
+    pub offers: Vec<MockOffer>,
+    pub targets: Vec<MockTarget>,
+    pub bids: Vec<MockBid>,
+    pub sales: Vec<MockSale>,
+    pub fees: Vec<MockFee>,
+    pub purchases: Vec<MockItem>,
+    /// Holds bought items in [`Self::in_transfer`] until [`Self::deliver`] is called
+    pub hold_deliveries: bool,
+    /// Bought items that are not in our inventory yet
+    pub in_transfer: Vec<MockItem>,
+    /// Offers priced above this many dollars are rejected
+    pub max_offer_price: Option<f64>,
+    /// Method and path of every request received, in order
+    pub requests: Vec<(Method, String)>,
+    failures: HashMap<String, VecDeque<StatusCode>>,
+    lost_responses: HashMap<String, VecDeque<StatusCode>>,
+    next_id: u128,
+}
+
+impl Default for MarketState {
+    fn default() -> Self {
+        Self {
+            balance: 0,
+            default_fee: "0.1".to_string(),
+            market: Vec::new(),
+            inventory: Vec::new(),
+            offers: Vec::new(),
+            targets: Vec::new(),
+            bids: Vec::new(),
+            sales: Vec::new(),
+            fees: Vec::new(),
+            purchases: Vec::new(),
+            hold_deliveries: false,
+            in_transfer: Vec::new(),
+            max_offer_price: None,
+            requests: Vec::new(),
+            failures: HashMap::new(),
+            lost_responses: HashMap::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl MarketState {
+    fn new_id(&mut self) -> Uuid {
+        self.next_id += 1;
+        Uuid::from_u128(self.next_id)
+    }
+
+    /// Lists an item owned by someone else on the market and returns it.
+    pub fn list(&mut self, game_id: &str, title: &str, price: u64) -> MockItem {
+        let item = MockItem {
+            item_id: self.new_id(),
+            offer_id: self.new_id(),
+            game_id: game_id.to_string(),
+            title: title.to_string(),
+            price,
+            owner: Uuid::from_u128(1),
+        };
+        self.market.push(item.clone());
+        item
+    }
+
+    /// Adds an item to our inventory and returns it.
+    pub fn add_inventory(&mut self, game_id: &str, title: &str) -> MockItem {
+        let item = MockItem {
+            item_id: self.new_id(),
+            offer_id: Uuid::nil(),
+            game_id: game_id.to_string(),
+            title: title.to_string(),
+            price: 0,
+            owner: Uuid::parse_str(DEFAULT_OWNER_ID).unwrap(),
+        };
+        self.inventory.push(item.clone());
+        item
+    }
+
+    /// Adds sales of `title` one hour apart, the first of `prices` (in dollars) being sold now.
+    pub fn add_sales<I: IntoIterator<Item = f64>>(
+        &mut self,
+        game_id: &str,
+        title: &str,
+        prices: I,
+    ) {
+        let now = unix_now();
+        self.sales
+            .extend(prices.into_iter().zip(0..).map(|(price, i)| MockSale {
+                game_id: game_id.to_string(),
+                title: title.to_string(),
+                price: format!("{price:.2}"),
+                date: now - i * 3600,
+            }));
+    }
+
+    /// Makes the next request to `path` fail with `status`.
+    pub fn fail_next(&mut self, path: &str, status: StatusCode) {
+        self.failures
+            .entry(path.to_string())
+            .or_default()
+            .push_back(status);
+    }
+
+    /// Makes the next request to `path` take effect but answer with `status`, as if the response
+    /// was lost on the way back.
+    pub fn lose_next_response(&mut self, path: &str, status: StatusCode) {
+        self.lost_responses
+            .entry(path.to_string())
+            .or_default()
+            .push_back(status);
+    }
+
+    /// Places a target as if it had been created earlier. `price` is in dollars.
+    pub fn add_target(&mut self, game_id: &str, title: &str, price: f64) -> Uuid {
+        let target_id = self.new_id();
+        self.targets.push(MockTarget {
+            target_id,
+            game_id: game_id.to_string(),
+            title: title.to_string(),
+            price,
+        });
+        target_id
+    }
+
+    /// The error for an offer priced at `price` dollars, if the market would reject it.
+    fn offer_price_error(&self, price: f64) -> Option<Value> {
+        let max = self.max_offer_price.filter(|max| price > *max)?;
+        Some(json!({
+            "Code": "PriceOutOfRange",
+            "Message": format!("Price must be between $0.02 and ${max:.2}"),
+        }))
+    }
+
+    /// Completes the transfer of every item bought while deliveries were held.
+    pub fn deliver(&mut self) {
+        self.inventory.append(&mut self.in_transfer);
+    }
+
+    pub fn request_count(&self, path: &str) -> usize {
+        self.requests.iter().filter(|(_, p)| p == path).count()
+    }
+
+    fn buy(&mut self, offer_id: &str, max_price: u64) -> bool {
+        let Some(index) = self
+            .market
+            .iter()
+            .position(|i| i.offer_id.to_string() == offer_id)
+        else {
+            return false;
+        };
+        let price = self.market[index].price;
+        if price > max_price || price as i64 > self.balance {
+            return false;
+        }
+        let item = self.market.remove(index);
+        self.balance -= price as i64;
+        self.purchases.push(item.clone());
+        self.in_transfer.push(MockItem {
+            owner: Uuid::parse_str(DEFAULT_OWNER_ID).unwrap(),
+            ..item
+        });
+        if !self.hold_deliveries {
+            self.deliver();
+        }
+        true
+    }
+}
+
+/// Best prices and counts for one title in the aggregated prices response. Prices are in cents.
+#[derive(Default)]
+struct TitleSummary {
+    best_offer: Option<u64>,
+    offers: i32,
+    best_order: Option<u64>,
+    orders: i32,
+}
+
+#[derive(Clone)]
+struct Shared {
+    state: Arc<Mutex<MarketState>>,
+}
+
+/// A running fake DMarket server bound to a random local port.
+pub struct MockServer {
+    addr: SocketAddr,
+    shared: Shared,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let shared = Shared {
+            state: Default::default(),
+        };
+
+        let app = Router::new().fallback(handler).with_state(shared.clone());
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Self { addr, shared }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The hex encoded public and secret key this server accepts.
+    pub fn key_pair() -> (String, String) {
+        let signing_key = SigningKey::from_bytes(&SECRET_KEY);
+        (
+            hex::encode(signing_key.verifying_key().to_bytes()),
+            hex::encode(SECRET_KEY),
+        )
+    }
+
+    /// A [`Signer`] holding the key pair this server accepts.
+    pub fn signer() -> Signer {
+        let (api_key, secret_key) = Self::key_pair();
+        Signer::new(&api_key, &secret_key).expect("valid mock key pair")
+    }
+
+    /// A [`Client`] pointed at this server.
+    pub fn client(&self) -> Client {
+        Client::with_base_url(&self.url(), Self::signer()).expect("valid mock server url")
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MarketState> {
+        self.shared.state.lock().unwrap()
+    }
+
+    /// Polls the state until `condition` holds, panicking after a generous timeout.
+    pub async fn wait_until<F: Fn(&MarketState) -> bool>(&self, condition: F) {
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            while !condition(&self.state()) {
+                tokio::time::sleep(WAIT_INTERVAL).await;
+            }
+        })
+        .await
+        .expect("condition not met before timeout");
+    }
+}
+
+/// Checks the DMarket signature headers against the key pair in [`SECRET_KEY`].
+fn is_signed(method: &Method, uri: &Uri, headers: &HeaderMap, body: &[u8]) -> bool {
+    let header = |name| headers.get(name).and_then(|h| h.to_str().ok());
+    let verifying_key = SigningKey::from_bytes(&SECRET_KEY).verifying_key();
+    let (Some(api_key), Some(signature), Some(timestamp)) = (
+        header("X-Api-Key"),
+        header("X-Request-Sign").and_then(|s| s.strip_prefix("dmar ed25519 ")),
+        header("X-Sign-Date"),
+    ) else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_slice(&signature) else {
+        return false;
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or_default();
+    let message = format!("{method}{path}{}{timestamp}", String::from_utf8_lossy(body));
+
+    api_key == hex::encode(verifying_key.to_bytes())
+        && VerifyingKey::verify_strict(&verifying_key, message.as_bytes(), &signature).is_ok()
+}
+
+/// 500 sale prices averaging `mean`, the recent half slightly above the older half, as
+/// [`MarketState::add_sales`] expects them with the newest first.
+pub fn trending_prices(mean: f64) -> impl Iterator<Item = f64> {
+    (0..500).map(move |i| if i < 250 { mean * 1.01 } else { mean * 0.99 })
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn dollars(cents: u64) -> String {
+    format!("{:.2}", cents as f64 / 100.0)
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(json!({ "code": status.as_u16(), "message": message })),
+    )
+        .into_response()
+}
+
+fn market_error(message: &str) -> Value {
+    json!({ "Code": "BadRequest", "Message": message })
+}
+
+fn item_json(item: &MockItem, price: u64, item_type: &str) -> Value {
+    json!({
+        "gameId": item.game_id,
+        "itemId": item.item_id,
+        "title": item.title,
+        "amount": 1,
+        "createdAt": unix_now(),
+        "discount": 0,
+        "extra": {
+            "isNew": false,
+            "tradable": true,
+            "offerId": (!item.offer_id.is_nil()).then_some(item.offer_id),
+        },
+        "status": "active",
+        "price": { "USD": price.to_string() },
+        "instantPrice": null,
+        "suggestedPrice": null,
+        "type": item_type,
+        "owner": item.owner,
+    })
+}
+
+fn cursor_page(
+    items: Vec<Value>,
+    query: &HashMap<String, String>,
+    key: &str,
+) -> (Vec<Value>, Option<String>) {
+    let start = query.get(key).and_then(|c| c.parse().ok()).unwrap_or(0);
+    let limit = query
+        .get("limit")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(PAGE_SIZE);
+    let end = (start + limit).min(items.len());
+    let cursor = (end < items.len()).then(|| end.to_string());
+    (items[start.min(end)..end].to_vec(), cursor)
+}
+
+fn item_response(items: Vec<Value>, query: &HashMap<String, String>) -> Value {
+    let count = items.len();
+    let (objects, cursor) = cursor_page(items, query, "cursor");
+    json!({
+        "cursor": cursor,
+        "objects": objects,
+        "total": { "items": count, "offers": count, "targets": count },
+    })
+}
+
+fn matches_filter(item: &MockItem, query: &HashMap<String, String>) -> bool {
+    query.get("gameId").is_none_or(|g| *g == item.game_id)
+        && query
+            .get("title")
+            .filter(|t| !t.is_empty())
+            .is_none_or(|t| item.title.contains(t.as_str()))
+}
+
+async fn handler(
+    State(shared): State<Shared>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> Response {
+    let path = uri.path().to_string();
+    let mut state = shared.state.lock().unwrap();
+    state.requests.push((method.clone(), path.clone()));
+
+    if !is_signed(&method, &uri, &headers, &body) {
+        return error(StatusCode::UNAUTHORIZED, "Invalid signature");
+    }
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    if let Some(status) = state.failures.get_mut(&path).and_then(|f| f.pop_front()) {
+        return error(status, "Injected failure");
+    }
+
+    let response = route(&mut state, method, &path, &query, &body);
+
+    match state
+        .lost_responses
+        .get_mut(&path)
+        .and_then(|f| f.pop_front())
+    {
+        Some(status) => error(status, "Injected failure after processing"),
+        None => response,
+    }
+}
+
+fn route(
+    state: &mut MarketState,
+    method: Method,
+    path: &str,
+    query: &HashMap<String, String>,
+    body: &Value,
+) -> Response {
+    match (method, path) {
+        (Method::GET, "/exchange/v1/market/items") => {
+            let mut items: Vec<_> = state
+                .market
+                .iter()
+                .chain(state.offers.iter().map(|o| &o.item))
+                .filter(|i| matches_filter(i, query))
+                .collect();
+            items.sort_by_key(|i| i.price);
+            let offer_prices: HashMap<_, _> = state
+                .offers
+                .iter()
+                .map(|o| (o.item.item_id, (o.price * 100.0).round() as u64))
+                .collect();
+            let items = items
+                .into_iter()
+                .map(|i| {
+                    item_json(
+                        i,
+                        *offer_prices.get(&i.item_id).unwrap_or(&i.price),
+                        "offer",
+                    )
+                })
+                .collect();
+            Json(item_response(items, query)).into_response()
+        }
+        (Method::GET, "/exchange/v1/user/items") => {
+            let items = state
+                .inventory
+                .iter()
+                .filter(|i| matches_filter(i, query))
+                .map(|i| item_json(i, i.price, "item"))
+                .collect();
+            Json(item_response(items, query)).into_response()
+        }
+        (Method::GET, "/exchange/v1/user/targets") => {
+            let items = state
+                .targets
+                .iter()
+                .filter(|t| query.get("gameId").is_none_or(|g| *g == t.game_id))
+                .map(|t| {
+                    let item = MockItem {
+                        item_id: t.target_id,
+                        offer_id: Uuid::nil(),
+                        game_id: t.game_id.clone(),
+                        title: t.title.clone(),
+                        price: 0,
+                        owner: Uuid::parse_str(DEFAULT_OWNER_ID).unwrap(),
+                    };
+                    item_json(&item, (t.price * 100.0).round() as u64, "target")
+                })
+                .collect();
+            Json(item_response(items, query)).into_response()
+        }
+        (Method::GET, "/trade-aggregator/v1/last-sales") => {
+            let limit = query
+                .get("limit")
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(usize::MAX);
+            let mut sales: Vec<_> = state
+                .sales
+                .iter()
+                .filter(|s| {
+                    query.get("gameId") == Some(&s.game_id) && query.get("title") == Some(&s.title)
+                })
+                .collect();
+            sales.sort_by_key(|s| std::cmp::Reverse(s.date));
+            let sales: Vec<_> = sales
+                .into_iter()
+                .take(limit)
+                .map(|s| {
+                    json!({
+                        "price": s.price,
+                        "date": s.date.to_string(),
+                        "txOperationType": "Offer",
+                    })
+                })
+                .collect();
+            Json(json!({ "sales": sales })).into_response()
+        }
+        (Method::GET, "/price-aggregator/v1/aggregated-prices") => {
+            let mut titles: BTreeMap<&str, TitleSummary> = BTreeMap::new();
+            for item in &state.market {
+                let summary = titles.entry(&item.title).or_default();
+                summary.best_offer =
+                    Some(summary.best_offer.map_or(item.price, |p| p.min(item.price)));
+                summary.offers += 1;
+            }
+            for bid in &state.bids {
+                let summary = titles.entry(&bid.title).or_default();
+                summary.best_order =
+                    Some(summary.best_order.map_or(bid.price, |p| p.max(bid.price)));
+                summary.orders += bid.amount as i32;
+            }
+            let aggregated: Vec<_> = titles
+                .into_iter()
+                .map(|(title, summary)| {
+                    json!({
+                        "MarketHashName": title,
+                        "Offers": {
+                            "BestPrice": dollars(summary.best_offer.unwrap_or(0)),
+                            "Count": summary.offers,
+                        },
+                        "Orders": {
+                            "BestPrice": dollars(summary.best_order.unwrap_or(0)),
+                            "Count": summary.orders,
+                        },
+                    })
+                })
+                .collect();
+            let total = aggregated.len();
+            Json(json!({
+                "Error": null,
+                "Total": total.to_string(),
+                "AggregatedTitles": aggregated,
+            }))
+            .into_response()
+        }
+        (Method::GET, "/exchange/v1/customized-fees") => {
+            let game_id = query.get("gameID").cloned().unwrap_or_default();
+            let fees: Vec<_> = state
+                .fees
+                .iter()
+                .filter(|f| f.game_id == game_id)
+                .map(|f| {
+                    json!({
+                        "expiresAt": f.expires_at,
+                        "fraction": f.fraction,
+                        "maxPrice": f.max_price,
+                        "minPrice": f.min_price,
+                        "title": f.title,
+                    })
+                })
+                .collect();
+            Json(json!({
+                "defaultFee": { "fraction": state.default_fee, "minAmount": 1 },
+                "reducedFees": fees,
+            }))
+            .into_response()
+        }
+        (Method::GET, "/account/v1/balance") => Json(json!({
+            "usd": state.balance.to_string(),
+            "usdAvailableToWithdraw": state.balance.to_string(),
+        }))
+        .into_response(),
+        (Method::GET, "/order-book/v2/market-depth") => {
+            // Our own targets are part of the order book too
+            let targets = state.targets.iter().map(|t| MockBid {
+                game_id: t.game_id.clone(),
+                title: t.title.clone(),
+                price: (t.price * 100.0).round() as u64,
+                amount: 1,
+            });
+            let orders: Vec<_> = state
+                .bids
+                .iter()
+                .cloned()
+                .chain(targets)
+                .filter(|b| {
+                    query.get("gameId") == Some(&b.game_id) && query.get("title") == Some(&b.title)
+                })
+                .map(|b| {
+                    json!({
+                        "amount": b.amount.to_string(),
+                        "price": dollars(b.price),
+                        "liquidity": "0",
+                        "attributes": [],
+                        "advancedAmount": "0",
+                    })
+                })
+                .collect();
+            Json(json!({ "UpdatedAt": unix_now().to_string(), "offers": [], "orders": orders }))
+                .into_response()
+        }
+        (Method::PATCH, "/exchange/v1/offers-buy") => {
+            let offers = body["offers"].as_array().cloned().unwrap_or_default();
+            let bought: Vec<_> = offers
+                .iter()
+                .map(|o| {
+                    let offer_id = o["offerId"].as_str().unwrap_or_default();
+                    let max_price = o["price"]["amount"]
+                        .as_str()
+                        .and_then(|a| a.parse().ok())
+                        .unwrap_or(0);
+                    (offer_id, state.buy(offer_id, max_price))
+                })
+                .collect();
+            let tx_status = |bought| if bought { "TxSuccess" } else { "TxFailed" };
+            let status = tx_status(bought.iter().all(|(_, b)| *b));
+            let offers_status: serde_json::Map<_, _> = bought
+                .iter()
+                .map(|(offer_id, b)| (offer_id.to_string(), json!({ "status": tx_status(*b) })))
+                .collect();
+            let order_id = state.new_id();
+            Json(json!({
+                "orderId": order_id,
+                "status": status,
+                "txId": order_id,
+                "dmOffersStatus": offers_status,
+            }))
+            .into_response()
+        }
+        (Method::POST, "/marketplace-api/v1/user-targets/create") => {
+            let game_id = body["GameID"].as_str().unwrap_or_default().to_string();
+            let targets = body["Targets"].as_array().cloned().unwrap_or_default();
+            let result: Vec<_> = targets
+                .into_iter()
+                .map(|t| {
+                    let target_id = state.new_id();
+                    state.targets.push(MockTarget {
+                        target_id,
+                        game_id: game_id.clone(),
+                        title: t["Title"].as_str().unwrap_or_default().to_string(),
+                        price: t["Price"]["Amount"].as_f64().unwrap_or_default(),
+                    });
+                    json!({
+                        "CreateTarget": {
+                            "Amount": t["Amount"].to_string(),
+                            "Price": t["Price"],
+                            "Title": t["Title"],
+                            "Attrs": t["Attrs"],
+                        },
+                        "TargetID": target_id,
+                        "Successful": true,
+                        "Error": null,
+                    })
+                })
+                .collect();
+            Json(json!({ "Result": result })).into_response()
+        }
+        (Method::POST, "/marketplace-api/v1/user-targets/delete") => {
+            let targets = body["Targets"].as_array().cloned().unwrap_or_default();
+            let result: Vec<_> = targets
+                .into_iter()
+                .map(|t| {
+                    let target_id = t["TargetID"].as_str().unwrap_or_default();
+                    let before = state.targets.len();
+                    state
+                        .targets
+                        .retain(|m| m.target_id.to_string() != target_id);
+                    let successful = state.targets.len() < before;
+                    json!({
+                        "DeleteTarget": t,
+                        "Successful": successful,
+                        "Error": (!successful).then(|| market_error("Target not found")),
+                    })
+                })
+                .collect();
+            Json(json!({ "Result": result })).into_response()
+        }
+        (Method::GET, "/marketplace-api/v1/user-offers") => {
+            let offers: Vec<_> = state
+                .offers
+                .iter()
+                .map(|o| {
+                    json!({
+                        "GameID": o.item.game_id,
+                        "Title": o.item.title,
+                        "AssetID": o.item.item_id,
+                        "Offer": {
+                            "OfferID": o.offer_id,
+                            "Price": { "Currency": "USD", "Amount": o.price },
+                        },
+                    })
+                })
+                .collect();
+            let total = offers.len();
+            let (items, cursor) = cursor_page(offers, query, "Cursor");
+            Json(json!({
+                "Items": items,
+                "Total": total.to_string(),
+                "Cursor": cursor.unwrap_or_default(),
+            }))
+            .into_response()
+        }
+        (Method::POST, "/marketplace-api/v1/user-offers/create") => {
+            let offers = body["Offers"].as_array().cloned().unwrap_or_default();
+            let result: Vec<_> = offers
+                .into_iter()
+                .map(|o| {
+                    let asset_id = o["AssetID"].as_str().unwrap_or_default();
+                    let price = o["Price"]["Amount"].as_f64().unwrap_or_default();
+                    let index = state
+                        .inventory
+                        .iter()
+                        .position(|i| i.item_id.to_string() == asset_id);
+                    let result = match (index, state.offer_price_error(price)) {
+                        (_, Some(error)) => Err(error),
+                        (None, _) => Err(market_error("Item not in inventory")),
+                        (Some(index), None) => {
+                            let offer_id = state.new_id();
+                            let item = state.inventory.remove(index);
+                            state.offers.push(MockOffer {
+                                offer_id,
+                                item: MockItem { offer_id, ..item },
+                                price,
+                            });
+                            Ok(offer_id)
+                        }
+                    };
+                    json!({
+                        "CreateOffer": o,
+                        "OfferID": result.as_ref().map(|id| id.to_string()).unwrap_or_default(),
+                        "Successful": result.is_ok(),
+                        "Error": result.err(),
+                    })
+                })
+                .collect();
+            Json(json!({ "Result": result })).into_response()
+        }
+        (Method::POST, "/marketplace-api/v1/user-offers/edit") => {
+            let offers = body["Offers"].as_array().cloned().unwrap_or_default();
+            let result: Vec<_> = offers
+                .into_iter()
+                .map(|o| {
+                    let offer_id = o["OfferID"].as_str().unwrap_or_default();
+                    let price = o["Price"]["Amount"].as_f64().unwrap_or_default();
+                    let price_error = state.offer_price_error(price);
+                    let offer = state
+                        .offers
+                        .iter_mut()
+                        .find(|m| m.offer_id.to_string() == offer_id);
+                    let error = match (offer, price_error) {
+                        (_, Some(error)) => Some(error),
+                        (None, _) => Some(market_error("Offer not found")),
+                        (Some(offer), None) => {
+                            offer.price = price;
+                            None
+                        }
+                    };
+                    let successful = error.is_none();
+                    json!({
+                        "EditOffer": o,
+                        "Successful": successful,
+                        "Error": error,
+                        "NewOfferID": if successful { offer_id } else { "" },
+                    })
+                })
+                .collect();
+            Json(json!({ "Result": result })).into_response()
+        }
+        (Method::DELETE, "/exchange/v1/offers") => {
+            let objects = body["objects"].as_array().cloned().unwrap_or_default();
+            let mut success = Vec::new();
+            let mut fail = Vec::new();
+            for o in objects {
+                let offer_id = o["offerId"].as_str().unwrap_or_default().to_string();
+                match state
+                    .offers
+                    .iter()
+                    .position(|m| m.offer_id.to_string() == offer_id)
+                {
+                    Some(index) => {
+                        let offer = state.offers.remove(index);
+                        state.inventory.push(offer.item);
+                        success.push(offer_id);
+                    }
+                    None => fail.push(offer_id),
+                }
+            }
+            Json(json!({
+                "result": [{ "created": [], "fail": fail, "locked": [], "success": success }],
+            }))
+            .into_response()
+        }
+        _ => error(StatusCode::NOT_FOUND, "Unknown endpoint"),
+    }
+}
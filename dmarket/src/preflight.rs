@@ -0,0 +1,54 @@
+//! Startup self-check, run with `dmarket preflight`.
+//!
+//! Verifies everything the bot needs before it trades: the database and its migrations, and for
+//! every enabled account its key pair, a working balance call and its row in `dmarket_account`.
+use crate::client::BASE_URL;
+use crate::{Client, Database, Signer};
+use common::preflight::{self, Report};
+use common::secrets;
+
+/// Checks the configured secrets, database and the live API.
+pub async fn run() -> Report {
+    let mut report = Report::default();
+    report.record("secrets", secrets::init().map(|()| "loaded"));
+    match Database::new().await {
+        Ok(db) => {
+            report.pass("database", "connected");
+            check(&mut report, &db, BASE_URL).await;
+        }
+        Err(e) => report.fail("database", e),
+    }
+    report
+}
+
+/// Checks `db` and authenticates each enabled account against the API at `base_url`.
+pub async fn check(report: &mut Report, db: &Database, base_url: &str) {
+    report.record("migrations", preflight::check_migrations(db.pool()).await);
+
+    let accounts = match db.get_trading_accounts().await {
+        Ok(accounts) if accounts.is_empty() => {
+            return report.fail("accounts", "no enabled DMarket accounts");
+        }
+        Ok(accounts) => accounts,
+        Err(e) => return report.fail("accounts", e),
+    };
+    report.pass("accounts", format!("{} enabled", accounts.len()));
+
+    for account in accounts {
+        let name = &account.name;
+        let balance = async {
+            let signer = Signer::from_credentials(&account.credentials)?;
+            Client::with_base_url(base_url, signer)?.get_balance().await
+        };
+        report.record(
+            format!("{name}: authentication"),
+            balance
+                .await
+                .map(|balance| format!("balance {} cents", balance.usd)),
+        );
+        report.record(
+            format!("{name}: dmarket_account row"),
+            preflight::check_balance_row(db.pool(), "dmarket_account", account.id).await,
+        );
+    }
+}
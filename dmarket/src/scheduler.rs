@@ -0,0 +1,210 @@
+use crate::Trader;
+use anyhow::Result;
+use common::RetryPolicy;
+use log::{error, info, warn};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tokio_cron_scheduler::{Job, JobScheduler};
+
+const FLIP: &str = "0 * * * * *";
+const OFFERS: &str = "30 */5 * * * *";
+const TARGETS: &str = "0 */15 * * * *";
+const TITLES: &str = "0 0 */6 * * *";
+const SALES: &str = "0 0 3 * * *";
+
+/// Runs each trading task as an independent job on its own schedule.
+///
+/// A failing job is retried with backoff and never affects the others. A job that is still
+/// running when it is due again is skipped instead of running twice.
+pub struct Scheduler {
+    trader: Trader,
+    scheduler: JobScheduler,
+    retry_policy: RetryPolicy,
+}
+
+impl Scheduler {
+    pub async fn new(trader: Trader) -> Result<Self> {
+        let scheduler = JobScheduler::new().await?;
+        Ok(Scheduler {
+            trader,
+            scheduler,
+            retry_policy: RetryPolicy::default(),
+        })
+    }
+
+    /// Replaces the policy used to retry failed runs of a job.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    fn job<F, Fut>(&self, name: &str, task: F) -> JobRunner<Fut>
+    where
+        F: Fn(Trader) -> Fut + Send + Sync + 'static,
+    {
+        JobRunner {
+            name: name.to_string(),
+            trader: self.trader.clone(),
+            retry_policy: self.retry_policy,
+            running: Arc::new(Mutex::new(())),
+            task: Arc::new(task),
+        }
+    }
+
+    pub async fn schedule_task<F, Fut>(&self, name: &str, schedule: &str, task: F) -> Result<()>
+    where
+        F: Fn(Trader) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        let job = self.job(name, task);
+
+        self.scheduler
+            .add(Job::new_async(schedule, move |_uuid, _l| {
+                let job = job.clone();
+                Box::pin(async move { job.run().await })
+            })?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Schedules `task` to run once, right after the scheduler starts.
+    pub async fn run_once<F, Fut>(&self, name: &str, task: F) -> Result<()>
+    where
+        F: Fn(Trader) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<()>> + Send + 'static,
+    {
+        let job = self.job(name, task);
+
+        self.scheduler
+            .add(Job::new_one_shot_async(
+                Duration::ZERO,
+                move |_uuid, _l| {
+                    let job = job.clone();
+                    Box::pin(async move { job.run().await })
+                },
+            )?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Schedules the trading jobs, starting with a full sync.
+    pub async fn schedule_tasks(&self) -> Result<()> {
+        self.schedule_market_tasks().await?;
+        self.schedule_account_tasks().await
+    }
+
+    /// Schedules the jobs that collect market data, which all accounts share.
+    pub async fn schedule_market_tasks(&self) -> Result<()> {
+        self.run_once("initial sync", |trader| async move { trader.sync().await })
+            .await?;
+
+        self.schedule_task("titles", TITLES, |trader| async move {
+            trader.sync_titles().await?;
+            trader.sync_fees().await
+        })
+        .await?;
+
+        self.schedule_task("sales", SALES, |trader| async move {
+            trader.sync_all_sales().await?;
+            trader.sync_stats().await
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Schedules the trading jobs of the trader's account.
+    pub async fn schedule_account_tasks(&self) -> Result<()> {
+        let account = &self.trader.account.name;
+
+        self.run_once(&format!("{account}/balance"), |trader| async move {
+            trader.sync_balance().await
+        })
+        .await?;
+
+        self.schedule_task(&format!("{account}/flip"), FLIP, |trader| async move {
+            trader.flip().await
+        })
+        .await?;
+
+        self.schedule_task(&format!("{account}/offers"), OFFERS, |trader| async move {
+            trader.update_offers().await?;
+            trader.list_inventory().await?;
+            trader.sync_balance().await
+        })
+        .await?;
+
+        self.schedule_task(
+            &format!("{account}/targets"),
+            TARGETS,
+            |trader| async move {
+                trader.sync_targets().await?;
+                Ok(())
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Starts running the scheduled jobs in the background.
+    pub async fn start(self) -> Result<()> {
+        Ok(self.scheduler.start().await?)
+    }
+}
+
+type Task<Fut> = Arc<dyn Fn(Trader) -> Fut + Send + Sync>;
+
+/// One scheduled job together with the lock that keeps its runs from overlapping.
+struct JobRunner<Fut> {
+    name: String,
+    trader: Trader,
+    retry_policy: RetryPolicy,
+    running: Arc<Mutex<()>>,
+    task: Task<Fut>,
+}
+
+impl<Fut> Clone for JobRunner<Fut> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            trader: self.trader.clone(),
+            retry_policy: self.retry_policy,
+            running: self.running.clone(),
+            task: self.task.clone(),
+        }
+    }
+}
+
+impl<Fut: Future<Output = crate::Result<()>>> JobRunner<Fut> {
+    async fn run(&self) {
+        let Ok(_guard) = self.running.try_lock() else {
+            warn!("Skipping job {}: previous run still in progress", self.name);
+            return;
+        };
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            info!("Running job {} (attempt {attempt})", self.name);
+
+            let Err(e) = (self.task)(self.trader.clone()).await else {
+                info!("Job {} finished", self.name);
+                return;
+            };
+
+            if attempt >= self.retry_policy.max_attempts {
+                error!("Job {} failed after {attempt} attempts: {e}", self.name);
+                return;
+            }
+            let delay = self.retry_policy.backoff(attempt);
+            warn!("Job {} failed: {e}. Retrying in {delay:?}", self.name);
+            sleep(delay).await;
+        }
+    }
+}
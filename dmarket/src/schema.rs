@@ -1,7 +1,13 @@
 #![allow(dead_code)]
 use crate::client::CURRENCY_USD;
-use serde::{Deserialize, Serialize};
+use common::lenient;
+use common::money::{serde_cents, serde_dollars};
+use common::Money;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 #[derive(FromRow, Deserialize, Serialize, Debug)]
@@ -63,13 +69,14 @@ pub struct Extra {
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Price {
-    #[serde(rename = "USD")]
+    #[serde(rename = "USD", deserialize_with = "lenient::string")]
     pub usd: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ItemResponse {
     pub cursor: Option<String>,
+    #[serde(deserialize_with = "lenient::list")]
     pub objects: Vec<Item>,
     pub total: Total,
 }
@@ -85,14 +92,17 @@ pub struct Total {
 
 #[derive(Deserialize, Debug)]
 pub struct SaleResponse {
+    #[serde(deserialize_with = "lenient::list")]
     pub sales: Vec<Sale>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Sale {
-    pub price: String,
-    pub date: String,
+    #[serde(with = "serde_dollars")]
+    pub price: Money,
+    #[serde(deserialize_with = "unix_time")]
+    pub date: OffsetDateTime,
     pub tx_operation_type: String,
 
     // DB-only fields, skipped during deserialization
@@ -102,6 +112,12 @@ pub struct Sale {
     pub game_title: GameTitle,
 }
 
+/// Seconds since the epoch, possibly sent as a string.
+fn unix_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffsetDateTime, D::Error> {
+    let seconds = lenient::number(deserializer)?;
+    OffsetDateTime::from_unix_timestamp(seconds).map_err(D::Error::custom)
+}
+
 impl Sale {
     pub fn with_game_title(mut self, game_title: &GameTitle) -> Self {
         self.game_title = game_title.clone();
@@ -113,6 +129,7 @@ impl Sale {
 #[serde(rename_all = "camelCase")]
 pub struct ListFeeResponse {
     pub default_fee: ListDefaultFee,
+    #[serde(deserialize_with = "lenient::list")]
     pub reduced_fees: Vec<ListPersonalFee>,
 }
 
@@ -120,16 +137,22 @@ pub struct ListFeeResponse {
 #[serde(rename_all = "camelCase")]
 pub struct ListDefaultFee {
     pub fraction: String,
-    pub min_amount: i64,
+    /// Least fee charged on a sale
+    #[serde(with = "serde_cents")]
+    pub min_amount: Money,
 }
 
+/// A reduced fee on a title, which applies to sale prices between `min_price` and `max_price`
+/// until `expires_at`, in seconds since the epoch.
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ListPersonalFee {
     pub expires_at: i64,
     pub fraction: String,
-    pub max_price: i64,
-    pub min_price: i64,
+    #[serde(with = "serde_cents")]
+    pub max_price: Money,
+    #[serde(with = "serde_cents")]
+    pub min_price: Money,
     pub title: String,
 }
 
@@ -139,7 +162,7 @@ pub struct GameTitle {
     pub title: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Balance {
     pub usd: String,
@@ -151,6 +174,7 @@ pub struct Balance {
 pub struct BestPricesResponse {
     pub error: Option<String>,
     pub total: String,
+    #[serde(deserialize_with = "lenient::list")]
     pub aggregated_titles: Vec<BestPrices>,
 }
 
@@ -165,6 +189,7 @@ pub struct BestPrices {
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct BestPrice {
+    #[serde(deserialize_with = "lenient::string")]
     pub best_price: String,
     pub count: i32,
 }
@@ -180,9 +205,42 @@ pub struct BuyOffer {
 #[serde(rename_all = "camelCase")]
 pub struct BuyOffersResponse {
     pub order_id: String,
-    //[ TxPending, TxSuccess, TxFailed ]
-    pub status: String,
+    pub status: TxStatus,
     pub tx_id: String,
+    /// State of each offer in the order, which may differ when only some of them were bought
+    #[serde(default)]
+    pub dm_offers_status: HashMap<Uuid, OfferTxStatus>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OfferTxStatus {
+    pub status: TxStatus,
+}
+
+impl BuyOffersResponse {
+    /// The outcome of the purchase of `offer_id`, if the response settles it. Without a state
+    /// for the offer only a successful order tells, since a failed one may have bought some of
+    /// its offers.
+    pub fn outcome(&self, offer_id: Uuid) -> Option<PurchaseStatus> {
+        let status = match self.dm_offers_status.get(&offer_id) {
+            Some(offer) => offer.status,
+            None if self.status == TxStatus::TxSuccess => TxStatus::TxSuccess,
+            None => return None,
+        };
+        match status {
+            TxStatus::TxSuccess => Some(PurchaseStatus::Confirmed),
+            TxStatus::TxFailed => Some(PurchaseStatus::Failed),
+            TxStatus::TxPending => None,
+        }
+    }
+}
+
+/// State of a buy order or of one of its offers.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    TxPending,
+    TxSuccess,
+    TxFailed,
 }
 
 #[derive(Serialize, Debug)]
@@ -214,7 +272,9 @@ pub struct ResponseCreateTarget {
 pub struct GetTargetsResponse {
     #[serde(rename = "UpdatedAt")]
     pub updated_at: String,
-    pub offers: Vec<Target>, // Add this field
+    #[serde(deserialize_with = "lenient::list")]
+    pub offers: Vec<Target>,
+    #[serde(deserialize_with = "lenient::list")]
     pub orders: Vec<Target>,
 }
 
@@ -222,6 +282,7 @@ pub struct GetTargetsResponse {
 #[serde(rename_all = "camelCase")]
 pub struct Target {
     pub amount: String,
+    #[serde(deserialize_with = "lenient::string")]
     pub price: String,
     pub liquidity: String,
     pub attributes: Vec<TargetAttribute>,
@@ -281,8 +342,9 @@ pub struct InnerOffer {
 }
 
 #[derive(Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", bound(deserialize = "T: DeserializeOwned"))]
 pub struct PaginatedResponse<T> {
+    #[serde(deserialize_with = "lenient::list")]
     pub items: Vec<T>,
     pub total: String,
     pub cursor: String,
@@ -292,7 +354,8 @@ pub struct PaginatedResponse<T> {
 #[serde(rename_all = "PascalCase")]
 pub struct MarketMoney {
     pub currency: String,
-    pub amount: f64,
+    #[serde(with = "serde_dollars")]
+    pub amount: Money,
 }
 
 #[derive(Serialize, Debug, Deserialize, Default)]
@@ -326,6 +389,62 @@ pub struct DeleteTargetResponse {
     pub error: Option<MarketError>,
 }
 
+/// The account configured through `DMARKET_API_KEY` before multiple accounts were supported
+pub const DEFAULT_ACCOUNT_ID: i32 = 2;
+pub(crate) const DEFAULT_OWNER_ID: &str = "aa749fbf-e726-46db-9419-5a2f384a896e";
+
+/// A DMarket account we trade with.
+#[derive(Clone, Debug)]
+pub struct TradingAccount {
+    pub id: i32,
+    pub name: String,
+    /// Prefix of the `_API_KEY` and `_SECRET_KEY` secrets of the account
+    pub credentials: String,
+    /// The account's user ID, which marks its offers on the market
+    pub owner_id: Option<Uuid>,
+    /// Most we pay for a single item
+    pub max_purchase_price: Option<Money>,
+    /// Most we spend on purchases within a day
+    pub daily_spend_limit: Option<Money>,
+}
+
+impl Default for TradingAccount {
+    fn default() -> Self {
+        Self {
+            id: DEFAULT_ACCOUNT_ID,
+            name: "default".to_string(),
+            credentials: "DMARKET".to_string(),
+            owner_id: Uuid::parse_str(DEFAULT_OWNER_ID).ok(),
+            max_purchase_price: None,
+            daily_spend_limit: None,
+        }
+    }
+}
+
+/// What we know about the outcome of a purchase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "purchase_status", rename_all = "lowercase")]
+pub enum PurchaseStatus {
+    /// The order was sent but its outcome is unknown
+    Pending,
+    Confirmed,
+    Failed,
+}
+
+/// A purchase of a single offer. The price is in cents.
+#[derive(Clone, Debug)]
+pub struct Purchase {
+    pub offer_id: Uuid,
+    pub account_id: i32,
+    pub item_id: Uuid,
+    pub game_id: String,
+    pub title: String,
+    pub price: Money,
+    pub status: PurchaseStatus,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Serialize, Debug)]
 pub struct Stats {
     pub game_id: String,
     pub title: String,
@@ -333,6 +452,8 @@ pub struct Stats {
     pub sale_count: Option<i32>,
     pub monthly_sales: Option<i32>,
     pub price_slope: Option<f64>,
+    /// Standard deviation of the log sale prices
+    pub price_volatility: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -416,7 +537,7 @@ pub struct CreatedOffer {
 }
 
 impl MarketMoney {
-    pub fn new(amount: f64) -> Self {
+    pub fn new(amount: Money) -> Self {
         Self {
             amount,
             currency: CURRENCY_USD.to_string(),
@@ -425,7 +546,7 @@ impl MarketMoney {
 }
 
 impl CreateOffer {
-    pub fn new(item_id: Uuid, price: f64) -> Self {
+    pub fn new(item_id: Uuid, price: Money) -> Self {
         Self {
             asset_id: item_id,
             price: MarketMoney::new(price),
@@ -434,7 +555,7 @@ impl CreateOffer {
 }
 
 impl CreateTarget {
-    pub fn new(title: String, price: f64) -> Self {
+    pub fn new(title: String, price: Money) -> Self {
         Self {
             title,
             amount: 1,
@@ -453,6 +574,19 @@ impl From<&Item> for GameTitle {
     }
 }
 
+impl From<&Offer> for DeleteOffer {
+    fn from(offer: &Offer) -> Self {
+        Self {
+            item_id: offer.asset_id.clone(),
+            offer_id: offer.offer.offer_id.clone(),
+            price: OfferMoney {
+                amount: offer.offer.price.amount.dollars().to_string(),
+                currency: offer.offer.price.currency.clone(),
+            },
+        }
+    }
+}
+
 impl From<&Offer> for GameTitle {
     fn from(item: &Offer) -> Self {
         Self {
@@ -0,0 +1,89 @@
+use crate::error::Error;
+use crate::Result;
+use common::secrets;
+use ed25519_dalek::{Signer as _, SigningKey, KEYPAIR_LENGTH, SECRET_KEY_LENGTH};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::Method;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SIGNATURE_PREFIX: &str = "dmar ed25519 ";
+
+/// Signs requests with an Ed25519 key pair as described in DMarket's API documentation.
+///
+/// Every request carries the public key, a timestamp and a signature over
+/// `method + path + body + timestamp`.
+pub struct Signer {
+    api_key: String,
+    signing_key: SigningKey,
+}
+
+impl Signer {
+    /// Builds a signer from hex encoded keys.
+    ///
+    /// The secret key may be either the 32 byte seed or the 64 byte seed and public key pair
+    /// DMarket hands out. Either way, the public key derived from it must match `public_key`.
+    pub fn new(public_key: &str, secret_key: &str) -> Result<Self> {
+        let secret = hex::decode(secret_key.trim())?;
+        let public = hex::decode(public_key.trim())?;
+
+        let signing_key = match secret.len() {
+            SECRET_KEY_LENGTH => SigningKey::from_bytes(&to_array(&secret)?),
+            KEYPAIR_LENGTH => SigningKey::from_keypair_bytes(&to_array(&secret)?)
+                .map_err(|e| Error::SigningKey(e.to_string()))?,
+            _ => return Err(Error::InvalidKeyLength),
+        };
+
+        let derived = signing_key.verifying_key().to_bytes();
+        if public != derived {
+            return Err(Error::PublicKeyMismatch);
+        }
+
+        Ok(Self {
+            api_key: hex::encode(derived),
+            signing_key,
+        })
+    }
+
+    /// Builds a signer from `DMARKET_API_KEY` and `DMARKET_SECRET_KEY`.
+    pub fn from_env() -> Result<Self> {
+        Self::from_credentials("DMARKET")
+    }
+
+    /// Builds a signer from the `{prefix}_API_KEY` and `{prefix}_SECRET_KEY` secrets.
+    pub fn from_credentials(prefix: &str) -> Result<Self> {
+        Self::new(
+            secrets::get(&format!("{prefix}_API_KEY"))?.expose(),
+            secrets::get(&format!("{prefix}_SECRET_KEY"))?.expose(),
+        )
+    }
+
+    /// Returns the authentication headers for a request.
+    ///
+    /// `path` must include the query string exactly as sent and `body` must be the exact
+    /// serialized payload, or an empty string for requests without one.
+    pub fn headers(&self, method: &Method, path: &str, body: &str) -> Result<HeaderMap> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)?
+            .as_secs()
+            .to_string();
+        let signature = self.sign(method, path, body, &timestamp);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Api-Key", HeaderValue::from_str(&self.api_key)?);
+        headers.insert(
+            "X-Request-Sign",
+            HeaderValue::from_str(&format!("{SIGNATURE_PREFIX}{signature}"))?,
+        );
+        headers.insert("X-Sign-Date", HeaderValue::from_str(&timestamp)?);
+        Ok(headers)
+    }
+
+    fn sign(&self, method: &Method, path: &str, body: &str, timestamp: &str) -> String {
+        let message = format!("{method}{path}{body}{timestamp}");
+        hex::encode(self.signing_key.sign(message.as_bytes()).to_bytes())
+    }
+}
+
+fn to_array<const N: usize>(bytes: &[u8]) -> Result<[u8; N]> {
+    bytes.try_into().map_err(|_| Error::InvalidKeyLength)
+}
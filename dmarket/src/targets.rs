@@ -0,0 +1,122 @@
+//! Incremental target management.
+//!
+//! Targets keep their place in the order book only as long as they exist, so instead of
+//! replacing all of them the desired targets are compared with the ones already placed and only
+//! the differences are sent. Prices follow the order book: a target goes just above the best
+//! competing bid as long as that still leaves our margin.
+use crate::schema::{CreateTarget, DeleteTarget, GameTitle, Item, Target};
+use common::Money;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Most targets we keep on a single title at once
+pub const MAX_TARGETS_PER_TITLE: usize = 1;
+/// Step by which we outbid the best competing target
+const BID_INCREMENT: Money = Money::from_cents(1);
+
+/// Changes needed to go from the placed targets to the desired ones.
+#[derive(Debug, Default, Serialize)]
+pub struct TargetPlan {
+    /// New and repriced targets by game ID
+    pub create: HashMap<String, Vec<CreateTarget>>,
+    /// Targets no longer wanted, repriced or duplicated
+    pub delete: Vec<DeleteTarget>,
+    /// Targets left untouched because their price is unchanged
+    pub unchanged: usize,
+}
+
+fn placed_price(target: &Item) -> Option<Money> {
+    target
+        .price
+        .as_ref()
+        .and_then(|p| Money::parse_cents(&p.usd).ok())
+}
+
+/// Prices of our placed targets by title.
+pub fn placed_prices(placed: &[Item]) -> HashMap<GameTitle, Vec<Money>> {
+    let mut prices: HashMap<_, Vec<_>> = HashMap::new();
+    for target in placed {
+        if let Some(price) = placed_price(target) {
+            prices
+                .entry(GameTitle::from(target))
+                .or_default()
+                .push(price);
+        }
+    }
+    prices
+}
+
+/// Chooses the price of the target on a title.
+///
+/// `orders` is the order book, which includes our own targets at `ours`. We bid one cent above
+/// the best competitor and never exceed `max_price`, so a target already on top is lowered when
+/// competitors drop their bids. Without competition we bid `max_price`. Returns `None` if the
+/// best competitor already bids more than we can afford to.
+pub fn competitive_price(max_price: Money, orders: &[Target], ours: &[Money]) -> Option<Money> {
+    let mut ours = ours.to_vec();
+    let mut best_competitor: Option<Money> = None;
+
+    for order in orders {
+        let Ok(price) = Money::parse_dollars(&order.price) else {
+            continue;
+        };
+        let mut amount = order.amount.parse::<u64>().unwrap_or(1);
+        while amount > 0 {
+            match ours.iter().position(|p| *p == price) {
+                Some(index) => {
+                    ours.swap_remove(index);
+                    amount -= 1;
+                }
+                None => break,
+            }
+        }
+        if amount > 0 {
+            best_competitor = Some(best_competitor.map_or(price, |best| best.max(price)));
+        }
+    }
+
+    let Some(competitor) = best_competitor else {
+        return Some(max_price);
+    };
+
+    let price = competitor + BID_INCREMENT;
+    (price <= max_price).then_some(price)
+}
+
+impl TargetPlan {
+    /// Compares `desired` prices with the `placed` targets.
+    ///
+    /// DMarket has no way to edit a target, so a target whose price changed is deleted and
+    /// created again.
+    pub fn new(desired: &HashMap<GameTitle, Money>, placed: &[Item]) -> Self {
+        let mut plan = Self::default();
+        let mut kept: HashMap<GameTitle, usize> = HashMap::new();
+
+        for target in placed {
+            let game_title = GameTitle::from(target);
+            let price = placed_price(target);
+            let wanted = desired.get(&game_title).copied();
+
+            let count = kept.entry(game_title).or_default();
+            if wanted.is_some() && wanted == price && *count < MAX_TARGETS_PER_TITLE {
+                *count += 1;
+                plan.unchanged += 1;
+            } else {
+                plan.delete.push(DeleteTarget {
+                    target_id: target.item_id,
+                });
+            }
+        }
+
+        for (game_title, price) in desired {
+            if kept.get(game_title).copied().unwrap_or_default() == 0 {
+                plan.create
+                    .entry(game_title.game_id.clone())
+                    .or_default()
+                    .push(CreateTarget::new(game_title.title.clone(), *price));
+            }
+        }
+
+        plan
+    }
+}
@@ -1,40 +1,46 @@
-use crate::client::CSGO_GAME_ID;
+use crate::batch::{self, BatchSummary};
+use crate::client::CURRENCY_USD;
 use crate::error::Error::Response;
+use crate::fees;
+use crate::flip::{self, Budget, Candidate};
 use crate::schema::{
-    CreateOffer, CreateOffersResponse, CreateTarget, DeleteTarget, EditOffer, GameTitle,
-    MarketMoney,
+    BuyOffer, CreateOffer, CreateTarget, DeleteTarget, EditOffer, GameTitle, Item, MarketMoney,
+    OfferMoney, Purchase, PurchaseStatus, TradingAccount,
 };
+use crate::targets::{self, TargetPlan};
 use crate::Client;
 use crate::Database;
 use crate::Result;
 use crate::GAME_IDS;
-use common::map;
+use common::{map, FeeSchedule, Money, Rounding};
 use futures::{future::try_join_all, pin_mut, StreamExt, TryStreamExt};
-use std::collections::HashMap;
+use sqlx::types::time::OffsetDateTime;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 const MAX_TASKS: usize = 10;
-const CS_GO_DEFAULT_FEE: f64 = 0.1;
-const DEFAULT_FEE: f64 = 0.05;
 const MIN_PROFIT_MARGIN: f64 = 0.2;
 const MIN_SALE_COUNT: i32 = 500;
 const MIN_MONTHLY_SALES: i32 = 60;
 const MAX_BALANCE_FRACTION: f64 = 0.5;
-const MAX_CHUNK_SIZE: usize = 100;
-const OWNER_ID: &str = "aa749fbf-e726-46db-9419-5a2f384a896e";
 
-fn round_up_cents(price: f64) -> f64 {
-    (price * 100.0).ceil() / 100.0
-}
+/// Lowest price we list an item for
+const MIN_LIST_PRICE: Money = Money::from_cents(3);
+/// Price at which we check whether a title is worth placing a target on
+const MIN_TARGET_PRICE: Money = Money::from_cents(2);
+/// Purchases that cannot be resolved within this time are given up on
+const MAX_PENDING_AGE: time::Duration = time::Duration::days(1);
 
-fn round_down_cents(price: f64) -> f64 {
-    (price * 100.0).floor() / 100.0
+/// Rounds the mean sale price `mean`, in dollars, up to whole cents.
+fn mean_price(mean: f64) -> Money {
+    Money::from_dollars(mean, Rounding::Up).round_to_cents(Rounding::Up)
 }
 
 #[derive(Clone)]
 pub struct Trader {
     pub db: Database,
     pub client: Client,
+    pub account: TradingAccount,
 }
 
 impl Trader {
@@ -42,9 +48,24 @@ impl Trader {
         Ok(Self {
             db: Database::new().await?,
             client: Client::new()?,
+            account: TradingAccount::default(),
         })
     }
 
+    /// Creates a trader for each enabled account, sharing one database.
+    pub async fn for_all_accounts() -> Result<Vec<Self>> {
+        let db = Database::new().await?;
+        let mut traders = Vec::new();
+        for account in db.get_trading_accounts().await? {
+            traders.push(Self {
+                db: db.clone(),
+                client: Client::for_account(&account)?,
+                account,
+            });
+        }
+        Ok(traders)
+    }
+
     pub async fn sync_game_titles(&self, game_id: &str, title: Option<&str>) -> Result<()> {
         let market_items = self.client.get_market_items(game_id, title).await;
 
@@ -64,36 +85,54 @@ impl Trader {
 
     pub async fn sync(&self) -> Result<()> {
         log::info!("Syncing market data");
+        self.sync_titles().await?;
+        self.sync_all_sales().await?;
+        self.sync_stats().await?;
+        self.sync_fees().await?;
+        self.sync_balance().await?;
+
+        Ok(())
+    }
+
+    /// Discovers the titles currently on the market in every game.
+    pub async fn sync_titles(&self) -> Result<()> {
         try_join_all(GAME_IDS.iter().map(|&id| self.sync_game_titles(id, None))).await?;
-        futures::stream::iter(&self.db.get_distinct_titles().await?)
+        Ok(())
+    }
+
+    /// Fetches the sales made since the last sync for every known title.
+    pub async fn sync_all_sales(&self) -> Result<()> {
+        futures::stream::iter(self.db.get_distinct_titles().await?)
             .map(|gt| async move {
-                if let Err(e) = self.sync_sales(gt).await {
+                if let Err(e) = self.sync_sales(&gt).await {
                     log::error!("Error syncing sales: {e}");
                 }
             })
             .buffer_unordered(MAX_TASKS)
             .collect::<Vec<_>>()
             .await;
-
-        self.sync_stats().await?;
-        try_join_all(GAME_IDS.iter().map(|&id| self.sync_reduced_fees(id))).await?;
-        self.sync_balance().await?;
-
         Ok(())
     }
 
-    async fn sync_stats(&self) -> Result<()> {
+    pub async fn sync_stats(&self) -> Result<()> {
         let stats = self.db.calculate_price_statistics().await?;
         self.db.update_price_statistics(&stats).await
     }
 
+    pub async fn sync_fees(&self) -> Result<()> {
+        try_join_all(GAME_IDS.iter().map(|&id| self.sync_game_fees(id))).await?;
+        Ok(())
+    }
+
     async fn sync_sales(&self, gt: &GameTitle) -> Result<()> {
         let latest_date = self.db.get_latest_date(gt).await?;
         match self.client.get_sales(gt).await {
             Ok(sales) => {
                 let sales = sales
                     .into_iter()
-                    .filter(|sale| sale.date.parse::<u64>().unwrap_or_default() > latest_date)
+                    // Sales from the same second as the latest may not have been stored yet,
+                    // the ones that were are skipped by the database
+                    .filter(|sale| sale.date >= latest_date)
                     .map(|s| s.with_game_title(gt))
                     .collect();
 
@@ -112,24 +151,25 @@ impl Trader {
         Ok(())
     }
 
-    async fn sync_reduced_fees(&self, game_id: &str) -> Result<()> {
-        let fees = self.client.get_personal_fees(game_id).await?;
-        self.db.store_reduced_fees(game_id, fees).await?;
-        Ok(())
+    async fn sync_game_fees(&self, game_id: &str) -> Result<()> {
+        let fees = self.client.get_fees(game_id).await?;
+        self.db.store_fees(game_id, fees).await
     }
 
-    async fn sync_balance(&self) -> Result<()> {
+    pub async fn sync_balance(&self) -> Result<()> {
         let balance = self.client.get_balance().await?;
-        self.db.update_balance(balance.usd.parse()?).await?;
+        self.db
+            .update_balance(self.account.id, Money::parse_cents(&balance.usd)?)
+            .await?;
         Ok(())
     }
 
-    async fn get_potential_list_price(&self, game_title: &GameTitle) -> Result<Option<f64>> {
+    async fn get_potential_list_price(&self, game_title: &GameTitle) -> Result<Option<Money>> {
         let avg_price = self
             .db
             .get_price_statistics(game_title)
             .await?
-            .and_then(|stats| stats.mean_price.map(round_up_cents));
+            .and_then(|stats| stats.mean_price.map(mean_price));
 
         if let Some(avg_price) = avg_price {
             let market_items = self
@@ -139,47 +179,286 @@ impl Trader {
                 .try_concat()
                 .await?;
 
+            let owners = self.db.get_owner_ids().await?;
             let lowest_competitor = market_items
                 .into_iter()
-                .filter(|item| item.owner.to_string() != OWNER_ID && item.title == game_title.title)
+                .filter(|item| !owners.contains(&item.owner) && item.title == game_title.title)
                 .filter_map(|item| item.price)
-                .filter_map(|price| price.usd.parse().ok())
-                .reduce(f64::min);
-
-            let undercut_price = lowest_competitor
-                .map(|x| (x - 1.0) / 100.0)
-                .unwrap_or(f64::NEG_INFINITY);
+                .filter_map(|price| Money::parse_cents(&price.usd).ok())
+                .reduce(Money::min);
 
-            return Ok(Some(avg_price.max(undercut_price).max(0.03)));
+            let mut price = avg_price.max(MIN_LIST_PRICE);
+            if let Some(competitor) = lowest_competitor {
+                price = price.max(competitor - Money::from_cents(1));
+            }
+            return Ok(Some(price));
         }
 
         Ok(None)
     }
 
-    pub async fn buy_game_title(&self, game_title: GameTitle, buy_price: String) -> Result<()> {
-        if let Some(item) = self.client.get_best_offer(&game_title).await? {
-            log::info!("Buying {} for {}", item.title, buy_price);
-            let offer_id = item.extra.offer_id.unwrap();
-            let response = self.client.buy_offer(offer_id, buy_price).await?;
-            log::info!("{:?}", response);
-            self.sync_balance().await?;
+    /// The offer [`Self::buy_game_title`] would buy, if any, given the risk limits of the
+    /// account.
+    pub async fn plan_purchase(
+        &self,
+        game_title: GameTitle,
+        price: Money,
+    ) -> Result<Option<Candidate>> {
+        let owners = self.db.get_owner_ids().await?;
+        let Some(candidate) = self.find_offer(game_title, price, &owners).await? else {
+            return Ok(None);
+        };
+        if let Some(reason) = self.limit_violation(candidate.price).await? {
+            log::warn!("Not buying {}: {reason}", candidate.game_title.title);
+            return Ok(None);
         }
+        Ok(Some(candidate))
+    }
 
-        Ok(())
+    /// Buys the cheapest offer of `game_title` that is not ours for at most `price`. Returns
+    /// the offer if the purchase went through.
+    pub async fn buy_game_title(
+        &self,
+        game_title: GameTitle,
+        price: Money,
+    ) -> Result<Option<Candidate>> {
+        let Some(candidate) = self.plan_purchase(game_title, price).await? else {
+            return Ok(None);
+        };
+        let result = self.buy_candidates(std::slice::from_ref(&candidate)).await;
+        self.sync_balance().await?;
+        Ok((result? == 1).then_some(candidate))
+    }
+
+    /// The cheapest offer of `game_title` that is not ours, if it costs at most `price` and no
+    /// earlier purchase of the title is unresolved. The expected profit and score are left at zero.
+    async fn find_offer(
+        &self,
+        game_title: GameTitle,
+        price: Money,
+        owners: &HashSet<Uuid>,
+    ) -> Result<Option<Candidate>> {
+        if self.db.has_pending_purchase(&game_title).await? {
+            log::warn!(
+                "Not buying {}: an earlier purchase is unresolved",
+                game_title.title
+            );
+            return Ok(None);
+        }
+
+        let Some(item) = self.client.get_best_offer(&game_title, owners).await? else {
+            return Ok(None);
+        };
+        let offer_price = item
+            .price
+            .as_ref()
+            .and_then(|p| Money::parse_cents(&p.usd).ok());
+        let (Some(offer_price), Some(offer_id)) = (offer_price, item.extra.offer_id) else {
+            return Ok(None);
+        };
+        if offer_price > price {
+            log::info!(
+                "Not buying {}: best offer {offer_price} is above the limit {price}",
+                game_title.title
+            );
+            return Ok(None);
+        }
+        Ok(Some(Candidate {
+            game_title,
+            offer_id,
+            item_id: item.item_id,
+            price: offer_price,
+            list_price: offer_price,
+            expected_profit: Money::ZERO,
+            score: 0.0,
+        }))
+    }
+
+    /// Buys `candidates` in a single order and records the outcome of each purchase.
+    ///
+    /// The order may buy only some of its offers. Purchases the response does not settle are
+    /// reconciled with the inventory and the market. Returns the number of offers bought.
+    async fn buy_candidates(&self, candidates: &[Candidate]) -> Result<usize> {
+        let mut purchases = Vec::new();
+        for candidate in candidates {
+            let purchase = Purchase {
+                offer_id: candidate.offer_id,
+                account_id: self.account.id,
+                item_id: candidate.item_id,
+                game_id: candidate.game_title.game_id.clone(),
+                title: candidate.game_title.title.clone(),
+                price: candidate.price,
+                status: PurchaseStatus::Pending,
+                created_at: OffsetDateTime::now_utc(),
+            };
+            if self.db.start_purchase(&purchase).await? {
+                log::info!("Buying {} for {}", purchase.title, purchase.price);
+                purchases.push(purchase);
+            } else {
+                log::warn!(
+                    "Offer {} was already bought or its purchase is unresolved",
+                    purchase.offer_id
+                );
+            }
+        }
+        if purchases.is_empty() {
+            return Ok(0);
+        }
+
+        let offers: Vec<_> = map(&purchases, |purchase| BuyOffer {
+            offer_id: purchase.offer_id,
+            price: OfferMoney {
+                amount: purchase.price.cents(Rounding::Up).to_string(),
+                currency: CURRENCY_USD.to_string(),
+            },
+        });
+        let result = self.client.buy_offers(&offers).await;
+
+        let mut bought = 0;
+        let mut failed = 0;
+        let mut spent = Money::ZERO;
+        for purchase in &purchases {
+            let outcome = match &result {
+                Ok(response) => response.outcome(purchase.offer_id),
+                Err(Response(status, _)) if status.is_client_error() => {
+                    Some(PurchaseStatus::Failed)
+                }
+                Err(_) => None,
+            };
+            let status = match outcome {
+                Some(status) => {
+                    self.db
+                        .set_purchase_status(purchase.offer_id, status)
+                        .await?;
+                    status
+                }
+                None => self.reconcile_purchase(purchase).await?,
+            };
+            match status {
+                PurchaseStatus::Confirmed => {
+                    bought += 1;
+                    spent = spent + purchase.price;
+                }
+                PurchaseStatus::Failed => failed += 1,
+                PurchaseStatus::Pending => (),
+            }
+        }
+        log::info!(
+            "Order of {} offers bought {bought} for {spent}, {failed} failed",
+            purchases.len()
+        );
+        result?;
+        Ok(bought)
+    }
+
+    /// Explains why buying for `price` would break a risk limit of the account, if it would.
+    async fn limit_violation(&self, price: Money) -> Result<Option<String>> {
+        let account = &self.account;
+        if account.max_purchase_price.is_some_and(|max| price > max) {
+            return Ok(Some(format!(
+                "{price} exceeds the max purchase price of account {}",
+                account.name
+            )));
+        }
+        if let Some(limit) = account.daily_spend_limit {
+            let spent = self.db.get_daily_spend(account.id).await?;
+            if spent + price > limit {
+                return Ok(Some(format!(
+                    "{price} would exceed the daily spend limit of account {}",
+                    account.name
+                )));
+            }
+        }
+        Ok(None)
     }
 
-    async fn get_fee(&self, game_title: &GameTitle) -> Result<f64> {
-        if let Some(reduced_fee) = self.db.get_reduced_fee(game_title).await? {
-            Ok(reduced_fee.fraction.parse()?)
-        } else if game_title.game_id == CSGO_GAME_ID {
-            Ok(CS_GO_DEFAULT_FEE)
+    /// Determines whether a purchase with an unknown outcome went through and records it.
+    ///
+    /// The purchase is confirmed once the item shows up in our inventory or our offers, and
+    /// failed if the offer is still on the market or the purchase is older than
+    /// [`MAX_PENDING_AGE`]. Otherwise the item may still be in transfer and the purchase stays
+    /// pending.
+    async fn reconcile_purchase(&self, purchase: &Purchase) -> Result<PurchaseStatus> {
+        let item_id = purchase.item_id.to_string();
+        let owned = self
+            .client
+            .get_inventory()
+            .await?
+            .iter()
+            .any(|item| item.item_id == purchase.item_id)
+            || self
+                .client
+                .get_offers()
+                .await?
+                .iter()
+                .any(|offer| offer.asset_id == item_id);
+
+        let status = if owned {
+            PurchaseStatus::Confirmed
         } else {
-            Ok(DEFAULT_FEE)
+            let listed = self
+                .client
+                .get_market_items(&purchase.game_id, Some(&purchase.title))
+                .await
+                .try_concat()
+                .await?
+                .iter()
+                .any(|item| item.extra.offer_id == Some(purchase.offer_id));
+            if listed {
+                PurchaseStatus::Failed
+            } else if OffsetDateTime::now_utc() - purchase.created_at > MAX_PENDING_AGE {
+                log::warn!(
+                    "Giving up on purchase of offer {}, which is still unresolved",
+                    purchase.offer_id
+                );
+                PurchaseStatus::Failed
+            } else {
+                PurchaseStatus::Pending
+            }
+        };
+
+        log::info!(
+            "Purchase of offer {} reconciled as {status:?}",
+            purchase.offer_id
+        );
+        self.db
+            .set_purchase_status(purchase.offer_id, status)
+            .await?;
+        Ok(status)
+    }
+
+    /// Resolves purchases whose outcome could not be determined earlier. A purchase that cannot
+    /// be looked up stays pending until the next attempt.
+    pub async fn reconcile_pending_purchases(&self) -> Result<()> {
+        let pending = self.db.get_pending_purchases(self.account.id).await?;
+        for purchase in &pending {
+            if let Err(e) = self.reconcile_purchase(purchase).await {
+                log::error!(
+                    "Error reconciling purchase of offer {}: {e}",
+                    purchase.offer_id
+                );
+            }
+        }
+        if !pending.is_empty() {
+            self.sync_balance().await?;
         }
+        Ok(())
+    }
+
+    /// The fees we pay when selling an item of `game_title`.
+    pub async fn fee_schedule(&self, game_title: &GameTitle) -> Result<FeeSchedule> {
+        let default = self.db.get_default_fee(&game_title.game_id).await?;
+        let reduced = self.db.get_reduced_fee(game_title).await?;
+        fees::schedule(&game_title.game_id, default.as_ref(), reduced.as_ref())
     }
 
-    pub async fn get_list_price(&self, game_title: &GameTitle, price: f64) -> Result<Option<f64>> {
-        if 100.0 * price > MAX_BALANCE_FRACTION * self.db.get_balance().await? as f64 {
+    pub async fn get_list_price(
+        &self,
+        game_title: &GameTitle,
+        price: Money,
+    ) -> Result<Option<Money>> {
+        let balance = self.db.get_balance(self.account.id).await?;
+        if price > balance.scale(MAX_BALANCE_FRACTION, Rounding::Down) {
             return Ok(None);
         }
         if let Some(stats) = self.db.get_price_statistics(game_title).await? {
@@ -195,10 +474,15 @@ impl Trader {
                 {
                     return Ok(None);
                 }
-                let fee = self.get_fee(game_title).await?;
-                let mean = round_up_cents(mean);
-                let fee_price = round_up_cents(mean * fee);
-                if (1.0 + MIN_PROFIT_MARGIN) * price <= mean - fee_price {
+                if let Some(reference) = self.db.get_steam_reference(game_title).await? {
+                    if let Some(reason) = reference.objection(price, OffsetDateTime::now_utc()) {
+                        log::info!("Not buying {}: {reason}", game_title.title);
+                        return Ok(None);
+                    }
+                }
+                let fees = self.fee_schedule(game_title).await?;
+                let mean = mean_price(mean);
+                if fees.is_profitable(price, mean, MIN_PROFIT_MARGIN) {
                     return Ok(Some(mean));
                 }
             }
@@ -206,67 +490,142 @@ impl Trader {
         Ok(None)
     }
 
-    pub async fn delete_targets(&self) -> Result<()> {
+    pub async fn delete_targets(&self) -> Result<BatchSummary> {
         log::info!("Deleting targets");
         let targets = self.client.get_user_targets().await?;
         let delete_targets: Vec<_> = map(targets, |t| DeleteTarget {
             target_id: t.item_id,
         });
 
-        for chunk in delete_targets.chunks(MAX_CHUNK_SIZE) {
-            if let Err(e) = self.client.delete_targets(chunk).await {
-                log::error!("Error deleting targets: {:?}", e);
+        Ok(batch::process(
+            "Deleting targets",
+            delete_targets,
+            |chunk| async move { self.client.delete_targets(&chunk).await },
+            |_| true,
+        )
+        .await)
+    }
+
+    /// Target prices for every title worth placing a target on, given our `placed` targets.
+    async fn desired_targets(&self, placed: &[Item]) -> Result<HashMap<GameTitle, Money>> {
+        let mut targets = HashMap::new();
+        let placed = targets::placed_prices(placed);
+
+        for game_title in self.db.get_distinct_titles().await? {
+            if let Some(list_price) = self.get_list_price(&game_title, MIN_TARGET_PRICE).await? {
+                let fees = self.fee_schedule(&game_title).await?;
+                let max_price = fees.max_purchase_price(list_price, MIN_PROFIT_MARGIN);
+
+                let orders = self.client.get_targets(&game_title).await?;
+                let ours = placed.get(&game_title).map_or(&[][..], Vec::as_slice);
+                match targets::competitive_price(max_price, &orders, ours) {
+                    Some(price) => {
+                        targets.insert(game_title, price);
+                    }
+                    None => log::debug!("Outbid on {} above {max_price}", game_title.title),
+                }
             }
         }
 
-        Ok(())
+        Ok(targets)
     }
 
-    pub async fn create_targets(&self) -> Result<()> {
-        log::info!("Creating targets");
-        let mut targets_map: HashMap<String, Vec<_>> = HashMap::new();
-
-        for game_title in &self.db.get_distinct_titles().await? {
-            if let Some(list_price) = self.get_list_price(game_title, 0.02).await? {
-                let fee = self.get_fee(game_title).await?;
-                let fee_price = round_up_cents(list_price * fee);
-                let target_price =
-                    round_down_cents((list_price - fee_price) / (1.0 + MIN_PROFIT_MARGIN));
-
-                targets_map
-                    .entry(game_title.game_id.clone())
-                    .or_default()
-                    .push(CreateTarget::new(game_title.title.clone(), target_price));
-            }
+    async fn send_target_creations(
+        &self,
+        targets: HashMap<String, Vec<CreateTarget>>,
+    ) -> BatchSummary {
+        let mut summary = BatchSummary::default();
+        for (game_id, targets) in targets {
+            let operation = format!("Creating targets for {game_id}");
+            let game_id = &game_id;
+            summary.merge(
+                batch::process(
+                    &operation,
+                    targets,
+                    |chunk| async move { self.client.create_targets(game_id, &chunk).await },
+                    |_| true,
+                )
+                .await,
+            );
         }
+        summary
+    }
 
-        for (game_id, targets) in targets_map {
-            for chunk in targets.chunks(MAX_CHUNK_SIZE) {
-                if let Err(e) = self.client.create_targets(&game_id, chunk).await {
-                    log::error!("Error creating targets for {}: {:?}", game_id, e);
-                    if matches!(e, Response(_, _)) {
-                        break;
-                    }
-                }
-            }
-        }
+    pub async fn create_targets(&self) -> Result<BatchSummary> {
+        log::info!("Creating targets");
+        let plan = TargetPlan::new(&self.desired_targets(&[]).await?, &[]);
+        Ok(self.send_target_creations(plan.create).await)
+    }
 
-        Ok(())
+    /// Changes that would bring the placed targets in line with the desired ones.
+    pub async fn plan_targets(&self) -> Result<TargetPlan> {
+        let placed = self.client.get_user_targets().await?;
+        let desired = self.desired_targets(&placed).await?;
+        Ok(TargetPlan::new(&desired, &placed))
     }
 
-    pub async fn list_inventory(&self) -> Result<CreateOffersResponse> {
-        log::info!("Listing inventory");
+    /// Brings the placed targets in line with the desired ones, only touching targets that
+    /// are missing, unwanted or mispriced.
+    pub async fn sync_targets(&self) -> Result<BatchSummary> {
+        log::info!("Syncing targets");
+        Ok(self.apply_target_plan(self.plan_targets().await?).await)
+    }
+
+    pub async fn apply_target_plan(&self, plan: TargetPlan) -> BatchSummary {
+        log::info!(
+            "Targets: {} unchanged, {} to delete, {} to create",
+            plan.unchanged,
+            plan.delete.len(),
+            plan.create.values().map(Vec::len).sum::<usize>()
+        );
+
+        let mut summary = BatchSummary::default();
+        if !plan.delete.is_empty() {
+            summary.merge(
+                batch::process(
+                    "Deleting targets",
+                    plan.delete,
+                    |chunk| async move { self.client.delete_targets(&chunk).await },
+                    |_| true,
+                )
+                .await,
+            );
+        }
+        summary.merge(self.send_target_creations(plan.create).await);
+        summary
+    }
+
+    /// Offers that would list the items in our inventory that are worth selling.
+    pub async fn plan_listings(&self) -> Result<Vec<CreateOffer>> {
         let mut offers = vec![];
         for item in &self.client.get_inventory().await? {
             if let Some(price) = self.get_potential_list_price(&item.into()).await? {
                 offers.push(CreateOffer::new(item.item_id, price));
             }
         }
-        self.client.create_offers(&offers).await
+        Ok(offers)
     }
 
-    pub async fn update_offers(&self) -> Result<()> {
-        log::info!("Updating offers");
+    pub async fn list_inventory(&self) -> Result<BatchSummary> {
+        log::info!("Listing inventory");
+        self.create_offers(self.plan_listings().await?).await
+    }
+
+    /// Lists `offers`, only correcting a rejected price if the item still sells at a profit.
+    pub async fn create_offers(&self, offers: Vec<CreateOffer>) -> Result<BatchSummary> {
+        let item_ids: Vec<_> = offers.iter().map(|offer| offer.asset_id).collect();
+        let costs = self.purchase_costs(&item_ids).await?;
+        Ok(batch::process(
+            "Listing inventory",
+            offers,
+            |chunk| async move { self.client.create_offers(&chunk).await },
+            |offer| covers_cost(&costs, offer.asset_id, offer.price.amount),
+        )
+        .await)
+    }
+
+    /// Price changes that would bring our offers in line with the current list prices.
+    pub async fn plan_offer_updates(&self) -> Result<Vec<EditOffer>> {
         let mut offers = vec![];
         for offer in &self.client.get_offers().await? {
             if let Some(price) = self.get_potential_list_price(&offer.into()).await? {
@@ -279,34 +638,130 @@ impl Trader {
                 }
             }
         }
+        Ok(offers)
+    }
+
+    pub async fn update_offers(&self) -> Result<BatchSummary> {
+        log::info!("Updating offers");
+        self.edit_offers(self.plan_offer_updates().await?).await
+    }
+
+    /// Reprices `offers`, only correcting a rejected price if the item still sells at a profit.
+    pub async fn edit_offers(&self, offers: Vec<EditOffer>) -> Result<BatchSummary> {
+        let item_ids: Vec<_> = offers.iter().map(|offer| offer.asset_id).collect();
+        let costs = self.purchase_costs(&item_ids).await?;
+        Ok(batch::process(
+            "Updating offers",
+            offers,
+            |chunk| async move { self.client.edit_offers(&chunk).await },
+            |offer| covers_cost(&costs, offer.asset_id, offer.price.amount),
+        )
+        .await)
+    }
 
-        for chunk in offers.chunks(MAX_CHUNK_SIZE) {
-            if let Err(e) = self.client.edit_offers(chunk).await {
-                log::error!("Error editing offers: {e}");
+    /// What we paid for each of `item_ids` we bought, with the fees on selling it.
+    async fn purchase_costs(
+        &self,
+        item_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, (Money, FeeSchedule)>> {
+        let mut costs = HashMap::new();
+        for &item_id in item_ids {
+            if let Some(purchase) = self.db.get_item_purchase(item_id).await? {
+                let game_title = GameTitle {
+                    game_id: purchase.game_id,
+                    title: purchase.title,
+                };
+                let fees = self.fee_schedule(&game_title).await?;
+                costs.insert(item_id, (purchase.price, fees));
             }
         }
-
-        Ok(())
+        Ok(costs)
     }
 
     pub async fn flip(&self) -> Result<()> {
+        self.reconcile_pending_purchases().await?;
+        let candidates = self.flip_candidates().await?;
+        let budget = self.flip_budget().await?;
+        let chosen = flip::allocate(candidates, budget);
+        if chosen.is_empty() {
+            return Ok(());
+        }
+
+        log::info!("Buying {} offers", chosen.len());
+        let mut bought = 0;
+        for order in chosen.chunks(flip::MAX_OFFERS_PER_ORDER) {
+            match self.buy_candidates(order).await {
+                Ok(count) => bought += count,
+                Err(e) => log::error!("Error buying offers: {e}"),
+            }
+        }
+        log::info!("Bought {bought} of {} offers", chosen.len());
+        self.sync_balance().await
+    }
+
+    /// Every offer worth buying, with what we expect to earn on it.
+    async fn flip_candidates(&self) -> Result<Vec<Candidate>> {
+        let owners = self.db.get_owner_ids().await?;
+        let mut candidates = Vec::new();
         for prices in self.client.get_best_prices().await? {
-            if prices.offers.count > 0 {
-                if let Some(game_title) = self.db.get_game_title(prices.market_hash_name).await? {
-                    let price = prices.offers.best_price.parse::<f64>()?;
-                    let cents = (100.0 * price).round().to_string();
-                    match self.get_list_price(&game_title, price).await {
-                        Ok(Some(_)) => {
-                            if let Err(e) = self.buy_game_title(game_title, cents).await {
-                                log::error!("Error buying game title: {e}");
-                            }
-                        }
-                        Err(e) => log::error!("Error getting list price: {e}"),
-                        _ => (),
-                    }
+            if prices.offers.count == 0 {
+                continue;
+            }
+            let Some(game_title) = self.db.get_game_title(prices.market_hash_name).await? else {
+                continue;
+            };
+            let price = Money::parse_dollars(&prices.offers.best_price)?;
+            let list_price = match self.get_list_price(&game_title, price).await {
+                Ok(Some(list_price)) => list_price,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::error!("Error getting list price: {e}");
+                    continue;
                 }
+            };
+            let fees = self.fee_schedule(&game_title).await?;
+            let stats = self.db.get_price_statistics(&game_title).await?;
+            let monthly_sales = stats.as_ref().and_then(|s| s.monthly_sales).unwrap_or(0);
+            let volatility = stats.and_then(|s| s.price_volatility).unwrap_or(0.0);
+            match self.find_offer(game_title, price, &owners).await {
+                Ok(Some(candidate)) => candidates.push(Candidate {
+                    list_price,
+                    expected_profit: fees.proceeds(list_price) - candidate.price,
+                    score: flip::score(
+                        candidate.price,
+                        list_price,
+                        &fees,
+                        monthly_sales,
+                        volatility,
+                    ),
+                    ..candidate
+                }),
+                Ok(None) => (),
+                Err(e) => log::error!("Error finding offer: {e}"),
             }
         }
-        Ok(())
+        Ok(candidates)
+    }
+
+    /// What flipping may spend given the balance and the risk limits of the account.
+    async fn flip_budget(&self) -> Result<Budget> {
+        let account = &self.account;
+        let mut available = self.db.get_balance(account.id).await?;
+        if let Some(limit) = account.daily_spend_limit {
+            let spent = self.db.get_daily_spend(account.id).await?;
+            available = available.min(limit - spent);
+        }
+        Ok(Budget {
+            available,
+            max_price: account.max_purchase_price,
+        })
     }
 }
+
+/// Whether selling `item_id` for `price` earns back what we paid for it, given the `costs` of
+/// the items we bought. Items we didn't buy have nothing to earn back.
+fn covers_cost(costs: &HashMap<Uuid, (Money, FeeSchedule)>, item_id: Uuid, price: Money) -> bool {
+    costs
+        .get(&item_id)
+        .is_none_or(|(cost, fees)| fees.max_purchase_price(price, 0.0) >= *cost)
+}
@@ -0,0 +1,794 @@
+//! End-to-end flows against the in-process fake DMarket server.
+use anyhow::Result;
+use catalog::{Catalog, ReferencePrice};
+use common::preflight::Report;
+use common::{Money, RetryPolicy};
+use dmarket::client::CSGO_GAME_ID;
+use dmarket::mock::{trending_prices, MockBid, MockFee, MockSale, MockServer};
+use dmarket::preflight;
+use dmarket::scheduler::Scheduler;
+use dmarket::schema::{GameTitle, MarketError, PurchaseStatus, TradingAccount, DEFAULT_ACCOUNT_ID};
+use dmarket::{BatchFailure, Client, Database, RequestGroup, Signer, Trader};
+use reqwest::StatusCode;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+const TITLE: &str = "AK-47 | Redline (Field-Tested)";
+const OTHER_TITLE: &str = "AWP | Asiimov (Field-Tested)";
+const BALANCE: i64 = 100_000;
+
+fn game_title(title: &str) -> GameTitle {
+    GameTitle {
+        game_id: CSGO_GAME_ID.to_string(),
+        title: title.to_string(),
+    }
+}
+
+async fn setup(pool: PgPool) -> (MockServer, Trader) {
+    let mock = MockServer::start().await;
+    {
+        let mut state = mock.state();
+        state.balance = BALANCE;
+        for title in [TITLE, OTHER_TITLE] {
+            state.add_sales(CSGO_GAME_ID, title, trending_prices(10.0));
+        }
+    }
+    let trader = Trader {
+        db: Database::from_pool(pool),
+        client: mock.client(),
+        account: TradingAccount::default(),
+    };
+    (mock, trader)
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn sync_paginates_and_retries_rate_limited_requests(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    {
+        let mut state = mock.state();
+        for i in 0..150 {
+            state.list(CSGO_GAME_ID, &format!("Sticker {i}"), 100);
+        }
+        state.list(CSGO_GAME_ID, TITLE, 1200);
+        state.list(CSGO_GAME_ID, OTHER_TITLE, 1200);
+        state.fail_next(
+            "/trade-aggregator/v1/last-sales",
+            StatusCode::TOO_MANY_REQUESTS,
+        );
+    }
+
+    trader.sync().await?;
+
+    assert_eq!(
+        mock.state().request_count("/exchange/v1/market/items"),
+        2 + 3 // one page for each game without items, two for CS2
+    );
+    assert_eq!(trader.db.get_distinct_titles().await?.len(), 152);
+    let stats = trader
+        .db
+        .get_price_statistics(&game_title(TITLE))
+        .await?
+        .unwrap();
+    assert_eq!(stats.sale_count, Some(500));
+    assert_eq!(stats.monthly_sales, Some(500));
+    assert_eq!(
+        trader.db.get_balance(DEFAULT_ACCOUNT_ID).await?,
+        Money::from_cents(BALANCE)
+    );
+    assert_eq!(
+        trader.client.rate_limit_stats()[&RequestGroup::LastSales].throttled,
+        1
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn overlapping_sale_fetches_are_stored_once(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    mock.state().list(CSGO_GAME_ID, TITLE, 1200);
+    trader.sync_titles().await?;
+    trader.sync_all_sales().await?;
+    trader.sync_all_sales().await?;
+
+    let sales = trader.db.get_sales(&game_title(TITLE)).await?;
+    assert_eq!(sales.len(), 500);
+
+    // A sale made in the same second as the latest stored one
+    let latest = sales.last().unwrap().date;
+    mock.state().sales.push(MockSale {
+        game_id: CSGO_GAME_ID.to_string(),
+        title: TITLE.to_string(),
+        price: "11.5".to_string(),
+        date: latest.unix_timestamp() as u64,
+    });
+    trader.sync_all_sales().await?;
+
+    let sales = trader.db.get_sales(&game_title(TITLE)).await?;
+    assert_eq!(sales.len(), 501);
+    assert!(sales
+        .iter()
+        .any(|sale| sale.price == Money::from_cents(1150)));
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn flip_buys_only_profitable_offers(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let (cheap, _) = {
+        let mut state = mock.state();
+        (
+            state.list(CSGO_GAME_ID, TITLE, 700),
+            state.list(CSGO_GAME_ID, OTHER_TITLE, 950),
+        )
+    };
+    trader.sync().await?;
+
+    trader.flip().await?;
+
+    let state = mock.state();
+    assert_eq!(state.purchases, vec![cheap]);
+    assert_eq!(state.balance, BALANCE - 700);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn flip_skips_offers_above_the_steam_reference(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool.clone()).await;
+    let other = {
+        let mut state = mock.state();
+        state.list(CSGO_GAME_ID, TITLE, 700);
+        state.list(CSGO_GAME_ID, OTHER_TITLE, 700)
+    };
+    trader.sync().await?;
+
+    let catalog = Catalog::from_pool(pool);
+    catalog.sync().await?;
+    for (title, median) in [(TITLE, 650), (OTHER_TITLE, 2000)] {
+        let item = catalog
+            .by_dmarket_title(CSGO_GAME_ID, title)
+            .await?
+            .expect("title is in the catalog");
+        let reference = ReferencePrice {
+            median_price: Money::from_cents(median),
+            lowest_price: None,
+            daily_volume: Some(50),
+            monthly_volume: Some(1500),
+            fetched_at: OffsetDateTime::now_utc(),
+        };
+        catalog.store_reference_price(item.id, &reference).await?;
+    }
+
+    trader.flip().await?;
+
+    assert_eq!(mock.state().purchases, vec![other]);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn flip_prefers_liquid_titles_over_bigger_profits(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let liquid = {
+        let mut state = mock.state();
+        // A rare title worth more, selling only twice a day
+        state.sales.retain(|sale| sale.title != OTHER_TITLE);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let rare_sales = trending_prices(12.0).zip(0..).map(|(price, i)| MockSale {
+            game_id: CSGO_GAME_ID.to_string(),
+            title: OTHER_TITLE.to_string(),
+            price: format!("{price:.2}"),
+            date: now - i * 12 * 3600,
+        });
+        state.sales.extend(rare_sales);
+        state.list(CSGO_GAME_ID, OTHER_TITLE, 700);
+        state.list(CSGO_GAME_ID, TITLE, 700)
+    };
+    trader.sync().await?;
+
+    let rare = trader
+        .db
+        .get_price_statistics(&game_title(OTHER_TITLE))
+        .await?
+        .unwrap();
+    // Still enough sales to be worth buying on its own
+    assert!((60..100).contains(&rare.monthly_sales.unwrap()));
+    assert!(rare.price_volatility.unwrap() > 0.0);
+
+    // Enough for one of the offers
+    let account = TradingAccount {
+        daily_spend_limit: Some(Money::from_cents(800)),
+        ..trader.account.clone()
+    };
+    let limited = Trader { account, ..trader };
+    limited.flip().await?;
+
+    assert_eq!(mock.state().purchases, vec![liquid]);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn fee_schedules_follow_the_synced_fees(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let reduced_fee = |title: &str, expires_at| MockFee {
+        game_id: CSGO_GAME_ID.to_string(),
+        title: title.to_string(),
+        fraction: "0.02".to_string(),
+        expires_at,
+        min_price: 500,
+        max_price: 2000,
+    };
+    {
+        let mut state = mock.state();
+        state.default_fee = "0.08".to_string();
+        state.fees = vec![
+            reduced_fee(TITLE, now + 3600),
+            reduced_fee(OTHER_TITLE, now - 1),
+        ];
+    }
+    trader.sync_fees().await?;
+
+    let fees = trader.fee_schedule(&game_title(TITLE)).await?;
+    assert_eq!(fees.fee(Money::from_cents(1000)), Money::from_cents(20));
+    assert_eq!(fees.fee(Money::from_cents(3000)), Money::from_cents(240));
+    let fees = trader.fee_schedule(&game_title(OTHER_TITLE)).await?;
+    assert_eq!(fees.fee(Money::from_cents(1000)), Money::from_cents(80));
+
+    // Reduced fees the market no longer reports are dropped
+    mock.state().fees.clear();
+    trader.sync_fees().await?;
+    let fees = trader.fee_schedule(&game_title(TITLE)).await?;
+    assert_eq!(fees.fee(Money::from_cents(1000)), Money::from_cents(80));
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn ambiguous_purchases_are_reconciled(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let offers = {
+        let mut state = mock.state();
+        [
+            state.list(CSGO_GAME_ID, TITLE, 700),
+            state.list(CSGO_GAME_ID, OTHER_TITLE, 720),
+        ]
+    };
+    trader.sync().await?;
+
+    // Both offers go in one order, which never reaches the market
+    mock.state()
+        .fail_next("/exchange/v1/offers-buy", StatusCode::BAD_GATEWAY);
+    trader.flip().await?;
+
+    assert!(mock.state().purchases.is_empty());
+    for offer in &offers {
+        let purchase = trader.db.get_purchase(offer.offer_id).await?.unwrap();
+        assert_eq!(purchase.status, PurchaseStatus::Failed);
+    }
+
+    // The next order goes through but its response is lost
+    mock.state()
+        .lose_next_response("/exchange/v1/offers-buy", StatusCode::BAD_GATEWAY);
+    trader.flip().await?;
+
+    assert_eq!(mock.state().purchases.len(), 2);
+    for offer in &offers {
+        let purchase = trader.db.get_purchase(offer.offer_id).await?.unwrap();
+        assert_eq!(purchase.status, PurchaseStatus::Confirmed);
+    }
+    assert_eq!(mock.state().request_count("/exchange/v1/offers-buy"), 2);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn purchases_in_transfer_stay_pending_until_resolved(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool.clone()).await;
+    let (listed, lost) = {
+        let mut state = mock.state();
+        (
+            state.list(CSGO_GAME_ID, TITLE, 700),
+            state.list(CSGO_GAME_ID, OTHER_TITLE, 720),
+        )
+    };
+    trader.sync().await?;
+
+    // Both purchases go through, but the items take a while to arrive
+    {
+        let mut state = mock.state();
+        state.hold_deliveries = true;
+        state.lose_next_response("/exchange/v1/offers-buy", StatusCode::BAD_GATEWAY);
+    }
+    trader.flip().await?;
+    for offer in [&listed, &lost] {
+        let purchase = trader.db.get_purchase(offer.offer_id).await?.unwrap();
+        assert_eq!(purchase.status, PurchaseStatus::Pending);
+    }
+
+    // One item arrives and is listed before the purchase is reconciled, the other never arrives
+    {
+        let mut state = mock.state();
+        state
+            .in_transfer
+            .retain(|item| item.item_id == listed.item_id);
+        state.deliver();
+    }
+    trader
+        .client
+        .create_offer(listed.item_id, Money::from_cents(900))
+        .await?;
+    trader.reconcile_pending_purchases().await?;
+
+    assert!(mock.state().inventory.is_empty());
+    let purchase = trader.db.get_purchase(listed.offer_id).await?.unwrap();
+    assert_eq!(purchase.status, PurchaseStatus::Confirmed);
+    let purchase = trader.db.get_purchase(lost.offer_id).await?.unwrap();
+    assert_eq!(purchase.status, PurchaseStatus::Pending);
+
+    sqlx::query(
+        "UPDATE dmarket_purchases SET created_at = now() - INTERVAL '2 days' WHERE offer_id = $1",
+    )
+    .bind(lost.offer_id)
+    .execute(&pool)
+    .await?;
+    trader.reconcile_pending_purchases().await?;
+    let purchase = trader.db.get_purchase(lost.offer_id).await?.unwrap();
+    assert_eq!(purchase.status, PurchaseStatus::Failed);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn manual_purchases_return_what_was_bought(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let offer = mock.state().list(CSGO_GAME_ID, TITLE, 700);
+    trader.sync().await?;
+    let game_title = trader.db.get_game_title(TITLE.to_string()).await?.unwrap();
+
+    let planned = trader
+        .plan_purchase(game_title.clone(), Money::from_cents(700))
+        .await?
+        .unwrap();
+    assert_eq!(planned.offer_id, offer.offer_id);
+    assert!(trader
+        .buy_game_title(game_title.clone(), Money::from_cents(699))
+        .await?
+        .is_none());
+    assert!(mock.state().purchases.is_empty());
+
+    let bought = trader
+        .buy_game_title(game_title, Money::from_cents(700))
+        .await?
+        .unwrap();
+    assert_eq!(bought.offer_id, offer.offer_id);
+    assert_eq!(bought.price, Money::from_cents(700));
+    assert_eq!(mock.state().purchases, vec![offer]);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn flip_buys_in_one_order_and_records_partial_fills(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let (cheap, dear) = {
+        let mut state = mock.state();
+        (
+            state.list(CSGO_GAME_ID, TITLE, 700),
+            state.list(CSGO_GAME_ID, OTHER_TITLE, 750),
+        )
+    };
+    trader.sync().await?;
+
+    // The offer with the most profit goes first, the other no longer fits the balance by the
+    // time the market gets to it
+    mock.state().balance = 1000;
+    trader.flip().await?;
+
+    {
+        let state = mock.state();
+        assert_eq!(state.purchases, vec![cheap.clone()]);
+        assert_eq!(state.request_count("/exchange/v1/offers-buy"), 1);
+        // The balance is synced once after the order, not after every purchase
+        assert_eq!(state.request_count("/account/v1/balance"), 2);
+    }
+
+    let purchase = trader.db.get_purchase(cheap.offer_id).await?.unwrap();
+    assert_eq!(purchase.status, PurchaseStatus::Confirmed);
+    let purchase = trader.db.get_purchase(dear.offer_id).await?.unwrap();
+    assert_eq!(purchase.status, PurchaseStatus::Failed);
+    assert_eq!(
+        trader.db.get_balance(trader.account.id).await?,
+        Money::from_cents(300)
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn flip_spends_a_limited_budget_on_the_most_profit(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let best = {
+        let mut state = mock.state();
+        state.list(CSGO_GAME_ID, OTHER_TITLE, 750);
+        state.list(CSGO_GAME_ID, TITLE, 600)
+    };
+    trader.sync().await?;
+
+    // Enough for one of the offers, within the daily spend limit
+    let account = TradingAccount {
+        daily_spend_limit: Some(Money::from_cents(800)),
+        ..trader.account.clone()
+    };
+    let limited = Trader { account, ..trader };
+    limited.flip().await?;
+
+    assert_eq!(mock.state().purchases, vec![best]);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn targets_are_created_and_deleted(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    mock.state().list(CSGO_GAME_ID, TITLE, 1200);
+    trader.sync().await?;
+
+    trader.create_targets().await?;
+    {
+        let state = mock.state();
+        assert_eq!(state.targets.len(), 1);
+        assert_eq!(state.targets[0].title, TITLE);
+        assert_eq!(state.targets[0].price, 7.5);
+    }
+
+    trader.delete_targets().await?;
+    assert!(mock.state().targets.is_empty());
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn targets_are_synced_incrementally(pool: PgPool) -> Result<()> {
+    const CREATE: &str = "/marketplace-api/v1/user-targets/create";
+    const DELETE: &str = "/marketplace-api/v1/user-targets/delete";
+
+    let (mock, trader) = setup(pool).await;
+    mock.state().list(CSGO_GAME_ID, TITLE, 1200);
+    trader.sync().await?;
+
+    let kept = mock.state().add_target(CSGO_GAME_ID, TITLE, 7.5);
+    mock.state().add_target(CSGO_GAME_ID, TITLE, 7.5);
+    mock.state().add_target(CSGO_GAME_ID, "Stale title", 1.0);
+
+    let summary = trader.sync_targets().await?;
+    assert_eq!((summary.succeeded, summary.failed.len()), (2, 0));
+    {
+        let state = mock.state();
+        assert_eq!(state.targets.len(), 1);
+        assert_eq!(state.targets[0].target_id, kept);
+        assert_eq!(state.request_count(CREATE), 0);
+    }
+
+    mock.state().targets[0].price = 7.0;
+    trader.sync_targets().await?;
+    {
+        let state = mock.state();
+        assert_eq!(state.targets.len(), 1);
+        assert_eq!(state.targets[0].price, 7.5);
+        assert_ne!(state.targets[0].target_id, kept);
+    }
+
+    trader.sync_targets().await?;
+    let state = mock.state();
+    assert_eq!(state.request_count(CREATE), 1);
+    assert_eq!(state.request_count(DELETE), 2);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn targets_outbid_competitors_within_margin(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    mock.state().list(CSGO_GAME_ID, TITLE, 1200);
+    trader.sync().await?;
+    let bid = |price| MockBid {
+        game_id: CSGO_GAME_ID.to_string(),
+        title: TITLE.to_string(),
+        price,
+        amount: 2,
+    };
+
+    mock.state().bids = vec![bid(650), bid(700)];
+    trader.sync_targets().await?;
+    assert_eq!(mock.state().targets[0].price, 7.01);
+
+    // Already on top, so the target keeps its place
+    let target_id = mock.state().targets[0].target_id;
+    trader.sync_targets().await?;
+    assert_eq!(mock.state().targets[0].target_id, target_id);
+
+    mock.state().bids = vec![bid(720)];
+    trader.sync_targets().await?;
+    assert_eq!(mock.state().targets[0].price, 7.21);
+
+    // Still on top when the competitors back off, but no longer paying more than it takes
+    mock.state().bids = vec![bid(600)];
+    trader.sync_targets().await?;
+    assert_eq!(mock.state().targets.len(), 1);
+    assert_eq!(mock.state().targets[0].price, 6.01);
+
+    // Outbidding would cost more than the margin allows
+    mock.state().bids = vec![bid(800)];
+    trader.sync_targets().await?;
+    assert!(mock.state().targets.is_empty());
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn inventory_is_listed_and_offers_follow_competitors(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    mock.state().add_inventory(CSGO_GAME_ID, TITLE);
+    mock.state().list(CSGO_GAME_ID, TITLE, 2000);
+    trader.sync().await?;
+
+    let summary = trader.list_inventory().await?;
+    assert_eq!((summary.succeeded, summary.corrected), (1, 0));
+    assert_eq!(mock.state().offers[0].price, 19.99);
+
+    mock.state().list(CSGO_GAME_ID, TITLE, 1600);
+    trader.update_offers().await?;
+    assert_eq!(mock.state().offers[0].price, 15.99);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn rejected_offer_prices_are_corrected(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    {
+        let mut state = mock.state();
+        state.add_inventory(CSGO_GAME_ID, TITLE);
+        state.add_inventory(CSGO_GAME_ID, TITLE);
+        state.list(CSGO_GAME_ID, TITLE, 2000);
+        state.max_offer_price = Some(12.5);
+    }
+    trader.sync().await?;
+
+    let summary = trader.list_inventory().await?;
+
+    assert_eq!((summary.succeeded, summary.corrected), (2, 2));
+    assert!(summary.failed.is_empty());
+    let state = mock.state();
+    assert!(state.offers.iter().all(|o| o.price == 12.5));
+    assert_eq!(
+        state.request_count("/marketplace-api/v1/user-offers/create"),
+        2
+    );
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn rejected_offer_prices_are_not_corrected_below_cost(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    let bought = mock.state().list(CSGO_GAME_ID, TITLE, 1200);
+    trader.sync().await?;
+    let game_title = trader.db.get_game_title(TITLE.to_string()).await?.unwrap();
+    trader
+        .buy_game_title(game_title, Money::from_cents(1200))
+        .await?
+        .unwrap();
+    {
+        let mut state = mock.state();
+        state.add_inventory(CSGO_GAME_ID, TITLE);
+        state.list(CSGO_GAME_ID, TITLE, 2000);
+        state.max_offer_price = Some(12.5);
+    }
+    trader.sync().await?;
+
+    // After the fee $12.50 doesn't earn back the $12 we paid, so only the item we didn't buy
+    // is listed for it
+    let summary = trader.list_inventory().await?;
+
+    assert_eq!((summary.succeeded, summary.corrected), (1, 1));
+    assert_eq!(summary.failed.len(), 1);
+    let state = mock.state();
+    assert_eq!(state.offers.len(), 1);
+    assert_ne!(state.offers[0].item.item_id, bought.item_id);
+    assert_eq!(state.offers[0].price, 12.5);
+    Ok(())
+}
+
+#[test]
+fn batch_failures_are_classified() {
+    let error = |code: &str, message: &str| MarketError {
+        code: code.to_string(),
+        message: message.to_string(),
+    };
+
+    assert_eq!(
+        BatchFailure::from(&error(
+            "BadRequest",
+            "Price must be between $0.02 and $100.00"
+        )),
+        BatchFailure::PriceOutOfRange {
+            min: Some(0.02),
+            max: Some(100.0)
+        }
+    );
+    assert_eq!(
+        BatchFailure::from(&error("ItemLocked", "Item is locked for trading")),
+        BatchFailure::ItemLocked
+    );
+    assert_eq!(
+        BatchFailure::from(&error("BadRequest", "Insufficient funds")),
+        BatchFailure::InsufficientFunds
+    );
+}
+
+#[tokio::test]
+async fn requests_with_foreign_keys_are_rejected() -> Result<()> {
+    let mock = MockServer::start().await;
+    let secret = [1; 32];
+    let public = ed25519_dalek::SigningKey::from_bytes(&secret).verifying_key();
+    let signer = Signer::new(&hex::encode(public.to_bytes()), &hex::encode(secret))?;
+
+    let result = Client::with_base_url(&mock.url(), signer)?
+        .get_balance()
+        .await;
+
+    assert!(result.is_err());
+    assert!(mock.client().get_balance().await.is_ok());
+    Ok(())
+}
+
+#[test]
+fn signer_rejects_mismatched_key_pair() {
+    let secret = hex::encode([1; 32]);
+    let other_public = hex::encode(
+        ed25519_dalek::SigningKey::from_bytes(&[2; 32])
+            .verifying_key()
+            .to_bytes(),
+    );
+
+    assert!(Signer::new(&other_public, &secret).is_err());
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn scheduled_jobs_retry_failures_and_never_overlap(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool).await;
+    mock.state()
+        .fail_next("/account/v1/balance", StatusCode::BAD_REQUEST);
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let scheduler = Scheduler::new(trader.clone())
+        .await?
+        .with_retry_policy(RetryPolicy {
+            base_delay: Duration::from_millis(10),
+            ..Default::default()
+        });
+    scheduler
+        .run_once(
+            "balance",
+            |trader| async move { trader.sync_balance().await },
+        )
+        .await?;
+    let counter = runs.clone();
+    scheduler
+        .schedule_task("slow", "* * * * * *", move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            }
+        })
+        .await?;
+    scheduler.start().await?;
+
+    mock.wait_until(|s| s.request_count("/account/v1/balance") == 2)
+        .await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(
+        trader.db.get_balance(DEFAULT_ACCOUNT_ID).await?,
+        Money::from_cents(BALANCE)
+    );
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn accounts_respect_limits_and_skip_each_others_offers(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool.clone()).await;
+    let second_owner = Uuid::from_u128(7);
+    sqlx::query(
+        "INSERT INTO trading_account (market, name, credentials, owner_id, max_purchase_price)
+         VALUES ('dmarket', 'second', 'DMARKET_SECOND', $1, 710)",
+    )
+    .bind(second_owner)
+    .execute(&pool)
+    .await?;
+    let other = {
+        let mut state = mock.state();
+        state.list(CSGO_GAME_ID, TITLE, 650);
+        state.market.last_mut().unwrap().owner = second_owner;
+        state.list(CSGO_GAME_ID, TITLE, 700);
+        state.list(CSGO_GAME_ID, OTHER_TITLE, 720)
+    };
+    trader.sync().await?;
+
+    // The second account neither buys its own offer nor breaks its price limit
+    let mut account = trader.db.get_trading_accounts().await?.pop().unwrap();
+    assert_eq!(account.name, "second");
+    let limited = Trader {
+        account: account.clone(),
+        ..trader.clone()
+    };
+    limited.sync_balance().await?;
+    limited.flip().await?;
+    assert!(mock.state().purchases.is_empty());
+
+    account.max_purchase_price = None;
+    let unlimited = Trader {
+        account: account.clone(),
+        ..trader.clone()
+    };
+    unlimited.flip().await?;
+
+    assert_eq!(mock.state().purchases, vec![other.clone()]);
+    let purchase = trader.db.get_purchase(other.offer_id).await?.unwrap();
+    assert_eq!(purchase.account_id, account.id);
+    assert_eq!(
+        trader.db.get_balance(account.id).await?,
+        Money::from_cents(BALANCE - 720)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn sampled_responses_match_the_structs() -> Result<()> {
+    let mock = MockServer::start().await;
+    {
+        let mut state = mock.state();
+        state.list(CSGO_GAME_ID, TITLE, 1000);
+        state.add_inventory(CSGO_GAME_ID, TITLE);
+        state.add_sales(CSGO_GAME_ID, TITLE, trending_prices(10.0));
+    }
+
+    let samples = mock.client().sample_responses(&game_title(TITLE)).await?;
+
+    assert!(samples.iter().any(|sample| !sample.objects.is_empty()));
+    for sample in samples {
+        assert!(sample.errors.is_empty(), "{sample:?}");
+    }
+    Ok(())
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn preflight_reports_misconfigured_accounts(pool: PgPool) -> Result<()> {
+    let (mock, trader) = setup(pool.clone()).await;
+    let (api_key, secret_key) = MockServer::key_pair();
+    std::env::set_var("DMARKET_API_KEY", api_key);
+    std::env::set_var("DMARKET_SECRET_KEY", secret_key);
+    trader.sync_balance().await?;
+
+    let mut report = Report::default();
+    preflight::check(&mut report, &trader.db, &mock.url()).await;
+    assert!(report.passed(), "{report}");
+
+    sqlx::query(
+        "INSERT INTO trading_account (market, name, credentials)
+         VALUES ('dmarket', 'unconfigured', 'DMARKET_UNCONFIGURED')",
+    )
+    .execute(&pool)
+    .await?;
+    let mut report = Report::default();
+    preflight::check(&mut report, &trader.db, &mock.url()).await;
+
+    let failed: Vec<_> = report
+        .checks
+        .iter()
+        .filter(|check| check.outcome.is_err())
+        .map(|check| check.name.as_str())
+        .collect();
+    assert_eq!(
+        failed,
+        [
+            "unconfigured: authentication",
+            "unconfigured: dmarket_account row"
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use dmarket::client::is_idempotent;
+use reqwest::Method;
+
+#[test]
+fn creating_and_buying_are_not_idempotent() {
+    let not_idempotent = [
+        (Method::PATCH, "/exchange/v1/offers-buy"),
+        (Method::POST, "/marketplace-api/v1/user-targets/create"),
+        (Method::POST, "/marketplace-api/v1/user-offers/create"),
+    ];
+    for (method, path) in not_idempotent {
+        assert!(!is_idempotent(&method, path), "{method} {path}");
+    }
+
+    let idempotent = [
+        (Method::GET, "/exchange/v1/market/items"),
+        (Method::DELETE, "/exchange/v1/offers"),
+        (Method::POST, "/marketplace-api/v1/user-targets/delete"),
+        (Method::POST, "/marketplace-api/v1/user-offers/edit"),
+    ];
+    for (method, path) in idempotent {
+        assert!(is_idempotent(&method, path), "{method} {path}");
+    }
+}
@@ -0,0 +1,12 @@
+CREATE TYPE purchase_status AS ENUM ('pending', 'confirmed', 'failed');
+
+CREATE TABLE Purchase (
+    item_id     INTEGER PRIMARY KEY,
+    skin_id     INTEGER NOT NULL,
+    price       DOUBLE PRECISION NOT NULL,
+    status      purchase_status NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_Purchase_status ON Purchase(status);
@@ -0,0 +1,12 @@
+CREATE TABLE dmarket_purchases (
+    offer_id   UUID PRIMARY KEY,
+    item_id    UUID NOT NULL,
+    game_id    TEXT NOT NULL,
+    title      TEXT NOT NULL,
+    price      BIGINT NOT NULL,
+    status     purchase_status NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_dmarket_purchases_status ON dmarket_purchases(status);
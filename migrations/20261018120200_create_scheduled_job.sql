@@ -0,0 +1,19 @@
+CREATE TYPE job_outcome AS ENUM ('running', 'succeeded', 'failed');
+
+CREATE TABLE ScheduledJob (
+    name         TEXT PRIMARY KEY,
+    last_run_at  TIMESTAMP WITH TIME ZONE,
+    last_outcome job_outcome,
+    next_run_at  TIMESTAMP WITH TIME ZONE
+);
+
+CREATE TABLE ScheduledJobRun (
+    id          SERIAL PRIMARY KEY,
+    job_name    TEXT NOT NULL REFERENCES ScheduledJob(name),
+    started_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    finished_at TIMESTAMP WITH TIME ZONE,
+    outcome     job_outcome NOT NULL DEFAULT 'running',
+    error       TEXT
+);
+
+CREATE INDEX idx_ScheduledJobRun_job_name ON ScheduledJobRun(job_name, started_at);
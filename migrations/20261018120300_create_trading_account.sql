@@ -0,0 +1,41 @@
+-- Accounts we trade with. Limits are in the market's own price units (thousandths of a dollar on
+-- BitSkins, cents on DMarket).
+CREATE TABLE trading_account (
+    id                 SERIAL PRIMARY KEY,
+    market             TEXT NOT NULL CHECK (market IN ('bitskins', 'dmarket')),
+    name               TEXT NOT NULL,
+    -- Names the credentials of the account, never the secrets themselves: the environment
+    -- variable holding the API key on BitSkins, the prefix of the key variables on DMarket
+    credentials        TEXT NOT NULL,
+    -- The account's user ID on the market, to recognise our own listings
+    owner_id           UUID,
+    max_purchase_price DOUBLE PRECISION,
+    daily_spend_limit  DOUBLE PRECISION,
+    enabled            BOOLEAN NOT NULL DEFAULT TRUE,
+    UNIQUE (market, name)
+);
+
+-- The accounts used so far, configured through the global environment variables
+INSERT INTO trading_account (id, market, name, credentials, owner_id) VALUES
+    (1, 'bitskins', 'default', 'BITSKIN_API_KEY', NULL),
+    (2, 'dmarket', 'default', 'DMARKET', 'aa749fbf-e726-46db-9419-5a2f384a896e');
+SELECT setval(pg_get_serial_sequence('trading_account', 'id'), 2);
+
+ALTER TABLE Account ADD COLUMN account_id INTEGER REFERENCES trading_account(id);
+UPDATE Account SET account_id = 1;
+ALTER TABLE Account DROP COLUMN id;
+ALTER TABLE Account ADD PRIMARY KEY (account_id);
+
+ALTER TABLE Offer ADD COLUMN account_id INTEGER NOT NULL DEFAULT 1 REFERENCES trading_account(id);
+ALTER TABLE Offer ALTER COLUMN account_id DROP DEFAULT;
+
+ALTER TABLE Purchase ADD COLUMN account_id INTEGER NOT NULL DEFAULT 1 REFERENCES trading_account(id);
+ALTER TABLE Purchase ALTER COLUMN account_id DROP DEFAULT;
+
+ALTER TABLE dmarket_account ADD COLUMN account_id INTEGER REFERENCES trading_account(id);
+UPDATE dmarket_account SET account_id = 2;
+ALTER TABLE dmarket_account DROP COLUMN id;
+ALTER TABLE dmarket_account ADD PRIMARY KEY (account_id);
+
+ALTER TABLE dmarket_purchases ADD COLUMN account_id INTEGER NOT NULL DEFAULT 2 REFERENCES trading_account(id);
+ALTER TABLE dmarket_purchases ALTER COLUMN account_id DROP DEFAULT;
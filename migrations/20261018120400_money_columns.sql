@@ -0,0 +1,17 @@
+-- Money is stored as BIGINT thousandths of a dollar (mills) in every table. BitSkins amounts
+-- already were mills, DMarket amounts were cents.
+ALTER TABLE Account ALTER COLUMN balance TYPE BIGINT USING round(balance);
+ALTER TABLE Purchase ALTER COLUMN price TYPE BIGINT USING round(price);
+ALTER TABLE MarketItem ALTER COLUMN price TYPE BIGINT USING round(price);
+ALTER TABLE Sale ALTER COLUMN price TYPE BIGINT USING round(price);
+
+ALTER TABLE dmarket_account ALTER COLUMN balance TYPE BIGINT USING balance * 10;
+UPDATE dmarket_purchases SET price = price * 10;
+
+ALTER TABLE trading_account
+    ALTER COLUMN max_purchase_price TYPE BIGINT USING round(
+        CASE market WHEN 'dmarket' THEN max_purchase_price * 10 ELSE max_purchase_price END
+    ),
+    ALTER COLUMN daily_spend_limit TYPE BIGINT USING round(
+        CASE market WHEN 'dmarket' THEN daily_spend_limit * 10 ELSE daily_spend_limit END
+    );
@@ -0,0 +1,10 @@
+-- Default fee of each game, synced from the market along with the reduced fees.
+CREATE TABLE dmarket_default_fees (
+    game_id TEXT PRIMARY KEY,
+    fraction TEXT NOT NULL,
+    min_fee BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Price ranges of reduced fees were cents, they are mills like every other amount.
+UPDATE dmarket_reduced_fees SET min_price = min_price * 10, max_price = max_price * 10;
@@ -0,0 +1,23 @@
+-- Sale prices become BIGINT mills like every other amount and dates become timestamps, instead
+-- of the text the API sends them as.
+ALTER TABLE dmarket_sales
+    ALTER COLUMN price TYPE BIGINT USING round(price::NUMERIC * 1000),
+    ALTER COLUMN date TYPE TIMESTAMPTZ USING to_timestamp(date::BIGINT);
+
+-- Overlapping fetches may have stored the same sale more than once. The API has no sale IDs, so
+-- a sale is identified by what it reports about it.
+DELETE FROM dmarket_sales a
+USING dmarket_sales b
+WHERE a.id > b.id
+  AND a.game_id = b.game_id
+  AND a.title = b.title
+  AND a.date = b.date
+  AND a.price = b.price
+  AND a.tx_operation_type = b.tx_operation_type;
+
+ALTER TABLE dmarket_sales
+    ADD CONSTRAINT dmarket_sales_natural_key
+    UNIQUE (game_id, title, date, price, tx_operation_type);
+
+-- Covered by the unique constraint
+DROP INDEX idx_dmarket_sales_game_title;
@@ -0,0 +1,17 @@
+-- Canonical items, each linked to the BitSkins skin and DMarket title that refer to it.
+CREATE TABLE catalog_item (
+    id SERIAL PRIMARY KEY,
+    -- Normalized name identifying the item however a market spells it
+    key TEXT NOT NULL UNIQUE,
+    base TEXT NOT NULL,
+    wear TEXT,
+    stattrak BOOLEAN NOT NULL,
+    souvenir BOOLEAN NOT NULL,
+    star BOOLEAN NOT NULL,
+    phase TEXT,
+    bitskins_skin_id INTEGER UNIQUE REFERENCES Skin (id),
+    dmarket_game_id TEXT,
+    dmarket_title TEXT,
+    UNIQUE (dmarket_game_id, dmarket_title),
+    FOREIGN KEY (dmarket_game_id, dmarket_title) REFERENCES dmarket_game_titles (game_id, title)
+);
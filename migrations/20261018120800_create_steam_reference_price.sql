@@ -0,0 +1,11 @@
+-- Steam Community Market prices of catalog items, a reference for purchases on the other markets.
+-- Prices are in mills like every other table.
+CREATE TABLE steam_reference_price (
+    catalog_item_id INTEGER PRIMARY KEY REFERENCES catalog_item (id) ON DELETE CASCADE,
+    median_price BIGINT NOT NULL,
+    lowest_price BIGINT,
+    -- Steam leaves the volume out of the overview of items that did not sell in the last day
+    daily_volume INTEGER,
+    monthly_volume INTEGER,
+    fetched_at TIMESTAMPTZ NOT NULL
+);
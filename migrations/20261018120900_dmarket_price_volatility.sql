@@ -0,0 +1,2 @@
+-- Standard deviation of the log sale prices of a title, how uncertain its price is.
+ALTER TABLE dmarket_game_titles ADD COLUMN price_volatility DOUBLE PRECISION;
@@ -0,0 +1,166 @@
+//! Detects changes in the shape of API responses before they break deserialization.
+use crate::sales::{self, Market};
+use crate::util::get_fields;
+use anyhow::{Context, Result};
+use common::drift::ResponseSample;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Fields of the objects returned by an endpoint, with their JSON types
+type Schema = BTreeMap<String, String>;
+
+/// Schemas of every sampled endpoint, keyed by sample name
+pub type Baseline = BTreeMap<String, Schema>;
+
+pub enum Change {
+    Added(String, String),
+    Removed(String, String),
+    Retyped {
+        field: String,
+        from: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Added(field, type_) => write!(f, "+ {field}: {type_}"),
+            Self::Removed(field, type_) => write!(f, "- {field}: {type_}"),
+            Self::Retyped { field, from, to } => write!(f, "~ {field}: {from} -> {to}"),
+        }
+    }
+}
+
+/// What changed in the responses of one endpoint.
+pub struct Drift {
+    pub name: String,
+    pub changes: Vec<Change>,
+    /// Deserialization errors with the number of objects each one affected
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        for change in &self.changes {
+            writeln!(f, "  {change}")?;
+        }
+        for (error, count) in &self.errors {
+            writeln!(f, "  ! {error} ({count} objects)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Samples the responses of every read-only endpoint of `market`, using `item` for the
+/// endpoints that need one, or any item with sales if none is given.
+pub async fn sample(market: Market, item: Option<&str>) -> Result<Vec<ResponseSample>> {
+    let item = match item {
+        Some(item) => item.to_string(),
+        None => sales::items_with_sales(market, 1)
+            .await?
+            .into_iter()
+            .next()
+            .context("No items with sales to sample, pass one")?,
+    };
+    Ok(match market {
+        Market::Bitskins => {
+            let api_key = bitskins::TradingAccount::default().api_key()?;
+            bitskins::HttpClient::new()
+                .with_api_key(api_key)
+                .sample_responses(item.parse().context("BitSkins items are skin IDs")?)
+                .await?
+        }
+        Market::Dmarket => {
+            let db = dmarket::Database::new().await?;
+            let game_title = db
+                .get_game_title(item.clone())
+                .await?
+                .with_context(|| format!("Unknown DMarket title {item}"))?;
+            dmarket::Client::new()?
+                .sample_responses(&game_title)
+                .await?
+        }
+    })
+}
+
+/// Infers the schema of each sample. Samples without objects are left out, as nothing can be
+/// inferred from them.
+pub fn schemas(samples: &[ResponseSample]) -> Baseline {
+    samples
+        .iter()
+        .filter(|sample| !sample.objects.is_empty())
+        .map(|sample| (sample.name.clone(), get_fields(&sample.objects)))
+        .collect()
+}
+
+/// Compares `samples` with the schemas in `baseline` and the structs they deserialize into.
+pub fn compare(samples: &[ResponseSample], baseline: &Baseline) -> Vec<Drift> {
+    let current = schemas(samples);
+    samples
+        .iter()
+        .map(|sample| {
+            let mut errors = BTreeMap::new();
+            for error in &sample.errors {
+                *errors.entry(error.clone()).or_default() += 1;
+            }
+            let changes = match (baseline.get(&sample.name), current.get(&sample.name)) {
+                (Some(old), Some(new)) => diff(old, new),
+                _ => Vec::new(),
+            };
+            Drift {
+                name: sample.name.clone(),
+                changes,
+                errors,
+            }
+        })
+        .filter(|drift| !drift.is_empty())
+        .collect()
+}
+
+fn diff(old: &Schema, new: &Schema) -> Vec<Change> {
+    let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    fields
+        .into_iter()
+        .filter_map(|field| match (old.get(field), new.get(field)) {
+            (None, Some(type_)) => Some(Change::Added(field.clone(), type_.clone())),
+            (Some(type_), None) => Some(Change::Removed(field.clone(), type_.clone())),
+            (Some(from), Some(to)) if from != to => Some(Change::Retyped {
+                field: field.clone(),
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn load_baseline(path: &Path) -> Result<Baseline> {
+    if !path.exists() {
+        log::warn!("No baseline at {path:?} yet, only checking against the structs");
+        return Ok(Baseline::new());
+    }
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Merges the schemas of `samples` into the baseline at `path`, keeping the stored schemas of
+/// endpoints that returned no objects this time.
+pub fn save_baseline(path: &Path, samples: &[ResponseSample]) -> Result<()> {
+    let mut baseline = load_baseline(path)?;
+    baseline.extend(schemas(samples));
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&baseline)?)?;
+    Ok(())
+}
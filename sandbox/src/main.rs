@@ -1,13 +1,311 @@
-use dmarket::Trader;
-use env_logger::Builder;
-use log::LevelFilter;
+//! Research toolkit for exploring the market data collected by the bots.
+mod drift;
+mod plotter;
+mod progress_bar;
+mod sales;
+mod util;
+
+use anyhow::{bail, Result};
+use catalog::{Catalog, SteamClient};
+use clap::{Parser, Subcommand, ValueEnum};
+use plotter::{Labels, PlotData};
+use progress_bar::ProgressTracker;
+use sales::Market;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Parser)]
+#[command(about = "Plots and schema discovery over the collected market data")]
+struct Cli {
+    /// Market whose database to read
+    #[arg(long, global = true, value_enum, default_value = "bitskins")]
+    market: Market,
+
+    /// Directory plots are written to
+    #[arg(long, global = true, default_value = "plots")]
+    out: PathBuf,
+
+    #[arg(long, global = true, value_enum, default_value = "png")]
+    format: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Scatter plot of float value against price, BitSkins only
+    Floats {
+        /// Skin ID on BitSkins, title on DMarket
+        item: String,
+    },
+    /// Scatter plot of sale date against price
+    Dates { item: String },
+    /// Histogram of sale prices
+    Histogram {
+        item: String,
+        /// Multiplier for the number of bins
+        #[arg(long, default_value_t = 8)]
+        precision: usize,
+    },
+    /// Histograms of sale prices for every item with enough sales
+    Histograms {
+        #[arg(long, default_value_t = 500)]
+        min_sales: i32,
+        #[arg(long, default_value_t = 8)]
+        precision: usize,
+    },
+    /// Lists the fields and their JSON types across the objects of a saved API response
+    Fields {
+        file: PathBuf,
+        /// JSON pointer to the array of objects, e.g. /objects
+        #[arg(long)]
+        pointer: Option<String>,
+    },
+    /// Compares live API responses with the structs they deserialize into and a stored baseline
+    Drift {
+        /// Item sampled by endpoints that need one, any item with sales by default
+        #[arg(long)]
+        item: Option<String>,
+        /// Baseline of the inferred schemas, schemas/<market>.json by default
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        /// Store the current schemas as the new baseline
+        #[arg(long)]
+        update: bool,
+        /// Keep sampling every this many minutes instead of exiting
+        #[arg(long)]
+        interval: Option<u64>,
+    },
+    /// Links the items of both markets, or looks one up by name
+    Catalog {
+        /// Name of the item as any market spells it
+        name: Option<String>,
+        /// Sync the catalog with the markets before the lookup
+        #[arg(long)]
+        sync: bool,
+    },
+    /// Fetches Steam reference prices of the catalog items whose prices are oldest
+    SteamPrices {
+        #[arg(long, default_value_t = 100)]
+        limit: i64,
+        /// JSON file of recorded responses to read instead of the live market
+        #[arg(long)]
+        fixtures: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Png,
+    Svg,
+}
+
+impl Cli {
+    /// Path of the plot of `kind` for `item`, creating its directory.
+    fn output_file(&self, kind: &str, item: &str) -> Result<PathBuf> {
+        let dir = self.out.join(kind);
+        fs::create_dir_all(&dir)?;
+        let name: String = item
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let extension = match self.format {
+            Format::Png => "png",
+            Format::Svg => "svg",
+        };
+        Ok(dir.join(format!("{:?}-{name}.{extension}", self.market).to_lowercase()))
+    }
+
+    /// Samples the API once, or every `interval` minutes, and reports any drift. A single run
+    /// fails if there is drift, so scheduled checks can alert on it.
+    async fn check_drift(
+        &self,
+        item: Option<&str>,
+        baseline: Option<&PathBuf>,
+        update: bool,
+        interval: Option<u64>,
+    ) -> Result<()> {
+        common::secrets::init()?;
+        let path = baseline.cloned().unwrap_or_else(|| {
+            PathBuf::from("schemas").join(format!("{:?}.json", self.market).to_lowercase())
+        });
+        let Some(minutes) = interval else {
+            let drifted = self.sample_drift(item, &path, update).await?;
+            if drifted > 0 && !update {
+                bail!("{drifted} endpoints drifted");
+            }
+            return Ok(());
+        };
+        loop {
+            if let Err(e) = self.sample_drift(item, &path, update).await {
+                log::warn!("Failed to sample the API: {e:#}");
+            }
+            tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+        }
+    }
+
+    /// Prints the drift of the current responses from the baseline at `path`, returning the
+    /// number of endpoints that drifted.
+    async fn sample_drift(&self, item: Option<&str>, path: &Path, update: bool) -> Result<usize> {
+        let samples = drift::sample(self.market, item).await?;
+        let drifts = drift::compare(&samples, &drift::load_baseline(path)?);
+        for drift in &drifts {
+            print!("{drift}");
+        }
+        if update {
+            drift::save_baseline(path, &samples)?;
+            println!("Updated {}", path.display());
+        }
+        Ok(drifts.len())
+    }
+
+    async fn plot_floats(&self, item: &str) -> Result<PathBuf> {
+        if let Market::Dmarket = self.market {
+            bail!("DMarket sales carry no float values");
+        }
+        let (floats, prices) = sales::load(self.market, item, true)
+            .await?
+            .into_iter()
+            .filter_map(|sale| Some((sale.float_value?, sale.price)))
+            .unzip();
+        let data = PlotData::new(floats, prices)?;
+        let path = self.output_file("floats", item)?;
+        plotter::plot_scatter(
+            &data,
+            &path,
+            &Labels {
+                title: &format!("Float vs price for {item}"),
+                x: "Float",
+                y: "Price ($)",
+                x_format: &|float| format!("{float:.3}"),
+            },
+        )?;
+        Ok(path)
+    }
+
+    async fn plot_dates(&self, item: &str) -> Result<PathBuf> {
+        let sales = sales::load(self.market, item, true).await?;
+        let data = PlotData::new(
+            sales
+                .iter()
+                .map(|sale| sale.time.unix_timestamp() as f64 / SECONDS_PER_DAY)
+                .collect(),
+            sales.iter().map(|sale| sale.price).collect(),
+        )?;
+        let path = self.output_file("dates", item)?;
+        plotter::plot_scatter(
+            &data,
+            &path,
+            &Labels {
+                title: &format!("Date vs price for {item}"),
+                x: "Date",
+                y: "Price ($)",
+                x_format: &|days| {
+                    OffsetDateTime::from_unix_timestamp((days * SECONDS_PER_DAY) as i64)
+                        .map(|time| time.date().to_string())
+                        .unwrap_or_default()
+                },
+            },
+        )?;
+        Ok(path)
+    }
+
+    async fn plot_histogram(&self, item: &str, precision: usize) -> Result<PathBuf> {
+        let prices: Vec<f64> = sales::load(self.market, item, false)
+            .await?
+            .into_iter()
+            .map(|sale| sale.price)
+            .collect();
+        let path = self.output_file("hist", item)?;
+        plotter::plot_histogram(&prices, &path, &format!("Prices of {item}"), precision)?;
+        Ok(path)
+    }
+
+    async fn plot_histograms(&self, min_sales: i32, precision: usize) -> Result<()> {
+        let items = sales::items_with_sales(self.market, min_sales).await?;
+        let progress = ProgressTracker::new(
+            items.len() as u64,
+            "[{elapsed_precise}] {bar:40} {pos}/{len} {msg}",
+        );
+        let mut failed = 0;
+        for item in &items {
+            if let Err(e) = self.plot_histogram(item, precision).await {
+                log::warn!("Skipping {item}: {e}");
+                failed += 1;
+            }
+            progress.increment().await;
+        }
+        progress
+            .finish(format!("{} plotted, {failed} failed", items.len() - failed))
+            .await;
+        Ok(())
+    }
+}
+
+async fn show_catalog(name: Option<&str>, sync: bool) -> Result<()> {
+    let catalog = Catalog::new().await?;
+    if sync || name.is_none() {
+        let summary = catalog.sync().await?;
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    }
+    if let Some(name) = name {
+        match catalog.find(name).await? {
+            Some(item) => println!("{}", serde_json::to_string_pretty(&item)?),
+            None => bail!("{name} is not in the catalog"),
+        }
+    }
+    Ok(())
+}
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    dotenvy::dotenv().ok();
-    Builder::new().filter_level(LevelFilter::Info).init();
+async fn main() -> Result<()> {
+    common::setup_env();
+    let cli = Cli::parse();
 
-    let trader = Trader::new().await?;
-    trader.sync().await?;
+    let path = match &cli.command {
+        Command::Floats { item } => cli.plot_floats(item).await?,
+        Command::Dates { item } => cli.plot_dates(item).await?,
+        Command::Histogram { item, precision } => cli.plot_histogram(item, *precision).await?,
+        Command::Histograms {
+            min_sales,
+            precision,
+        } => return cli.plot_histograms(*min_sales, *precision).await,
+        Command::Fields { file, pointer } => {
+            let objects = util::load_objects(file, pointer.as_deref())?;
+            for (field, type_) in util::get_fields(&objects) {
+                println!("{field}: {type_}");
+            }
+            return Ok(());
+        }
+        Command::Drift {
+            item,
+            baseline,
+            update,
+            interval,
+        } => {
+            return cli
+                .check_drift(item.as_deref(), baseline.as_ref(), *update, *interval)
+                .await
+        }
+        Command::Catalog { name, sync } => return show_catalog(name.as_deref(), *sync).await,
+        Command::SteamPrices { limit, fixtures } => {
+            let client = match fixtures {
+                Some(path) => SteamClient::from_fixtures(path)?,
+                None => SteamClient::new(),
+            };
+            let stored = Catalog::new()
+                .await?
+                .sync_steam_prices(&client, *limit)
+                .await?;
+            println!("Stored {stored} reference prices");
+            return Ok(());
+        }
+    };
+    println!("Wrote {}", path.display());
     Ok(())
 }
@@ -0,0 +1,87 @@
+//! Sales of a single item from either market, in a common shape.
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use time::OffsetDateTime;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Market {
+    Bitskins,
+    Dmarket,
+}
+
+pub struct SalePoint {
+    pub time: OffsetDateTime,
+    /// Price in dollars
+    pub price: f64,
+    pub float_value: Option<f64>,
+}
+
+/// Loads the sales of `item`: a skin ID on BitSkins, a title on DMarket.
+///
+/// With `clean`, BitSkins sales with stickers, phases or other extras, which distort the price,
+/// are left out.
+pub async fn load(market: Market, item: &str, clean: bool) -> Result<Vec<SalePoint>> {
+    let sales: Vec<_> = match market {
+        Market::Bitskins => {
+            let db = bitskins::Database::new().await?;
+            let skin_id = item.parse().context("BitSkins items are skin IDs")?;
+            let sales = if clean {
+                db.get_sales_without_bullshit(skin_id).await?
+            } else {
+                db.get_sales_by_skin_id(skin_id).await?
+            };
+            sales
+                .into_iter()
+                .map(|sale| SalePoint {
+                    time: sale.created_at,
+                    price: sale.price.dollars(),
+                    float_value: sale.float_value,
+                })
+                .collect()
+        }
+        Market::Dmarket => {
+            let db = dmarket::Database::new().await?;
+            let game_title = db
+                .get_game_title(item.to_string())
+                .await?
+                .with_context(|| format!("Unknown DMarket title {item}"))?;
+            db.get_sales(&game_title)
+                .await?
+                .into_iter()
+                .map(|sale| SalePoint {
+                    time: sale.date,
+                    price: sale.price.dollars(),
+                    float_value: None,
+                })
+                .collect()
+        }
+    };
+    if sales.is_empty() {
+        bail!("No sales found for {item}");
+    }
+    Ok(sales)
+}
+
+/// Items with at least `min_sales` sales.
+pub async fn items_with_sales(market: Market, min_sales: i32) -> Result<Vec<String>> {
+    Ok(match market {
+        Market::Bitskins => bitskins::Database::new()
+            .await?
+            .get_skins_by_sale_count(min_sales.into())
+            .await?
+            .into_iter()
+            .map(|skin_id| skin_id.to_string())
+            .collect(),
+        Market::Dmarket => {
+            let db = dmarket::Database::new().await?;
+            let mut titles = Vec::new();
+            for game_title in db.get_distinct_titles().await? {
+                let stats = db.get_price_statistics(&game_title).await?;
+                if stats.and_then(|s| s.sale_count) >= Some(min_sales) {
+                    titles.push(game_title.title);
+                }
+            }
+            titles
+        }
+    })
+}
@@ -1,13 +1,8 @@
+use anyhow::{Context, Result};
 use serde_json::Value;
 use std::collections::BTreeMap;
-
-fn count<I, T, F>(iter: I, condition: F) -> usize
-where
-    I: IntoIterator<Item = T>,
-    F: Fn(&T) -> bool,
-{
-    iter.into_iter().filter(|item| condition(item)).count()
-}
+use std::fs;
+use std::path::Path;
 
 fn get_json_type(value: &Value) -> &'static str {
     match value {
@@ -49,3 +44,20 @@ pub(crate) fn get_fields(values: &[Value]) -> BTreeMap<String, String> {
 
     fields
 }
+
+/// Reads the array of objects at the JSON `pointer` of a saved API response, or the top-level
+/// array if no pointer is given.
+pub(crate) fn load_objects(path: &Path, pointer: Option<&str>) -> Result<Vec<Value>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    let value: Value = serde_json::from_str(&text)?;
+    let target = match pointer {
+        Some(pointer) => value
+            .pointer(pointer)
+            .with_context(|| format!("Nothing at {pointer} in {path:?}"))?,
+        None => &value,
+    };
+    target
+        .as_array()
+        .cloned()
+        .with_context(|| format!("Expected an array in {path:?}, pass --pointer to select one"))
+}